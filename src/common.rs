@@ -1,8 +1,8 @@
 use std::collections::HashMap;
 use std::fmt;
 use std::path::PathBuf;
-use std::sync::{Arc, RwLock, LazyLock};
-use std::sync::atomic::AtomicBool;
+use std::sync::{Arc, Mutex, OnceLock, RwLock, LazyLock};
+use std::sync::atomic::{AtomicBool, AtomicI64, Ordering};
 use std::sync::mpsc::Sender;
 use egui::Color32;
 use openrouter_rs::Message;
@@ -12,9 +12,7 @@ use rusqlite::types::{FromSql, FromSqlError, FromSqlResult, ToSqlOutput, ValueRe
 use secrecy::SecretString;
 use strum::{Display, EnumString};
 use thiserror::Error;
-use crate::db::{CURRENT_SANDBOX_VERSION, mk_agent};
-//use crate::openr::do_openr_chat_que;
-//use crate::ollama::{do_ollama_chat_que};
+use crate::db::{CURRENT_SANDBOX_VERSION, mk_agent, swap_agent_inds};
 use ollama_rs::generation::chat::{ChatMessage, MessageRole};
 use ollama_rs::generation::images::Image;
 
@@ -25,6 +23,55 @@ pub static THEME_COLORS: LazyLock<RwLock<AppColors>> = LazyLock::new(|| {
     RwLock::new(AppColors::default())
 });
 
+// where the Ollama server runs, set from the saved app settings
+pub static OLLAMA_HOST: LazyLock<RwLock<OllamaHost>> = LazyLock::new(|| {
+    RwLock::new(OllamaHost::default())
+});
+
+// the file set with --debug-log, every request and streamed chunk goes there
+pub static DEBUG_LOG: OnceLock<Mutex<std::fs::File>> = OnceLock::new();
+
+// the preset new agents start with, read from the sandbox. 0 for none
+pub static DEFAULT_PRESET_ID: AtomicI64 = AtomicI64::new(0);
+
+#[derive(Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize)]
+pub struct OllamaHost {
+    pub host: String, // "http://" is assumed when no scheme is given
+    pub port: u16,
+}
+
+impl Default for OllamaHost {
+    fn default() -> Self {
+        Self {
+            host: "http://127.0.0.1".to_string(),
+            port: 11434,
+        }
+    }
+}
+
+impl OllamaHost {
+    /// Checks the host and port and returns the full server url
+    pub fn url(&self) -> Result<reqwest::Url, MyError> {
+        let host = self.host.trim();
+        let invalid = |reason: String| MyError::OllamaHost(
+            format!("{}:{}", host, self.port), reason);
+        let with_scheme = if host.contains("://") {
+            host.to_string()
+        } else {
+            format!("http://{}", host)
+        };
+        let mut url = reqwest::Url::parse(&with_scheme)
+            .map_err(|e| invalid(e.to_string()))?;
+        if url.host_str().is_none() {
+            return Err(invalid("no host name".to_string()));
+        }
+        if self.port == 0 || url.set_port(Some(self.port)).is_err() {
+            return Err(invalid("invalid port".to_string()));
+        }
+        Ok(url)
+    }
+}
+
 #[derive(Clone, Copy)]
 pub struct AppColors {
     pub cloud: Color32,
@@ -71,6 +118,7 @@ pub fn router_color(router: &ChatRouter) -> Color32 {
     match router {
         ChatRouter::Ollama => local_color(),
         ChatRouter::Openrouter => cloud_color(),
+        ChatRouter::OpenAICompatible => local_color(),
     }
 }
 
@@ -81,12 +129,41 @@ pub struct Attachment {
     pub content: String,   // Raw text or base64 encoded data
 }
 
+// token usage reported by the router at the end of a stream
+#[derive(Debug, Default, Clone, Copy)]
+pub struct MsgUsage {
+    pub prompt_tokens: i64,
+    pub completion_tokens: i64,
+    pub cost: Option<f64>, // as reported by the router, if any
+    pub tokens_per_sec: Option<f64>, // measured by the server, if it can
+}
+
 // when streaming a chat, this structure is passed to the GUI
 pub enum ChatStreamEvent {
     Content(usize, String),
     Reasoning(usize, String),
-    Finished(usize),
-    Error(usize, String),
+    Usage(usize, MsgUsage),
+    Finished(usize, bool), // true if the user stopped it
+    Truncated(usize), // the reply was cut off by max_tokens
+    Warning(usize, String), // recoverable, the stream goes on
+    Error(usize, String), // fatal, the agent stops streaming
+    Title(i64, String, String), // chat id, title it replaces, new title
+}
+
+impl ChatStreamEvent {
+    /// The agent the event is about, None for the ones about the whole chat
+    pub fn agent_ind(&self) -> Option<usize> {
+        match self {
+            ChatStreamEvent::Content(ind, _)
+            | ChatStreamEvent::Reasoning(ind, _)
+            | ChatStreamEvent::Usage(ind, _)
+            | ChatStreamEvent::Finished(ind, _)
+            | ChatStreamEvent::Truncated(ind)
+            | ChatStreamEvent::Warning(ind, _)
+            | ChatStreamEvent::Error(ind, _) => Some(*ind),
+            ChatStreamEvent::Title(..) => None,
+        }
+    }
 }
 
 #[derive(Default, Clone)]
@@ -102,6 +179,11 @@ pub enum FileOp {
     OpenMerge,
     OpenMergeRight,
     ExportChat,
+    ExportPreset,
+    ImportPreset,
+    ExportSandbox,
+    ImportSandbox,
+    ExportAllChats,
 }
 
 #[derive(Default, Clone)]
@@ -128,6 +210,18 @@ pub enum MyError {
     Database(#[from] rusqlite::Error),
     #[error("Sandbox Version Mismatch: expected {0}, found: {1}")]
     SandboxVersionMismatch(i32, i32),
+    #[error("Preset Import Error: the file is not a valid preset")]
+    PresetImport,
+    #[error("Invalid Ollama host '{0}': {1}")]
+    OllamaHost(String, String),
+    #[error("Backup Error: {0}")]
+    Backup(#[from] std::io::Error),
+    #[error("Sandbox Archive Error: {0}")]
+    SandboxArchive(String),
+    #[error("Cache Database Error: {0}")]
+    CacheDb(String),
+    #[error("Read-only Sandbox Error: the Sandbox needs a schema update, open it without --readonly once")]
+    SandboxReadOnly,
 }
 
 #[derive(Default, Clone, PartialEq, serde::Deserialize, serde::Serialize)]
@@ -201,6 +295,7 @@ pub enum ChatRouter {
     #[default]
     Ollama,
     Openrouter,
+    OpenAICompatible, // any server speaking the OpenAI chat api, see base_url
 }
 
 impl FromSql for ChatRouter {
@@ -230,9 +325,47 @@ impl fmt::Display for ChatRouter {
 }
 */
 
+// requested from a message header, carried out after the chat is rendered
+#[derive(Clone, Copy, PartialEq)]
+pub enum MsgAction {
+    SaveEdit,
+    Regenerate,
+    Delete,
+    Branch,
+    TogglePin,
+    Continue,
+    CopyCurl, // only reads the chat, so it works in read-only mode too
+    SavePreset, // stores the edited preset snapshot
+}
+
 #[derive(Default, Clone)]
 pub struct ChatMsgUi {
     pub show_raw: bool,
+    pub no_wrap: bool, // raw text keeps its long lines, scrolled sideways
+    pub editing: bool,
+    pub edit_buffer: String,
+    pub action: Option<MsgAction>,
+    pub scroll_to: bool, // bring this message into view on the next frame
+    // on the first reply of a batch: its columns scroll together
+    pub sync_scroll: bool,
+    // None follows the role, see renders_markdown
+    pub render_markdown: Option<bool>,
+    pub inspecting: bool, // the window with the preset snapshot is open
+    pub preset_buffer: String, // its json being edited, filled when it opens
+    // the find bar's term, lowercased, while this message matches it
+    pub find_term: Option<String>,
+    // thought process unfolded, None until shown: follows expand_reasoning
+    pub reasoning_open: Option<bool>,
+    // what went wrong while streaming without stopping it, this session only
+    pub warnings: Vec<String>,
+}
+
+impl ChatMsgUi {
+    /// Replies are rendered as markdown, prompts are shown as typed unless
+    /// toggled, since markdown pasted into them is often meant literally
+    pub fn renders_markdown(&self, msg: &ChatMsg) -> bool {
+        self.render_markdown.unwrap_or(msg.msg_role == MsgRole::Assistant)
+    }
 }
 
 // ChatMsg to be stored in the database
@@ -249,6 +382,26 @@ pub struct ChatMsg {
     pub reasoning: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub details: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prompt_tokens: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub completion_tokens: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cost: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub elapsed_ms: Option<i64>, // how long the reply took to stream
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tokens_per_sec: Option<f64>, // generation speed of the reply
+    #[serde(default)]
+    pub pinned: bool, // always sent, however the history is trimmed
+    #[serde(default)]
+    pub truncated: bool, // cut off by max_tokens, can be continued
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>, // why the stream failed, kept out of content
+    #[serde(default)]
+    pub interrupted: bool, // stopped by the user, the content is partial
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ts_created: Option<String>, // UTC, as sqlite's current_timestamp
 }
 
 // convert inhouse ChatMsg to Ollama's ChatMessage:
@@ -321,7 +474,9 @@ pub struct Agent {
     pub preset_selection: PresetSelection,
     pub preset: Option<Preset>,
     pub muted: bool,
-    pub hidden: bool, // not showing in the bottom panel, Omnis has hidden=true
+    // Omnis has hidden=true to stay out of the bottom panel, other agents
+    // are hidden by the user to keep their replies out of the chat
+    pub hidden: bool,
     pub deleted: bool,
 }
 
@@ -348,7 +503,7 @@ impl Default for Chat {
         let mut agent1 = Agent::default();
         agent1.agent_ind = 1;
         agent1.name = "Agent1".to_string();
-        agent1.preset_selection.id = 0;
+        agent1.preset_selection.id = DEFAULT_PRESET_ID.load(Ordering::Relaxed);
 
         Self {
             id: 0, // 0 indicates it hasn't been saved to DB yet
@@ -360,39 +515,75 @@ impl Default for Chat {
 }
 
 impl Chat {
+    /// Sum of the known costs of all the messages in this chat, in USD.
+    pub fn total_cost(&self) -> f64 {
+        self.msg_pool.values().filter_map(|msg| msg.cost).sum()
+    }
+
+    /// The chat's system prompt: the first system message in Omnis
+    pub fn system_msg(&self) -> Option<&ChatMsg> {
+        self.agents.first()?.msg_ids.iter()
+            .filter_map(|id| self.msg_pool.get(id))
+            .find(|msg| msg.msg_role == MsgRole::System)
+    }
+
+    /// Index of the agent that authored the message, skipping Omnis which
+    /// holds every message.
+    pub fn agent_ind_of(&self, msg_id: i64) -> Option<usize> {
+        self.agents.iter()
+            .filter(|agent| agent.agent_ind != 0)
+            .find(|agent| agent.msg_ids.contains(&msg_id))
+            .map(|agent| agent.agent_ind)
+    }
+
+    /// The messages of an agent's history that are sent to the model. With a
+    /// history_limit only the most recent ones go, plus the system and the
+    /// pinned messages. The chat itself is left untouched.
+    pub fn sent_msgs(&self, agent_ind: usize, history_limit: Option<u32>)
+            -> Vec<&ChatMsg> {
+        let Some(agent) = self.agents.get(agent_ind) else {
+            return Vec::new();
+        };
+        let msgs: Vec<&ChatMsg> = agent.msg_ids.iter()
+            .filter_map(|msg_id| self.msg_pool.get(msg_id))
+            .collect();
+        let Some(limit) = history_limit else {
+            return msgs;
+        };
+
+        let kept = |msg: &ChatMsg| msg.pinned || msg.msg_role == MsgRole::System;
+        let trimmable = msgs.iter().filter(|msg| !kept(msg)).count();
+        let mut to_drop = trimmable.saturating_sub(limit as usize);
+        msgs.into_iter()
+            .filter(|msg| {
+                if to_drop == 0 || kept(msg) {
+                    return true;
+                }
+                to_drop -= 1;
+                false
+            })
+            .collect()
+    }
+
     /// Converts a specific agent history into a vector of OpenRouter Messages.
     /// Returns an empty vector if the hist_id is not found.
-    pub fn to_openrouter_messages(&self, agent_ind: usize) -> Vec<Message> {
-        if let Some(agent) = self.agents.get(agent_ind) {
-            agent.msg_ids
-                .iter()
-                .filter_map(|msg_id| {
-                    self.msg_pool
-                        .get(msg_id)
-                        .cloned()       // Clone the ChatMsg (From consumes input)
-                        .map(Into::into)// Convert ChatMsg -> Message
-                })
-                .collect()
-        } else {
-            Vec::new()
-        }
+    pub fn to_openrouter_messages(&self, agent_ind: usize,
+            history_limit: Option<u32>) -> Vec<Message> {
+        self.sent_msgs(agent_ind, history_limit)
+            .into_iter()
+            .cloned()        // Clone the ChatMsg (From consumes input)
+            .map(Into::into) // Convert ChatMsg -> Message
+            .collect()
     }
 
     /// Converts a specific history branch into a vector of Ollama ChatMessages.
-    pub fn to_ollama_messages(&self, agent_ind: usize) -> Vec<ChatMessage> {
-        if let Some(agent) = self.agents.get(agent_ind) {
-            agent.msg_ids
-                .iter()
-                .filter_map(|msg_id| {
-                    self.msg_pool
-                        .get(msg_id)
-                        .cloned()        // Clone the ChatMsg
-                        .map(Into::into) // Convert ChatMsg -> ollama_rs ChatMessage
-                })
-                .collect()
-        } else {
-            Vec::new()
-        }
+    pub fn to_ollama_messages(&self, agent_ind: usize,
+            history_limit: Option<u32>) -> Vec<ChatMessage> {
+        self.sent_msgs(agent_ind, history_limit)
+            .into_iter()
+            .cloned()        // Clone the ChatMsg
+            .map(Into::into) // Convert ChatMsg -> ollama_rs ChatMessage
+            .collect()
     }
 
     /// Adds a new agent sequentially if the limit has not been reached.
@@ -409,6 +600,7 @@ impl Chat {
         let mut new_agent = Agent::default();
         new_agent.agent_ind = new_ind;
         new_agent.name = format!("Agent{}", new_ind);
+        new_agent.preset_selection.id = DEFAULT_PRESET_ID.load(Ordering::Relaxed);
         new_agent.hidden = false;
         new_agent.deleted = false;
         new_agent.muted = false;
@@ -424,20 +616,134 @@ impl Chat {
 
         Ok(())
     }
+
+    /// Swaps the agent at `ind` with the nearest agent above or below it that
+    /// is not deleted. Omnis stays first. Saved agents are swapped in the db.
+    pub fn move_agent(&mut self, conn: &Connection, ind: usize, up: bool)
+            -> rusqlite::Result<()> {
+        let live = |j: &usize| !self.agents[*j].deleted;
+        let other = if up {
+            (1..ind).rev().find(live)
+        } else {
+            (ind + 1..self.agents.len()).find(live)
+        };
+        let Some(other) = other.filter(|_| ind != 0) else {
+            return Ok(());
+        };
+
+        let (first_id, second_id) = (self.agents[ind].id, self.agents[other].id);
+        if first_id != 0 && second_id != 0 {
+            swap_agent_inds(conn, first_id, second_id)?;
+        }
+        self.agents.swap(ind, other);
+        self.agents[ind].agent_ind = ind;
+        self.agents[other].agent_ind = other;
+        Ok(())
+    }
 }
 
 // this is only used for loading chat titles to show them in the side pane
 #[derive(Debug)]
 pub struct DbChat {
     pub id: i64,
-    pub title: String
+    pub title: String,
+    pub parent: Option<i64>, // set for chats branched off another chat
+    pub ts_created: Option<String>, // UTC, as sqlite's current_timestamp
+    pub pinned: bool, // listed above the other chats
+}
+
+// how hard a reasoning model should think, only OpenRouter takes it
+#[derive(Debug, Clone, Copy, PartialEq, serde::Deserialize, serde::Serialize)]
+pub enum ReasoningEffort {
+    Low,
+    Medium,
+    High,
+}
+
+// the shape the reply must take, for replies that are read by programs
+#[derive(Debug, Clone, PartialEq, serde::Deserialize, serde::Serialize)]
+pub enum ResponseFormat {
+    JsonObject, // any valid json object
+    JsonSchema(String), // json that follows this schema, kept as typed
+}
+
+impl ResponseFormat {
+    /// The schema as json, None if it is not a json object (or there is no
+    /// schema at all)
+    pub fn schema(&self) -> Option<serde_json::Value> {
+        match self {
+            ResponseFormat::JsonObject => None,
+            ResponseFormat::JsonSchema(text) => serde_json::from_str(text).ok()
+                .filter(|value: &serde_json::Value| value.is_object()),
+        }
+    }
 }
 
 #[derive(Debug, Default, Clone, serde::Deserialize, serde::Serialize)]
 pub struct ModelOptions {
     pub include_reasoning: Option<bool>,
+    // only used with include_reasoning, None means High (older presets)
+    #[serde(default)]
+    pub reasoning_effort: Option<ReasoningEffort>,
     pub seed: Option<i32>, // we use i32 but do not allow negative values
     pub temperature: Option<f64>,
+    pub max_tokens: Option<u32>, // caps the length of the reply
+    pub top_p: Option<f64>, // nucleus sampling, 0.0..=1.0
+    pub top_k: Option<i32>, // we use i32 but do not allow negative values
+    pub frequency_penalty: Option<f64>, // -2.0..=2.0, scales with repeat count
+    pub presence_penalty: Option<f64>, // -2.0..=2.0, flat once a token appeared
+    #[serde(default)]
+    pub stop: Vec<String>, // stop sequences, empty means none
+    // ollama only, how long the model stays loaded after a reply:
+    // "30s", "5m", "2h", "-1" forever, "0" unload at once. None = server default
+    #[serde(default)]
+    pub keep_alive: Option<String>,
+    // Some(false) waits for the whole reply, for providers that stream badly
+    #[serde(default)]
+    pub stream: Option<bool>,
+    // only the most recent messages are sent, system messages always go.
+    // None sends the whole history
+    #[serde(default)]
+    pub history_limit: Option<u32>,
+    // None lets the model answer in free text
+    #[serde(default)]
+    pub response_format: Option<ResponseFormat>,
+    // gives up on a stream that sends nothing for this long, None waits forever
+    #[serde(default)]
+    pub timeout_secs: Option<u64>,
+}
+
+impl ModelOptions {
+    /// whether the reply is streamed, on unless turned off
+    pub fn streams(&self) -> bool {
+        self.stream.unwrap_or(true)
+    }
+
+    /// the effort to ask for when reasoning is on
+    pub fn effort(&self) -> ReasoningEffort {
+        self.reasoning_effort.unwrap_or(ReasoningEffort::High)
+    }
+
+    /// how long to wait for the next chunk of a stream, if at all
+    pub fn stream_timeout(&self) -> Option<std::time::Duration> {
+        self.timeout_secs.map(std::time::Duration::from_secs)
+    }
+
+    /// false if a json schema was asked for but what was typed isn't one
+    pub fn is_response_format_valid(&self) -> bool {
+        match &self.response_format {
+            Some(format @ ResponseFormat::JsonSchema(_)) => format.schema().is_some(),
+            _ => true,
+        }
+    }
+
+    /// stop sequences with blank entries dropped, ready to be sent
+    pub fn stop_sequences(&self) -> Vec<String> {
+        self.stop.iter()
+            .filter(|s| !s.trim().is_empty())
+            .cloned()
+            .collect()
+    }
 }
 
 // Preset is the essential data structure, because it will hide all
@@ -451,6 +757,14 @@ pub struct Preset {
     pub chat_router: ChatRouter,
     pub model: String,
     pub options: ModelOptions,
+    #[serde(default)]
+    pub base_url: String, // only used by the OpenAICompatible router
+    #[serde(default)]
+    pub category: Option<String>, // None shows up under "General"
+    #[serde(default)]
+    pub favorite: bool, // favorites are listed before the other presets
+    #[serde(default)]
+    pub key_name: Option<String>, // OpenRouter key to bill, None is the default one
     pub hidden: bool, // true when used as an override
     pub deleted: bool,
     #[serde(skip)]
@@ -467,6 +781,10 @@ impl Default for Preset {
             chat_router: ChatRouter::Ollama,
             model: "".to_string(),
             options: ModelOptions::default(),
+            base_url: "".to_string(),
+            category: None,
+            favorite: false,
+            key_name: None,
             hidden: false,
             deleted: false,
             api_key: ApiKey::default(),
@@ -538,6 +856,16 @@ impl Presets {
         self.generation
     }
 
+    /// Sorted, distinct categories of the presets that are not deleted
+    pub fn categories(&self) -> Vec<String> {
+        let mut categories: Vec<String> = self.cache.iter()
+            .filter_map(|(id, _)| self.get(*id)?.category.clone())
+            .collect();
+        categories.sort_unstable_by_key(|c| c.to_lowercase());
+        categories.dedup();
+        categories
+    }
+
     /// Replaces all current presets with new ones loaded from DB
     pub fn replace_all(&mut self, new_presets: Vec<Preset>) {
         self.hash.clear();
@@ -653,6 +981,17 @@ pub struct DbOpenrModel {
     pub ts_model: Option<String>,
 }
 
+impl DbOpenrModel {
+    /// cost of a request in USD, if the prices of this model are known
+    pub fn cost_of(&self, prompt_tokens: i64, completion_tokens: i64)
+            -> Option<f64> {
+        let prompt = self.price_prompt?;
+        let completion = self.price_completion?;
+        Some(prompt * prompt_tokens as f64
+            + completion * completion_tokens as f64)
+    }
+}
+
 #[derive(Debug, Default)]
 pub struct DbOllamaModel {
     pub id: i64,
@@ -666,12 +1005,25 @@ pub struct DbOllamaModel {
     pub ts_updated: Option<String>,
 }
 
+// result of checking an OpenRouter key against the api
+#[derive(Default, Clone)]
+pub enum KeyCheck {
+    #[default]
+    Idle,
+    Checking,
+    Valid(Option<f64>),  // remaining balance in USD, when known
+    Rejected(String),    // refused by OpenRouter, the key is not disabled yet
+    Invalid(String),     // refused by OpenRouter and disabled
+    Unreachable(String), // OpenRouter could not be asked, the key stays on
+}
+
 #[derive(Default, Clone)] // Clone allows easy passing to threads
 pub struct OllamaDownloading {
     pub progress: f32,       // 0.0 to 1.0
     pub status_text: String, // e.g. "pulling sha256..."
     pub progress_text: String,
     pub is_downloading: bool,
+    pub model: String, // the model being pulled, the editor may show another
     pub error_msg: Option<String>,
 	pub abort_flag: Arc<AtomicBool>,
 }
@@ -719,72 +1071,102 @@ pub fn mask_key_secure(key: &str) -> String {
     format!("{}..{}", start, end)
 }
 
-/*
-// do chat completion request
-pub async fn do_chat_que(cq: ChatQue) -> Result<ChatResp, String> {
-    let model = cq.preset.model.clone();
-    match cq.preset.chat_router {
-        ChatRouter::Ollama => {
-            let response = do_ollama_chat_que(cq)
-                .await;
-
-            match response {
-                Ok(res) => {
-                    println!("{:?}", res);
-                    return Ok(ChatResp {
-                        chat_msg: ChatMsg {
-                            msg_role: MsgRole::Assistant,
-                            content: res.message.content.clone(),
-                            name: None,
-                            model: Some(model),
-                            reasoning: res.message.thinking.clone(),
-                            ..Default::default()
-                        }
-                    });
-                },
-                Err(_err) => {
-                    return Err(format!(
-                    "Error accessing Ollama. Is it running? \
-                    Try typing in terminal: ollama serve \n\
-                    If Ollama is running, check if it has the model '{}' \
-                    installed: ollama list\n\
-                    to install it: ollama pull {}", model, model).to_string());
+/// Appends a JSON line to the --debug-log file, nothing happens (and `data`
+/// isn't built) without the flag. `secret` is the API key of the request, it
+/// is blanked out wherever it shows up.
+pub fn debug_log(agent_ind: usize, kind: &str, secret: &str,
+        data: impl FnOnce() -> serde_json::Value) {
+    use std::io::Write;
+    let Some(file) = DEBUG_LOG.get() else {
+        return;
+    };
+    let entry = serde_json::json!({
+        "ts": chrono::Utc::now().to_rfc3339(),
+        "agent": agent_ind,
+        "kind": kind,
+        "data": data(),
+    });
+    let mut line = entry.to_string();
+    if !secret.is_empty() {
+        line = line.replace(secret, "[REDACTED]");
+    }
+    let written = file.lock().map_err(|e| e.to_string())
+        .and_then(|mut file| writeln!(file, "{}", line).map_err(|e| e.to_string()));
+    if let Err(e) = written {
+        eprintln!("Could not write the debug log: {}", e);
+    }
+}
+
+/// The next item of a stream, or Err if none came within `timeout`. None
+/// waits as long as it takes.
+pub async fn next_or_timeout<S: futures_util::Stream + Unpin>(stream: &mut S,
+        timeout: Option<std::time::Duration>)
+        -> Result<Option<S::Item>, tokio::time::error::Elapsed> {
+    use futures_util::StreamExt;
+    match timeout {
+        Some(timeout) => tokio::time::timeout(timeout, stream.next()).await,
+        None => Ok(stream.next().await),
+    }
+}
+
+/// The output of `future`, or Err if it did not finish within `timeout`, like
+/// `next_or_timeout` for the request that opens a stream
+pub async fn or_timeout<F: std::future::Future>(future: F,
+        timeout: Option<std::time::Duration>)
+        -> Result<F::Output, tokio::time::error::Elapsed> {
+    match timeout {
+        Some(timeout) => tokio::time::timeout(timeout, future).await,
+        None => Ok(future.await),
+    }
+}
+
+/// Turns the bytes of a stream into text. A character split between two
+/// chunks is held back until the rest of it arrives.
+#[derive(Default)]
+pub struct Utf8Buffer {
+    pending: Vec<u8>,
+}
+
+impl Utf8Buffer {
+    /// The text of what was held back followed by `bytes`, up to the last
+    /// complete character. Invalid bytes come out as U+FFFD.
+    pub fn push(&mut self, bytes: &[u8]) -> String {
+        self.pending.extend_from_slice(bytes);
+        let mut text = String::new();
+        let mut start = 0;
+        while let Err(e) = std::str::from_utf8(&self.pending[start..]) {
+            let valid_end = start + e.valid_up_to();
+            text.push_str(&String::from_utf8_lossy(&self.pending[start..valid_end]));
+            match e.error_len() {
+                Some(len) => {
+                    text.push(char::REPLACEMENT_CHARACTER);
+                    start = valid_end + len;
                 }
-            }
-        },
-        ChatRouter::Openrouter => {
-            let response = do_openr_chat_que(cq)
-                .await;
-
-            match response {
-                Ok(res) => {
-                    match &res.choices[0].content() {
-                        Some(str_output) => {
-                            println!("{:#?}", res);
-                            return Ok(ChatResp {
-                                chat_msg: ChatMsg {
-                                    msg_role: MsgRole::Assistant,
-                                    content: str_output.to_string(),
-                                    name: None,
-                                    model: Some(res.model),
-                                    //reasoning :
-                                    ..Default::default()
-                                }
-                            });
-                        }
-                        None => {
-                            return Err(format!("Empty reply from openrouter").to_string());
-                        }
-                    }
-                },
-                Err(err) => {
-                    return Err(format!("Error accessing openrouter: {}", err).to_string());
+                None => {
+                    // the sequence is cut short, the next chunk completes it
+                    self.pending.drain(..valid_end);
+                    return text;
                 }
             }
         }
+        text.push_str(&String::from_utf8_lossy(&self.pending[start..]));
+        self.pending.clear();
+        text
+    }
+
+    /// Whatever is still held back once the stream has ended
+    pub fn finish(&mut self) -> String {
+        let text = String::from_utf8_lossy(&self.pending).into_owned();
+        self.pending.clear();
+        text
     }
 }
-*/
+
+/// What a stream that went quiet for too long reports
+pub fn timeout_error(timeout: Option<std::time::Duration>) -> String {
+    format!("Stream timed out: nothing received for {}s",
+        timeout.unwrap_or_default().as_secs())
+}
 
 pub async fn run_chat_stream_router(
     query: ChatQue,
@@ -793,6 +1175,9 @@ pub async fn run_chat_stream_router(
     abort_flag: Arc<AtomicBool>,
 ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     println!("Agent options: {:?}", &query.preset.options);
+    if !query.preset.options.streams() {
+        return run_chat_que_router(query, tx, ctx, abort_flag).await;
+    }
     match query.preset.chat_router {
         ChatRouter::Openrouter => {
             crate::openr::do_openr_chat_stream(query, tx, ctx, abort_flag).await
@@ -800,12 +1185,125 @@ pub async fn run_chat_stream_router(
         ChatRouter::Ollama => {
             crate::ollama::do_ollama_chat_stream(query, tx, ctx, abort_flag).await
         }
+        ChatRouter::OpenAICompatible => {
+            crate::openai::do_openai_chat_stream(query, tx, ctx, abort_flag).await
+        }
     }
 }
 
+// non-streaming fallback: waits for the whole reply and sends it through tx
+// in one piece, so to the caller it looks like a very short stream
+async fn run_chat_que_router(
+    query: ChatQue,
+    tx: Sender<ChatStreamEvent>,
+    ctx: &egui::Context,
+    abort_flag: Arc<AtomicBool>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let agent_ind = query.agent_ind;
+    match query.preset.chat_router {
+        ChatRouter::Openrouter => {
+            let response = crate::openr::do_openr_chat_que(query).await?;
+            if !abort_flag.load(Ordering::Relaxed) {
+                crate::openr::send_response_events(&response, agent_ind, &tx,
+                    ctx);
+            }
+        }
+        ChatRouter::Ollama => {
+            let max_tokens = query.preset.options.max_tokens;
+            let response = crate::ollama::do_ollama_chat_que(query).await?;
+            if !abort_flag.load(Ordering::Relaxed) {
+                crate::ollama::send_response_events(response, agent_ind,
+                    max_tokens, &tx, ctx);
+            }
+        }
+        ChatRouter::OpenAICompatible => {
+            let response = crate::openai::do_openai_chat_que(query).await?;
+            if !abort_flag.load(Ordering::Relaxed) {
+                crate::openr::send_response_events(&response, agent_ind, &tx,
+                    ctx);
+            }
+        }
+    }
+    ctx.request_repaint();
+    Ok(())
+}
+
+/// A summary of a saved chat, shown before archiving or deleting it
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ChatStats {
+    pub msgs: i64,
+    pub agents: i64, // not counting Omnis or deleted agents
+    pub models: Vec<String>, // of the replies, sorted
+    pub ts_created: Option<String>, // UTC, as sqlite's current_timestamp
+    pub cost: f64,
+}
+
 #[derive(Clone, Debug, PartialEq, serde::Deserialize, serde::Serialize)]
 pub struct SearchResult {
     pub chat_id: i64,
+    #[serde(default)]
+    pub msg_id: i64, // the first matching message, to scroll to
     pub chat_title: String,
     pub snippet: String,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_utf8_buffer_split_chunks() {
+        let text = "Привет, 世界 👋!";
+        let bytes = text.as_bytes();
+        // cut anywhere, even in the middle of a character
+        for cut in 0..=bytes.len() {
+            let mut buffer = Utf8Buffer::default();
+            let mut joined = buffer.push(&bytes[..cut]);
+            assert!(text.starts_with(&joined));
+            joined.push_str(&buffer.push(&bytes[cut..]));
+            joined.push_str(&buffer.finish());
+            assert_eq!(joined, text, "cut at byte {}", cut);
+        }
+
+        // one byte at a time, nothing comes out half done
+        let mut buffer = Utf8Buffer::default();
+        let joined: String = bytes.iter().map(|b| buffer.push(&[*b])).collect();
+        assert_eq!(joined, text);
+
+        // invalid bytes are replaced, a sequence the stream never completes
+        // is only given up at the end
+        let mut buffer = Utf8Buffer::default();
+        assert_eq!(buffer.push(b"a\xffb\xe4\xb8"), "a\u{fffd}b");
+        assert_eq!(buffer.finish(), "\u{fffd}");
+    }
+
+    #[test]
+    fn test_history_limit() {
+        let mut chat = Chat::default();
+        let roles = [MsgRole::System, MsgRole::User, MsgRole::Assistant,
+            MsgRole::User, MsgRole::Assistant, MsgRole::User];
+        for (i, role) in roles.into_iter().enumerate() {
+            let msg = ChatMsg {
+                id: i as i64 + 1,
+                msg_role: role,
+                content: format!("msg {}", i),
+                ..Default::default()
+            };
+            chat.agents[1].msg_ids.push(msg.id);
+            chat.msg_pool.insert(msg.id, msg);
+        }
+
+        let contents = |chat: &Chat, limit| chat.sent_msgs(1, limit).iter()
+            .map(|msg| msg.content.clone()).collect::<Vec<_>>();
+        assert_eq!(contents(&chat, None).len(), 6);
+        assert_eq!(contents(&chat, Some(2)), ["msg 0", "msg 4", "msg 5"]);
+        assert_eq!(contents(&chat, Some(10)).len(), 6);
+        // trimming only affects what is sent
+        assert_eq!(chat.agents[1].msg_ids.len(), 6);
+
+        // a pinned message survives the trim
+        let first_user = chat.agents[1].msg_ids[1];
+        chat.msg_pool.get_mut(&first_user).unwrap().pinned = true;
+        assert_eq!(contents(&chat, Some(2)), ["msg 0", "msg 1", "msg 4", "msg 5"]);
+    }
+}
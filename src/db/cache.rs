@@ -2,9 +2,36 @@ use std::{fs, path::PathBuf};
 use rusqlite::{Connection, params, Row};
 use directories::ProjectDirs;
 use crate::{common::{DbOllamaModel, DbOpenrModel, MyError}, db::table_exists};
-use chrono::{TimeZone, Utc};
+use chrono::{DateTime, TimeZone, Utc};
+
+// the pre-set cache shipped in assets
+const BUNDLED_CACHE: &[u8] = include_bytes!("../../assets/cache2.db");
+
+/// Opens the cache db in the user's cache directory. When that is not
+/// possible (e.g. the directory is read-only), the bundled cache is loaded
+/// into memory for this session. The returned flag is true in that case.
+pub fn get_cache_db_conn() -> Result<(Connection, bool), MyError> {
+    match open_cache_file() {
+        Ok(conn) => Ok((conn, false)),
+        Err(error) => {
+            eprintln!("Could not open the cache file ({}), using an in-memory cache",
+                error);
+            Ok((open_bundled_cache()?, true))
+        }
+    }
+}
 
-pub fn get_cache_db_conn() -> Result<Connection, MyError> {
+fn open_bundled_cache() -> Result<Connection, MyError> {
+    let mut conn = Connection::open_in_memory()?;
+    conn.deserialize_read_exact("main", BUNDLED_CACHE, BUNDLED_CACHE.len(),
+        false)?;
+    if !table_exists(&conn, "schema_version")? {
+        create_database_schema(&conn)?;
+    }
+    Ok(conn)
+}
+
+fn open_cache_file() -> Result<Connection, MyError> {
     if let Some(proj_dirs) = ProjectDirs::from(
             "", "", "inforno") {
         let mut file_path_buf = PathBuf::from(proj_dirs.cache_dir());
@@ -20,9 +47,16 @@ pub fn get_cache_db_conn() -> Result<Connection, MyError> {
         // if cache.db is missing, use the pre-set one in assets. This is needed,
         // because it contains the Ollama model info which is scraped from the
         // website. We don't want every user to run website scrape operation.
-        if !file_path_buf.exists() {
-            let _ = std::fs::write(&file_path_buf,
-                include_bytes!("../../assets/cache2.db"));
+        let written = if file_path_buf.exists() {
+            Ok(())
+        } else {
+            std::fs::write(&file_path_buf, BUNDLED_CACHE)
+        };
+        if let Err(error) = written {
+            // a partly written copy would be taken for a good one next time
+            let _ = fs::remove_file(&file_path_buf);
+            return Err(MyError::CacheDb(format!("could not write {}: {}",
+                file_path_buf.display(), error)));
         }
 
         let conn = Connection::open(
@@ -160,6 +194,18 @@ pub fn get_ollama_model_info(conn: &Connection, model_id: &str) ->
 }
 
 
+/// When the model catalogs (Openrouter and Ollama library) were last
+/// refreshed, None while both are empty
+pub fn get_models_last_updated(conn: &Connection) -> Option<DateTime<Utc>> {
+    let secs: Option<i64> = conn.query_row(
+        "select cast(strftime('%s', max(ts_updated)) as integer) from (
+            select ts_updated from openr_model
+            union all
+            select ts_updated from ollama_model
+        )", [], |row| row.get(0)).ok()?;
+    Utc.timestamp_opt(secs?, 0).single()
+}
+
 pub fn get_openr_model_names(conn: &Connection) -> rusqlite::Result<Vec<String>> {
     let sql = "select model_id from openr_model order by name";
     let mut stmt = conn.prepare(sql)?;
@@ -190,6 +236,17 @@ pub fn get_ollama_model_installed(conn: &Connection) -> rusqlite::Result<Vec<Str
     Ok(names)
 }
 
+// bytes taken on disk by an installed model, None if it isn't installed
+pub fn get_ollama_installed_size(conn: &Connection, name: &str)
+        -> rusqlite::Result<Option<i64>> {
+    let sql = "select size from ollama_installed where name = ?";
+    match conn.query_row(sql, params![name], |row| row.get(0)) {
+        Ok(size) => Ok(Some(size)),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
 pub fn get_ollama_model_names(conn: &Connection) -> rusqlite::Result<Vec<String>> {
     let sql = "select name, variants from ollama_model order by name";
     let mut stmt = conn.prepare(sql)?;
@@ -1,12 +1,17 @@
-use std::{collections::HashMap, fs, path::PathBuf, sync::OnceLock};
+use std::{collections::HashMap, fs, path::{Path, PathBuf}, sync::OnceLock};
 use regex::Regex;
-use rusqlite::{Connection, params, Result};
+use rusqlite::{Connection, OptionalExtension, params, Result};
+use rusqlite::types::Value;
+use serde_json::{Map, Value as JsonValue};
 use directories::ProjectDirs;
 use crate::common::{Agent, Chat, ChatMsg, DbChat, MyError, Preset, PresetSelection, Presets};
 
 pub mod cache;
 
-pub const CURRENT_SANDBOX_VERSION: i32 = 2;
+pub const CURRENT_SANDBOX_VERSION: i32 = 15;
+
+// how many backups of one sandbox are kept in its backups/ directory
+pub const SANDBOX_BACKUPS_KEPT: usize = 10;
 
 // Helper to securely find the user's home sandbox file
 pub fn get_home_sandbox_path() -> Option<PathBuf> {
@@ -31,12 +36,12 @@ pub fn init_project_sandbox(proj_dir: &PathBuf, copy_presets: bool) -> Result<()
     let db_path = inforno_dir.join("info.rno");
 
     // Open/create the new sandbox database
-    let new_conn = connect_sandbox_db(&db_path)?;
+    let new_conn = connect_sandbox_db(&db_path, false)?;
 
     // If the user wants presets, fetch them from the home DB
     if copy_presets {
         if let Some(home_path) = get_home_sandbox_path() {
-            if let Ok(home_conn) = connect_sandbox_db(&home_path) {
+            if let Ok(home_conn) = connect_sandbox_db(&home_path, false) {
                 if let Ok(presets) = load_presets_vec(&home_conn) {
                     for mut preset in presets {
                         preset.id = 0; // Set to 0 so `save_preset` treats it as a brand new insert!
@@ -49,10 +54,13 @@ pub fn init_project_sandbox(proj_dir: &PathBuf, copy_presets: bool) -> Result<()
     Ok(())
 }
 
-pub fn get_sandbox_db_conn(sandbox: &Option<PathBuf>) ->
+/// Opens the sandbox, or the home one when none is given. With `readonly` the
+/// connection refuses writes from the start, so an out-of-date schema is an
+/// error rather than being migrated.
+pub fn get_sandbox_db_conn(sandbox: &Option<PathBuf>, readonly: bool) ->
             Result<(Connection, PathBuf), MyError> {
     if let Some(sandbox) = sandbox {
-        let conn = connect_sandbox_db(sandbox)?;
+        let conn = connect_sandbox_db(sandbox, readonly)?;
         return Ok((conn, sandbox.clone()));
     }
 
@@ -66,15 +74,55 @@ pub fn get_sandbox_db_conn(sandbox: &Option<PathBuf>) ->
         }
         file_path_buf.push("info.rno");
 
-        let conn = connect_sandbox_db(&file_path_buf)?;
+        let conn = connect_sandbox_db(&file_path_buf, readonly)?;
         return Ok((conn, file_path_buf));
     }
     Err(MyError::ProjectDir)
 }
 
-fn connect_sandbox_db(sandbox: &PathBuf) -> Result<Connection, MyError> {
+/// Copies the open sandbox into a backups/ directory next to it, named
+/// "<sandbox name>-<timestamp>.rno", then removes all but the newest
+/// SANDBOX_BACKUPS_KEPT backups of that sandbox. VACUUM INTO gives a
+/// consistent snapshot even while the sandbox is in use.
+pub fn backup_sandbox(conn: &Connection, sandbox: &Path)
+        -> Result<PathBuf, MyError> {
+    let backup_dir = sandbox.parent().ok_or(MyError::ProjectDir)?
+        .join("backups");
+    fs::create_dir_all(&backup_dir)?;
+
+    let stem = sandbox.file_stem().unwrap_or_default().to_string_lossy();
+    let prefix = format!("{}-", stem);
+    let backup_path = backup_dir.join(format!("{}{}.rno", prefix,
+        chrono::Local::now().format("%Y%m%d-%H%M%S-%3f")));
+    conn.execute("vacuum into ?1", [backup_path.to_string_lossy()])?;
+
+    // timestamps sort by name, oldest first
+    let mut backups: Vec<PathBuf> = fs::read_dir(&backup_dir)?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "rno")
+            && path.file_name().and_then(|n| n.to_str())
+                .is_some_and(|n| n.starts_with(&prefix)))
+        .collect();
+    backups.sort();
+    let excess = backups.len().saturating_sub(SANDBOX_BACKUPS_KEPT);
+    for old in &backups[..excess] {
+        fs::remove_file(old)?;
+    }
+
+    Ok(backup_path)
+}
+
+/// Makes SQLite refuse every write on this connection while `readonly` is set
+pub fn set_readonly(conn: &Connection, readonly: bool) -> Result<()> {
+    conn.pragma_update(None, "query_only", readonly)
+}
+
+fn connect_sandbox_db(sandbox: &PathBuf, readonly: bool)
+        -> Result<Connection, MyError> {
     let conn = Connection::open(sandbox)?;
     conn.pragma_update(None, "foreign_keys", "ON")?;
+    set_readonly(&conn, readonly)?;
 
     if table_exists(&conn, "schema_version")? {
         // Table exists, check the version
@@ -84,16 +132,28 @@ fn connect_sandbox_db(sandbox: &PathBuf) -> Result<Connection, MyError> {
             |row| row.get(0)
         )?;
 
-        if current_version != CURRENT_SANDBOX_VERSION {
-            // Version mismatch
+        if current_version < CURRENT_SANDBOX_VERSION && readonly {
+            return Err(MyError::SandboxReadOnly);
+        } else if current_version < CURRENT_SANDBOX_VERSION {
+            // Older sandbox: bring it up to date step by step
+            println!("Migrating Main Database schema from version {} to {}...",
+                current_version, CURRENT_SANDBOX_VERSION);
+            migrate_sandbox_db(&conn, current_version,
+                CURRENT_SANDBOX_VERSION)?;
+        } else if current_version != CURRENT_SANDBOX_VERSION {
+            // Sandbox from a newer build, we don't know how to read it
             return Err(MyError::SandboxVersionMismatch(
                 CURRENT_SANDBOX_VERSION,
                 current_version));
         }
 
-        println!("Main Database schema exists and is version 2.");
+        println!("Main Database schema exists and is version {}.",
+            CURRENT_SANDBOX_VERSION);
         return Ok(conn);
     } else {
+        if readonly {
+            return Err(MyError::SandboxReadOnly);
+        }
         // Schema does not exist. Create new database schema.
         println!("Main Database schema does not exist. Creating new one...");
         create_database_schema(&conn)?;
@@ -102,6 +162,161 @@ fn connect_sandbox_db(sandbox: &PathBuf) -> Result<Connection, MyError> {
     }
 }
 
+/// Upgrades the sandbox schema one version at a time. Every step runs in its
+/// own transaction and bumps schema_version when done, so an interrupted
+/// migration resumes from the last completed step. Steps are idempotent.
+pub fn migrate_sandbox_db(conn: &Connection, from_version: i32,
+        to_version: i32) -> Result<(), MyError> {
+    for version in from_version..to_version {
+        let tx = conn.unchecked_transaction()?;
+        match version {
+            1 => migrate_v1_to_v2(&tx)?,
+            2 => migrate_v2_to_v3(&tx)?,
+            3 => migrate_v3_to_v4(&tx)?,
+            4 => migrate_v4_to_v5(&tx)?,
+            5 => migrate_v5_to_v6(&tx)?,
+            6 => migrate_v6_to_v7(&tx)?,
+            7 => migrate_v7_to_v8(&tx)?,
+            8 => migrate_v8_to_v9(&tx)?,
+            9 => migrate_v9_to_v10(&tx)?,
+            10 => migrate_v10_to_v11(&tx)?,
+            11 => migrate_v11_to_v12(&tx)?,
+            12 => migrate_v12_to_v13(&tx)?,
+            13 => migrate_v13_to_v14(&tx)?,
+            14 => migrate_v14_to_v15(&tx)?,
+            _ => return Err(MyError::SandboxVersionMismatch(to_version,
+                version)),
+        }
+        tx.execute("update schema_version set version = ?1,
+            applied_on = current_timestamp", [version + 1])?;
+        tx.commit()?;
+        println!("Main Database schema migrated to version {}", version + 1);
+    }
+    Ok(())
+}
+
+// version 1 sandboxes predate per-message/agent preset snapshots, usage
+// tracking and the agent/preset flags, so add whichever columns are missing
+fn migrate_v1_to_v2(conn: &Connection) -> rusqlite::Result<()> {
+    let columns = [
+        ("msg", "preset_id", "integer not null default 0"),
+        ("msg", "preset_json", "text"),
+        ("msg", "prompt_tokens", "integer"),
+        ("msg", "completion_tokens", "integer"),
+        ("msg", "cost", "real"),
+        ("agent", "name", "text not null default ''"),
+        ("agent", "preset_id", "integer not null default 0"),
+        ("agent", "preset_json", "text"),
+        ("agent", "muted", "integer not null default 0"),
+        ("agent", "hidden", "integer not null default 0"),
+        ("agent", "deleted", "integer not null default 0"),
+        ("preset", "hidden", "integer not null default 0"),
+        ("preset", "deleted", "integer not null default 0"),
+    ];
+    for (table, column, definition) in columns {
+        add_column_if_missing(conn, table, column, definition)?;
+    }
+    Ok(())
+}
+
+// version 3 adds the server address of OpenAI compatible presets
+fn migrate_v2_to_v3(conn: &Connection) -> rusqlite::Result<()> {
+    add_column_if_missing(conn, "preset", "base_url", "text not null default ''")
+}
+
+fn migrate_v3_to_v4(conn: &Connection) -> rusqlite::Result<()> {
+    add_column_if_missing(conn, "preset", "category", "text")
+}
+
+// version 5 remembers how long each reply took to stream
+fn migrate_v4_to_v5(conn: &Connection) -> rusqlite::Result<()> {
+    add_column_if_missing(conn, "msg", "elapsed_ms", "integer")
+}
+
+// version 6 keeps the generation speed of each reply
+fn migrate_v5_to_v6(conn: &Connection) -> rusqlite::Result<()> {
+    add_column_if_missing(conn, "msg", "tokens_per_sec", "real")
+}
+
+// version 7 moves deleted chats to the trash instead of dropping them
+fn migrate_v6_to_v7(conn: &Connection) -> rusqlite::Result<()> {
+    add_column_if_missing(conn, "chat", "deleted",
+        "integer not null default 0")
+}
+
+// version 8 pins messages so that trimming the history never drops them
+fn migrate_v7_to_v8(conn: &Connection) -> rusqlite::Result<()> {
+    add_column_if_missing(conn, "msg", "pinned", "integer not null default 0")
+}
+
+// version 9 remembers replies cut off by max_tokens, so they can be continued
+fn migrate_v8_to_v9(conn: &Connection) -> rusqlite::Result<()> {
+    add_column_if_missing(conn, "msg", "truncated",
+        "integer not null default 0")
+}
+
+// version 10 keeps a failed stream's error apart from the reply's content
+fn migrate_v9_to_v10(conn: &Connection) -> rusqlite::Result<()> {
+    add_column_if_missing(conn, "msg", "error", "text")
+}
+
+// version 11 marks replies the user stopped halfway
+fn migrate_v10_to_v11(conn: &Connection) -> rusqlite::Result<()> {
+    add_column_if_missing(conn, "msg", "interrupted",
+        "integer not null default 0")
+}
+
+// version 12 remembers the preset that new agents start with
+fn migrate_v11_to_v12(conn: &Connection) -> rusqlite::Result<()> {
+    add_column_if_missing(conn, "preset", "is_default",
+        "integer not null default 0")
+}
+
+// version 13 pins chats to the top of the side panel
+fn migrate_v12_to_v13(conn: &Connection) -> rusqlite::Result<()> {
+    add_column_if_missing(conn, "chat", "pinned", "integer not null default 0")
+}
+
+// version 14 lists favorite presets first
+fn migrate_v13_to_v14(conn: &Connection) -> rusqlite::Result<()> {
+    add_column_if_missing(conn, "preset", "favorite",
+        "integer not null default 0")
+}
+
+// version 15 lets a preset bill to a named OpenRouter key
+fn migrate_v14_to_v15(conn: &Connection) -> rusqlite::Result<()> {
+    add_column_if_missing(conn, "preset", "key_name", "text")
+}
+
+fn column_exists(conn: &Connection, table: &str, column: &str)
+        -> rusqlite::Result<bool> {
+    conn.query_row(
+        "select exists(select 1 from pragma_table_info(?1) where name = ?2)",
+        params![table, column],
+        |row| row.get(0),
+    )
+}
+
+fn add_column_if_missing(conn: &Connection, table: &str, column: &str,
+        definition: &str) -> rusqlite::Result<()> {
+    if !column_exists(conn, table, column)? {
+        conn.execute(&format!("alter table {} add column {} {}", table,
+            column, definition), [])?;
+    }
+    Ok(())
+}
+
+/// (chats, presets) that a reset would delete. Hidden and deleted presets are
+/// not counted since the user never sees them.
+pub fn count_sandbox_contents(conn: &Connection) -> rusqlite::Result<(i64, i64)> {
+    let chats = conn.query_row("select count(*) from chat", [],
+        |row| row.get(0))?;
+    let presets = conn.query_row(
+        "select count(*) from preset where hidden = 0 and deleted = 0", [],
+        |row| row.get(0))?;
+    Ok((chats, presets))
+}
+
 pub fn reset_sandbox_db(conn: &Connection) -> rusqlite::Result<()> {
     conn.execute("PRAGMA foreign_keys = OFF", [])?;
 
@@ -267,6 +482,102 @@ pub fn mod_agent_msgs(conn: &Connection, id: i64, msg_ids: &[i64])
     Ok(())
 }
 
+pub fn mod_agent_name(conn: &Connection, id: i64, name: &str)
+        -> rusqlite::Result<()> {
+    conn.execute(
+        "UPDATE agent
+         SET name = ?1
+         WHERE id = ?2",
+        params![name, id],
+    )?;
+    Ok(())
+}
+
+/// Pins or unpins a message, a pinned one survives any history trimming
+pub fn mod_msg_pinned(conn: &Connection, id: i64, pinned: bool)
+        -> rusqlite::Result<()> {
+    conn.execute(
+        "UPDATE msg
+         SET pinned = ?1
+         WHERE id = ?2",
+        params![pinned, id],
+    )?;
+    Ok(())
+}
+
+pub fn mod_msg_truncated(conn: &Connection, id: i64, truncated: bool)
+        -> rusqlite::Result<()> {
+    conn.execute(
+        "UPDATE msg
+         SET truncated = ?1
+         WHERE id = ?2",
+        params![truncated, id],
+    )?;
+    Ok(())
+}
+
+pub fn mod_msg_error(conn: &Connection, id: i64, error: Option<&str>)
+        -> rusqlite::Result<()> {
+    conn.execute(
+        "UPDATE msg
+         SET error = ?1
+         WHERE id = ?2",
+        params![error, id],
+    )?;
+    Ok(())
+}
+
+pub fn mod_msg_interrupted(conn: &Connection, id: i64, interrupted: bool)
+        -> rusqlite::Result<()> {
+    conn.execute(
+        "UPDATE msg
+         SET interrupted = ?1
+         WHERE id = ?2",
+        params![interrupted, id],
+    )?;
+    Ok(())
+}
+
+pub fn mod_agent_hidden(conn: &Connection, id: i64, hidden: bool)
+        -> rusqlite::Result<()> {
+    conn.execute(
+        "UPDATE agent
+         SET hidden = ?1
+         WHERE id = ?2",
+        params![hidden, id],
+    )?;
+    Ok(())
+}
+
+pub fn mod_agent_muted(conn: &Connection, id: i64, muted: bool)
+        -> rusqlite::Result<()> {
+    conn.execute(
+        "UPDATE agent
+         SET muted = ?1
+         WHERE id = ?2",
+        params![muted, id],
+    )?;
+    Ok(())
+}
+
+/// Swaps the places of two agents of a chat. agent_ind is unique per chat,
+/// so the first agent waits at a temporary index while the second moves.
+pub fn swap_agent_inds(conn: &Connection, first_id: i64, second_id: i64)
+        -> rusqlite::Result<()> {
+    let tx = conn.unchecked_transaction()?;
+    let ind_of = |id: i64| tx.query_row(
+        "select agent_ind from agent where id = ?1", [id],
+        |row| row.get::<_, i64>(0));
+    let (first_ind, second_ind) = (ind_of(first_id)?, ind_of(second_id)?);
+    tx.execute("update agent set agent_ind = -1 where id = ?1",
+        [first_id])?;
+    tx.execute("update agent set agent_ind = ?1 where id = ?2",
+        [first_ind, second_id])?;
+    tx.execute("update agent set agent_ind = ?1 where id = ?2",
+        [second_ind, first_id])?;
+    tx.commit()
+}
+
 /// Updates an agent's base preset selection and preset_snapshot, of course
 pub fn mod_agent_preset(
     conn: &Connection,
@@ -471,6 +782,8 @@ pub fn mk_msg(conn: &rusqlite::Connection, msg: &mut ChatMsg)
     )?;
 
     msg.id = conn.last_insert_rowid();
+    msg.ts_created = conn.query_row("select ts_created from msg where id = ?1",
+        [msg.id], |row| row.get(0))?;
     msg.content = normalize_code_blocks(&msg.content);
 
     Ok(())
@@ -493,8 +806,17 @@ pub fn mod_msg(conn: &Connection, msg: &ChatMsg) -> Result<()> {
              name = ?4,
              details = ?5,
              preset_json = ?6,
-             preset_id = ?7
-         WHERE id = ?8",
+             preset_id = ?7,
+             prompt_tokens = ?8,
+             completion_tokens = ?9,
+             cost = ?10,
+             elapsed_ms = ?11,
+             tokens_per_sec = ?12,
+             pinned = ?13,
+             truncated = ?14,
+             error = ?15,
+             interrupted = ?16
+         WHERE id = ?17",
         params![
             msg.content,
             msg.reasoning,
@@ -503,6 +825,15 @@ pub fn mod_msg(conn: &Connection, msg: &ChatMsg) -> Result<()> {
             msg.details,
             preset_json,
             msg.preset_id,
+            msg.prompt_tokens,
+            msg.completion_tokens,
+            msg.cost,
+            msg.elapsed_ms,
+            msg.tokens_per_sec,
+            msg.pinned,
+            msg.truncated,
+            msg.error,
+            msg.interrupted,
             msg.id
         ],
     )?;
@@ -520,6 +851,16 @@ pub fn mod_msg_content(conn: &Connection, id: i64, content: &str) -> Result<()>
     Ok(())
 }
 
+pub fn mod_msg_preset(conn: &Connection, id: i64, preset: &Preset) -> Result<()> {
+    conn.execute(
+        "UPDATE msg
+         SET preset_json = ?2
+         WHERE id = ?1",
+        params![id, preset.to_json()],
+    )?;
+    Ok(())
+}
+
 pub fn mod_msg_content_reasoning(conn: &Connection, id: i64, content: &str,
         reasoning: &str) -> Result<()> {
     conn.execute(
@@ -531,17 +872,60 @@ pub fn mod_msg_content_reasoning(conn: &Connection, id: i64, content: &str,
     Ok(())
 }
 
+pub fn delete_msg(conn: &Connection, id: i64) -> Result<()> {
+    conn.execute("delete from msg where id = ?1", params![id])?;
+    Ok(())
+}
+
+pub fn mod_msg_usage(conn: &Connection, id: i64, prompt_tokens: i64,
+        completion_tokens: i64, cost: Option<f64>, tokens_per_sec: Option<f64>)
+        -> Result<()> {
+    conn.execute(
+        "UPDATE msg
+         SET prompt_tokens = ?2, completion_tokens = ?3, cost = ?4,
+             tokens_per_sec = ?5
+         WHERE id = ?1",
+        params![id, prompt_tokens, completion_tokens, cost, tokens_per_sec],
+    )?;
+    Ok(())
+}
+
+pub fn mod_msg_elapsed(conn: &Connection, id: i64, elapsed_ms: i64)
+        -> Result<()> {
+    conn.execute(
+        "UPDATE msg
+         SET elapsed_ms = ?2
+         WHERE id = ?1",
+        params![id, elapsed_ms],
+    )?;
+    Ok(())
+}
 
 pub fn fetch_chat_titles(conn: &Connection) -> rusqlite::Result<Vec<DbChat>> {
+    fetch_chat_titles_where(conn, false)
+}
+
+/// Chats in the trash, most recent first
+pub fn fetch_deleted_chat_titles(conn: &Connection)
+        -> rusqlite::Result<Vec<DbChat>> {
+    fetch_chat_titles_where(conn, true)
+}
+
+fn fetch_chat_titles_where(conn: &Connection, deleted: bool)
+        -> rusqlite::Result<Vec<DbChat>> {
     let mut stmt = conn.prepare(
-        "select id, title, ts_created from chat where parent is null or
-        parent = 0 order by ts_created desc"
+        "select id, title, ts_created, nullif(parent, 0), pinned from chat
+        where deleted = ?1
+        order by pinned desc, ts_created desc, id desc"
     )?;
 
-    let chat_iter = stmt.query_map([], |row| {
+    let chat_iter = stmt.query_map([deleted], |row| {
         Ok(DbChat {
             id: row.get(0)?,
             title: row.get(1)?,
+            ts_created: row.get(2)?,
+            parent: row.get(3)?,
+            pinned: row.get(4)?,
         })
     })?;
 
@@ -621,7 +1005,9 @@ pub fn fetch_chat(conn: &Connection, chat_id: i64, presets: &Presets)
 
         let sql = format!(
             "SELECT id, role, content, name, reasoning, details, preset_json,
-                    preset_id
+                    preset_id, prompt_tokens, completion_tokens, cost, elapsed_ms,
+                    tokens_per_sec, pinned, truncated, error, interrupted,
+                    ts_created
              FROM msg
              WHERE id IN ({})",
             placeholders
@@ -649,7 +1035,16 @@ pub fn fetch_chat(conn: &Connection, chat_id: i64, presets: &Presets)
                 details: row.get(5)?,
                 preset: msg_preset,
                 preset_id: row.get(7)?,
-                ..Default::default()
+                prompt_tokens: row.get(8)?,
+                completion_tokens: row.get(9)?,
+                cost: row.get(10)?,
+                elapsed_ms: row.get(11)?,
+                tokens_per_sec: row.get(12)?,
+                pinned: row.get(13)?,
+                truncated: row.get(14)?,
+                error: row.get(15)?,
+                interrupted: row.get(16)?,
+                ts_created: row.get(17)?,
             })
         })?;
 
@@ -670,6 +1065,13 @@ pub fn fetch_chat(conn: &Connection, chat_id: i64, presets: &Presets)
 
 }
 
+pub fn mod_chat_pinned(conn: &Connection, chat_id: i64, pinned: bool)
+        -> rusqlite::Result<()> {
+    conn.execute("update chat set pinned = ?1 where id = ?2",
+        params![pinned, chat_id])?;
+    Ok(())
+}
+
 pub fn mod_chat_title(conn: &Connection, chat_id: i64,  new_title: &str)
         -> rusqlite::Result<()> {
     let clean_title = new_title.split('\n').next().unwrap_or(new_title).trim();
@@ -678,8 +1080,148 @@ pub fn mod_chat_title(conn: &Connection, chat_id: i64,  new_title: &str)
     Ok(())
 }
 
+/// Forks a chat: the new chat (with `parent` pointing at the source) gets copies
+/// of the source agents, each keeping only the messages up to and including
+/// `up_to_msg_id`. The kept messages are copied too, so editing or deleting
+/// them in one branch leaves the other alone. The new chat is titled
+/// "<title> (<suffix>)". Returns the new chat id.
+pub fn branch_chat(conn: &Connection, source_chat_id: i64, up_to_msg_id: i64,
+        suffix: &str) -> rusqlite::Result<i64> {
+    let tx = conn.unchecked_transaction()?;
+
+    let omnis_ids = fetch_omnis_msg_ids(&tx, source_chat_id)?;
+    let Some(pos) = omnis_ids.iter().position(|id| *id == up_to_msg_id) else {
+        return Err(rusqlite::Error::QueryReturnedNoRows);
+    };
+    let new_chat_id = copy_chat(&tx, source_chat_id, &omnis_ids[..=pos],
+        Some(source_chat_id), suffix)?;
+
+    tx.commit()?;
+    Ok(new_chat_id)
+}
+
+/// Deep-copies a whole chat with its agents and messages into a new
+/// top level chat titled "<title> (<suffix>)". Returns the new chat id.
+pub fn duplicate_chat(conn: &Connection, chat_id: i64, suffix: &str)
+        -> rusqlite::Result<i64> {
+    let tx = conn.unchecked_transaction()?;
+
+    let omnis_ids = fetch_omnis_msg_ids(&tx, chat_id)?;
+    let new_chat_id = copy_chat(&tx, chat_id, &omnis_ids, None, suffix)?;
+
+    tx.commit()?;
+    Ok(new_chat_id)
+}
+
+// Omnis (agent 0) holds every message in order
+fn fetch_omnis_msg_ids(conn: &Connection, chat_id: i64)
+        -> rusqlite::Result<Vec<i64>> {
+    let omnis_json: String = conn.query_row(
+        "select msg_ids from agent where chat_id = ?1 and agent_ind = 0",
+        [chat_id], |row| row.get(0))?;
+    Ok(serde_json::from_str(&omnis_json).unwrap_or_default())
+}
+
+// Inserts "<title> (<suffix>)" with copies of the source agents and of the
+// messages in `kept_ids`. Messages not in `kept_ids` are dropped from every
+// agent. Call inside a transaction.
+fn copy_chat(conn: &Connection, source_chat_id: i64, kept_ids: &[i64],
+        parent: Option<i64>, suffix: &str) -> rusqlite::Result<i64> {
+    // copy the kept messages, remembering old id -> new id
+    let mut id_map: HashMap<i64, i64> = HashMap::new();
+    for old_id in kept_ids {
+        conn.execute("insert into msg (content, reasoning, role, name, details,
+                preset_id, preset_json, prompt_tokens, completion_tokens, cost,
+                elapsed_ms, tokens_per_sec, pinned, truncated, error, interrupted,
+                ts_created)
+            select content, reasoning, role, name, details, preset_id,
+                preset_json, prompt_tokens, completion_tokens, cost, elapsed_ms,
+                tokens_per_sec, pinned, truncated, error, interrupted, ts_created
+            from msg where id = ?1", [old_id])?;
+        id_map.insert(*old_id, conn.last_insert_rowid());
+    }
+
+    let title: String = conn.query_row("select title from chat where id = ?1",
+        [source_chat_id], |row| row.get(0))?;
+    conn.execute("insert into chat (parent, title) values (?1, ?2)",
+        params![parent, format!("{} ({})", title, suffix)])?;
+    let new_chat_id = conn.last_insert_rowid();
+
+    conn.execute("insert into agent (chat_id, agent_ind, msg_ids, name,
+            preset_id, preset_json, muted, hidden, deleted)
+        select ?1, agent_ind, msg_ids, name, preset_id, preset_json, muted,
+            hidden, deleted
+        from agent where chat_id = ?2",
+        params![new_chat_id, source_chat_id])?;
+
+    // now point every agent at the copied messages
+    let agents: Vec<(i64, String)> = {
+        let mut stmt = conn.prepare(
+            "select id, msg_ids from agent where chat_id = ?1")?;
+        stmt.query_map([new_chat_id], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<rusqlite::Result<_>>()?
+    };
+    for (agent_id, json) in agents {
+        let msg_ids: Vec<i64> = serde_json::from_str::<Vec<i64>>(&json)
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|id| id_map.get(&id).copied())
+            .collect();
+        mod_agent_msgs(conn, agent_id, &msg_ids)?;
+    }
+
+    Ok(new_chat_id)
+}
+
+/// Moves the chat to the trash, it can be restored until purged
 pub fn delete_chat(conn: &Connection, chat_id: i64) -> rusqlite::Result<()> {
-    conn.execute("delete from chat where id = ?1", params![chat_id])?;
+    conn.execute("update chat set deleted = 1 where id = ?1",
+        params![chat_id])?;
+    Ok(())
+}
+
+pub fn restore_chat(conn: &Connection, chat_id: i64) -> rusqlite::Result<()> {
+    conn.execute("update chat set deleted = 0 where id = ?1",
+        params![chat_id])?;
+    Ok(())
+}
+
+/// Deletes the chat for good, its agents and messages go with it. Its
+/// branches move up to the chat's own parent.
+pub fn purge_chat(conn: &Connection, chat_id: i64) -> rusqlite::Result<()> {
+    let tx = conn.unchecked_transaction()?;
+
+    // every chat owns its messages, branches get copies
+    let agent_msgs: Vec<String> = {
+        let mut stmt = tx.prepare("select msg_ids from agent where chat_id = ?1")?;
+        stmt.query_map([chat_id], |row| row.get(0))?
+            .collect::<rusqlite::Result<_>>()?
+    };
+    let mut msg_ids: Vec<i64> = agent_msgs.iter()
+        .flat_map(|json| serde_json::from_str::<Vec<i64>>(json)
+            .unwrap_or_default())
+        .collect();
+    msg_ids.sort_unstable();
+    msg_ids.dedup();
+    for msg_id in msg_ids {
+        tx.execute("delete from msg where id = ?1", [msg_id])?;
+    }
+
+    tx.execute("update chat set parent = (select parent from chat where id = ?1)
+        where parent = ?1", [chat_id])?;
+    tx.execute("delete from chat where id = ?1", params![chat_id])?;
+    tx.commit()
+}
+
+/// The preset new agents start with, 0 if there is none
+pub fn get_default_preset_id(conn: &Connection) -> rusqlite::Result<i64> {
+    conn.query_row("select id from preset where is_default = 1", [],
+        |row| row.get(0)).optional().map(|id| id.unwrap_or(0))
+}
+
+/// Makes `id` the preset new agents start with, 0 clears it
+pub fn set_default_preset(conn: &Connection, id: i64) -> rusqlite::Result<()> {
+    conn.execute("update preset set is_default = (id = ?1)", params![id])?;
     Ok(())
 }
 
@@ -694,17 +1236,28 @@ pub fn save_preset(conn: &Connection, entry: &mut Preset) ->
             rusqlite::Result<i64> {
     let options_json = serde_json::to_string(&entry.options)
         .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+    // a blank category is the same as none
+    let category = entry.category.as_deref().map(str::trim)
+        .filter(|c| !c.is_empty());
+    // and a blank key name is the default key
+    let key_name = entry.key_name.as_deref().map(str::trim)
+        .filter(|k| !k.is_empty());
     if entry.id == 0 {
         // 0 means that the preset is brand new, we need to insert it
         conn.execute(
-            "INSERT INTO preset (title, tooltip, chat_router, model, options)
-             VALUES (?1, ?2, ?3, ?4, ?5)",
+            "INSERT INTO preset (title, tooltip, chat_router, model, options,
+                base_url, category, favorite, key_name)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
             params![
                 entry.title,
                 entry.tooltip,
                 &entry.chat_router, // CLEANER: Pass directly, ToSql handles the string conversion
                 entry.model,
-                options_json
+                options_json,
+                entry.base_url,
+                category,
+                entry.favorite,
+                key_name
             ]
         )?;
         Ok(conn.last_insert_rowid())
@@ -717,6 +1270,10 @@ pub fn save_preset(conn: &Connection, entry: &mut Preset) ->
                  chat_router = ?4,
                  model = ?5,
                  options = ?6,
+                 base_url = ?7,
+                 category = ?8,
+                 favorite = ?9,
+                 key_name = ?10,
                  ts_modified = current_timestamp
              WHERE id = ?1",
             params![
@@ -725,7 +1282,11 @@ pub fn save_preset(conn: &Connection, entry: &mut Preset) ->
                 entry.tooltip,
                 &entry.chat_router, // CLEANER: Pass directly
                 entry.model,
-                options_json
+                options_json,
+                entry.base_url,
+                category,
+                entry.favorite,
+                key_name
             ]
         )?;
         if changes == 0 {
@@ -736,11 +1297,202 @@ pub fn save_preset(conn: &Connection, entry: &mut Preset) ->
     }
 }
 
+/// Saves a preset read from a JSON file as a brand new one. Titles are unique,
+/// so " (imported)" is appended until the title is free. Returns the new id.
+pub fn import_preset(conn: &Connection, json: &str) -> Result<i64, MyError> {
+    let json = json.to_string();
+    let mut preset = Preset::from_json(Some(&json))
+        .ok_or(MyError::PresetImport)?;
+    save_preset_as_new(conn, &mut preset, " (imported)")
+}
+
+/// Inserts a copy of the preset under a free title, appending the suffix as
+/// many times as needed. Returns the new id, which is also set on the preset.
+pub fn save_preset_as_new(conn: &Connection, preset: &mut Preset, suffix: &str)
+        -> Result<i64, MyError> {
+    preset.id = 0;
+    preset.hidden = false;
+    preset.deleted = false;
+
+    while conn.query_row("select count(*) from preset where title = ?1",
+            params![preset.title], |row| row.get::<_, i64>(0))? > 0 {
+        preset.title.push_str(suffix);
+    }
+
+    preset.id = save_preset(conn, preset)?;
+    Ok(preset.id)
+}
+
+// tables of a JSON sandbox archive, rows are imported in this order so that
+// whatever a row points at is already there
+const ARCHIVE_TABLES: [&str; 4] = ["preset", "msg", "chat", "agent"];
+const ARCHIVE_FORMAT: &str = "inforno_sandbox";
+
+/// Dumps the presets, messages, chats and agents of the sandbox into one
+/// human-readable JSON document, every row an object keyed by column name.
+pub fn export_sandbox_json(conn: &Connection) -> Result<String, MyError> {
+    let mut tables = Map::new();
+    for table in ARCHIVE_TABLES {
+        tables.insert(table.to_string(),
+            JsonValue::Array(fetch_table_rows(conn, table)?));
+    }
+    let archive = serde_json::json!({
+        "format": ARCHIVE_FORMAT,
+        "version": CURRENT_SANDBOX_VERSION,
+        "tables": tables,
+    });
+    serde_json::to_string_pretty(&archive)
+        .map_err(|e| MyError::SandboxArchive(e.to_string()))
+}
+
+fn fetch_table_rows(conn: &Connection, table: &str)
+        -> rusqlite::Result<Vec<JsonValue>> {
+    let mut stmt = conn.prepare(
+        &format!("select * from {} order by id", table))?;
+    let columns: Vec<String> = stmt.column_names().into_iter()
+        .map(String::from).collect();
+    let rows = stmt.query_map([], |row| {
+        let mut object = Map::new();
+        for (i, column) in columns.iter().enumerate() {
+            let value = match row.get::<_, Value>(i)? {
+                Value::Null => JsonValue::Null,
+                Value::Integer(n) => n.into(),
+                Value::Real(x) => x.into(),
+                Value::Text(s) => s.into(),
+                Value::Blob(_) => JsonValue::Null, // the schema has no blobs
+            };
+            object.insert(column.clone(), value);
+        }
+        Ok(JsonValue::Object(object))
+    })?;
+    rows.collect()
+}
+
+/// Adds everything in a JSON sandbox archive to this sandbox. Rows get new
+/// ids and the references between them are remapped. A preset identical to
+/// one already here is reused, a title clash gets " (imported)" appended.
+/// Returns how many chats were imported.
+pub fn import_sandbox_json(conn: &Connection, json: &str)
+        -> Result<usize, MyError> {
+    let archive: JsonValue = serde_json::from_str(json)
+        .map_err(|e| MyError::SandboxArchive(e.to_string()))?;
+    if archive["format"] != ARCHIVE_FORMAT {
+        return Err(MyError::SandboxArchive(
+            "the file is not an Inforno sandbox archive".to_string()));
+    }
+    let rows = |table: &str| -> Vec<Map<String, JsonValue>> {
+        archive["tables"][table].as_array().map(|rows| rows.iter()
+            .filter_map(|row| row.as_object().cloned()).collect())
+            .unwrap_or_default()
+    };
+    let old_id = |row: &Map<String, JsonValue>| row.get("id")
+        .and_then(JsonValue::as_i64).unwrap_or(0);
+    // old id -> new id, ids that were not imported map to None
+    let remap = |ids: &HashMap<i64, i64>, row: &Map<String, JsonValue>,
+            column: &str| row.get(column).and_then(JsonValue::as_i64)
+            .and_then(|id| ids.get(&id).copied());
+
+    let tx = conn.unchecked_transaction()?;
+
+    let mut preset_ids = HashMap::new();
+    for mut row in rows("preset") {
+        let field = |column: &str| json_to_sql(
+            row.get(column).unwrap_or(&JsonValue::Null));
+        let same: Option<i64> = tx.query_row(
+            "select id from preset where title is ?1 and chat_router is ?2
+                and model is ?3 and options is ?4 and base_url is ?5",
+            params![field("title"), field("chat_router"), field("model"),
+                field("options"), field("base_url")],
+            |r| r.get(0)).optional()?;
+        let new_id = match same {
+            Some(id) => id,
+            None => {
+                let mut title = row.get("title").and_then(JsonValue::as_str)
+                    .unwrap_or_default().to_string();
+                while tx.query_row("select count(*) from preset where title = ?1",
+                        params![title], |r| r.get::<_, i64>(0))? > 0 {
+                    title.push_str(" (imported)");
+                }
+                row.insert("title".to_string(), title.into());
+                // the current default preset stays the default
+                row.insert("is_default".to_string(), 0.into());
+                insert_row(&tx, "preset", &row)?
+            }
+        };
+        preset_ids.insert(old_id(&row), new_id);
+    }
+
+    let mut msg_ids = HashMap::new();
+    for mut row in rows("msg") {
+        let preset_id = remap(&preset_ids, &row, "preset_id").unwrap_or(0);
+        row.insert("preset_id".to_string(), preset_id.into());
+        msg_ids.insert(old_id(&row), insert_row(&tx, "msg", &row)?);
+    }
+
+    // a branch comes after its parent, so the parent is already mapped
+    let mut chat_ids = HashMap::new();
+    for mut row in rows("chat") {
+        let parent = remap(&chat_ids, &row, "parent");
+        row.insert("parent".to_string(), parent.into());
+        chat_ids.insert(old_id(&row), insert_row(&tx, "chat", &row)?);
+    }
+
+    for mut row in rows("agent") {
+        let Some(chat_id) = remap(&chat_ids, &row, "chat_id") else {
+            continue;
+        };
+        let old_msg_ids: Vec<i64> = row.get("msg_ids")
+            .and_then(JsonValue::as_str)
+            .and_then(|text| serde_json::from_str(text).ok())
+            .unwrap_or_default();
+        let new_msg_ids: Vec<i64> = old_msg_ids.iter()
+            .filter_map(|id| msg_ids.get(id).copied()).collect();
+        let preset_id = remap(&preset_ids, &row, "preset_id").unwrap_or(0);
+        row.insert("chat_id".to_string(), chat_id.into());
+        row.insert("msg_ids".to_string(),
+            serde_json::to_string(&new_msg_ids).unwrap_or_default().into());
+        row.insert("preset_id".to_string(), preset_id.into());
+        insert_row(&tx, "agent", &row)?;
+    }
+
+    tx.commit()?;
+    Ok(chat_ids.len())
+}
+
+fn json_to_sql(value: &JsonValue) -> Value {
+    match value {
+        JsonValue::Null => Value::Null,
+        JsonValue::Bool(b) => Value::Integer(*b as i64),
+        JsonValue::Number(n) => n.as_i64().map(Value::Integer)
+            .or_else(|| n.as_f64().map(Value::Real)).unwrap_or(Value::Null),
+        JsonValue::String(s) => Value::Text(s.clone()),
+        other => Value::Text(other.to_string()),
+    }
+}
+
+// inserts the columns of `row` the table knows about, except the id
+fn insert_row(conn: &Connection, table: &str, row: &Map<String, JsonValue>)
+        -> rusqlite::Result<i64> {
+    let known: Vec<String> = conn.prepare(
+            "select name from pragma_table_info(?1)")?
+        .query_map([table], |r| r.get(0))?
+        .collect::<rusqlite::Result<_>>()?;
+    let (columns, values): (Vec<&str>, Vec<Value>) = row.iter()
+        .filter(|(column, _)| *column != "id" && known.contains(*column))
+        .map(|(column, value)| (column.as_str(), json_to_sql(value)))
+        .unzip();
+    conn.execute(&format!("insert into {} ({}) values ({})", table,
+            columns.join(", "), vec!["?"; columns.len()].join(", ")),
+        rusqlite::params_from_iter(values))?;
+    Ok(conn.last_insert_rowid())
+}
+
 pub fn load_presets_vec(conn: &Connection)
         -> rusqlite::Result<Vec<Preset>> {
     let mut stmt = conn.prepare(
-        "select id, title, tooltip, chat_router, model, options, deleted
-        from preset order by title"
+        "select id, title, tooltip, chat_router, model, options, deleted,
+            base_url, category, favorite, key_name
+        from preset order by favorite desc, title"
     )?;
 
     let rows = stmt.query_map([], |row| {
@@ -753,6 +1505,10 @@ pub fn load_presets_vec(conn: &Connection)
             options: serde_json::from_str(&row.get::<_, String>(5)?)
                     .unwrap_or_default(),
             deleted: row.get(6)?,
+            base_url: row.get(7)?,
+            category: row.get(8)?,
+            favorite: row.get(9)?,
+            key_name: row.get(10)?,
             ..Default::default()
         })
     })?;
@@ -808,41 +1564,84 @@ pub fn extract_snippet(text: &str, keyword: &str) -> String {
     }
 }
 
-/// Searches all messages for keywords and returns deduplicated chats containing them
-pub fn search_chats(conn: &Connection, keyword: &str) -> rusqlite::Result<Vec<crate::common::SearchResult>> {
+/// Counts the messages, agents and cost of a chat and lists the models that
+/// replied. Omnis (agent 0) holds every message of the chat.
+pub fn chat_stats(conn: &Connection, chat_id: i64)
+        -> rusqlite::Result<crate::common::ChatStats> {
+    let ts_created = conn.query_row(
+        "select ts_created from chat where id = ?1", [chat_id],
+        |row| row.get(0))?;
+    let agents = conn.query_row(
+        "select count(*) from agent
+         where chat_id = ?1 and agent_ind > 0 and deleted = 0", [chat_id],
+        |row| row.get(0))?;
+    let (msgs, cost) = conn.query_row(
+        "select count(*), coalesce(sum(m.cost), 0)
+         from agent a, json_each(a.msg_ids) j
+         join msg m on m.id = j.value
+         where a.chat_id = ?1 and a.agent_ind = 0", [chat_id],
+        |row| Ok((row.get(0)?, row.get(1)?)))?;
+    let mut stmt = conn.prepare(
+        "select distinct json_extract(m.preset_json, '$.model') as model
+         from agent a, json_each(a.msg_ids) j
+         join msg m on m.id = j.value
+         where a.chat_id = ?1 and a.agent_ind = 0 and m.role = 'assistant'
+            and model is not null and model != ''
+         order by model")?;
+    let models = stmt.query_map([chat_id], |row| row.get(0))?
+        .collect::<rusqlite::Result<_>>()?;
+    Ok(crate::common::ChatStats { msgs, agents, models, ts_created, cost })
+}
+
+/// Searches all messages for a keyword, newest chats first.
+/// Returns (chat_id, msg_id, snippet) for every matching message.
+pub fn search_messages(conn: &Connection, keyword: &str)
+        -> rusqlite::Result<Vec<(i64, i64, String)>> {
     // We use json_each to unpack the msg_ids JSON array directly in SQLite
     let mut stmt = conn.prepare(
-        "SELECT DISTINCT c.id, c.title, m.content, c.ts_created
+        "SELECT DISTINCT c.id, m.id, m.content, c.ts_created
          FROM msg m
          JOIN agent a ON EXISTS (SELECT 1 FROM json_each(a.msg_ids) WHERE value = m.id)
          JOIN chat c ON c.id = a.chat_id
-         WHERE m.content LIKE ?1
-         ORDER BY c.ts_created DESC"
+         WHERE m.content LIKE ?1 AND c.deleted = 0
+         ORDER BY c.ts_created DESC, m.id ASC"
     )?;
 
     let kw_param = format!("%{}%", keyword);
     let rows = stmt.query_map(rusqlite::params![kw_param], |row| {
         Ok((
             row.get::<_, i64>(0)?,
-            row.get::<_, String>(1)?,
+            row.get::<_, i64>(1)?,
             row.get::<_, String>(2)?
         ))
     })?;
 
+    let mut results = Vec::new();
+    for row in rows {
+        let (chat_id, msg_id, content) = row?;
+        results.push((chat_id, msg_id, extract_snippet(&content, keyword)));
+    }
+
+    Ok(results)
+}
+
+/// Searches all messages for keywords and returns deduplicated chats containing them
+pub fn search_chats(conn: &Connection, keyword: &str) -> rusqlite::Result<Vec<crate::common::SearchResult>> {
     let mut unique_results = Vec::new();
     let mut seen = std::collections::HashSet::new();
 
-    for row in rows {
-        let (chat_id, title, content) = row?;
-
+    for (chat_id, msg_id, snippet) in search_messages(conn, keyword)? {
         // We only want one snippet per chat to keep the list clean
-        if !seen.contains(&chat_id) {
-            seen.insert(chat_id);
+        if seen.insert(chat_id) {
+            let title: String = conn.query_row(
+                "SELECT title FROM chat WHERE id = ?1", [chat_id],
+                |row| row.get(0))?;
             let clean_title = title.split('\n').next().unwrap_or(&title).trim().to_string();
             unique_results.push(crate::common::SearchResult {
                 chat_id,
+                msg_id,
                 chat_title: clean_title,
-                snippet: extract_snippet(&content, keyword),
+                snippet,
             });
         }
     }
@@ -865,6 +1664,12 @@ fn create_database_schema(conn: &Connection) -> rusqlite::Result<()> {
         prompt_tokens integer,
         completion_tokens integer,
         cost real,
+        elapsed_ms integer,
+        tokens_per_sec real,
+        pinned integer not null default 0,
+        truncated integer not null default 0,
+        error text,
+        interrupted integer not null default 0,
         ts_created datetime default current_timestamp
     );", ())?;
     // chats contain messages
@@ -872,6 +1677,8 @@ fn create_database_schema(conn: &Connection) -> rusqlite::Result<()> {
         id integer primary key autoincrement,
         parent integer,
         title text,
+        deleted integer not null default 0,
+        pinned integer not null default 0,
         ts_created datetime default current_timestamp,
         ts_modified datetime default current_timestamp
     );", ())?;
@@ -917,8 +1724,13 @@ fn create_database_schema(conn: &Connection) -> rusqlite::Result<()> {
         chat_router text,
         model text,
         options text,
+        base_url text not null default '',
+        category text,
+        favorite integer not null default 0,
+        key_name text,
         hidden integer not null default 0,
         deleted integer not null default 0,
+        is_default integer not null default 0,
         ts_created datetime default current_timestamp,
         ts_modified datetime default current_timestamp
     )", ())?;
@@ -963,23 +1775,25 @@ fn create_database_schema(conn: &Connection) -> rusqlite::Result<()> {
 /// Exports a chat to a Markdown-formatted string.
 pub fn export_chat_to_markdown(conn: &Connection, chat_id: i64, presets: &Presets) -> rusqlite::Result<String> {
     let chat = fetch_chat(conn, chat_id, presets)?;
+    Ok(chat_to_markdown(&chat))
+}
+
+/// Walks Omnis' (agent 0) messages in order and renders them as Markdown,
+/// with the reasoning in collapsible <details> blocks.
+pub fn chat_to_markdown(chat: &Chat) -> String {
     let mut md = String::new();
     md.push_str(&format!("# {}\n\n", chat.title));
 
     if let Some(agent) = chat.agents.first() {
         for msg_id in &agent.msg_ids {
             if let Some(msg) = chat.msg_pool.get(msg_id) {
-                let role_label = match msg.msg_role {
-                    crate::common::MsgRole::User => "User",
-                    crate::common::MsgRole::Assistant => {
-                        msg.name.as_deref().unwrap_or("Assistant")
-                    },
-                    crate::common::MsgRole::System => "System",
-                    crate::common::MsgRole::Developer => "Developer",
-                    crate::common::MsgRole::Tool => "Tool",
-                };
-
-                md.push_str(&format!("## {}\n\n", role_label));
+                // e.g. "## user" or "## assistant (Agent1)"
+                match (&msg.msg_role, &msg.name) {
+                    (crate::common::MsgRole::Assistant, Some(name)) => {
+                        md.push_str(&format!("## {} ({})\n\n", msg.msg_role, name));
+                    }
+                    _ => md.push_str(&format!("## {}\n\n", msg.msg_role)),
+                }
 
                 if let Some(reasoning) = &msg.reasoning {
                     if !reasoning.is_empty() {
@@ -995,7 +1809,48 @@ pub fn export_chat_to_markdown(conn: &Connection, chat_id: i64, presets: &Preset
         }
     }
 
-    Ok(md)
+    md
+}
+
+/// Writes every chat as its own Markdown file into `dir`, named after the
+/// title and the chat id. Files already there are never overwritten: the
+/// name gets a number appended instead. Returns how many files were written.
+pub fn export_all_chats_markdown(conn: &Connection, dir: &Path,
+        presets: &Presets) -> Result<usize, MyError> {
+    fs::create_dir_all(dir)?;
+    let mut count = 0;
+    for db_chat in fetch_chat_titles(conn)? {
+        let chat = fetch_chat(conn, db_chat.id, presets)?;
+        let stem = format!("{} ({})", markdown_file_stem(&db_chat.title),
+            db_chat.id);
+        let mut n = 1;
+        let mut file = loop {
+            let name = if n == 1 {
+                format!("{}.md", stem)
+            } else {
+                format!("{} {}.md", stem, n)
+            };
+            match fs::OpenOptions::new().write(true).create_new(true)
+                    .open(dir.join(name)) {
+                Ok(file) => break file,
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => n += 1,
+                Err(e) => return Err(e.into()),
+            }
+        };
+        std::io::Write::write_all(&mut file, chat_to_markdown(&chat).as_bytes())?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+/// A chat title made safe to use as a file name on every platform.
+fn markdown_file_stem(title: &str) -> String {
+    let stem: String = title.chars()
+        .map(|c| if c.is_alphanumeric() || " -_.,()".contains(c) { c } else { '_' })
+        .take(60)
+        .collect();
+    let stem = stem.trim().trim_end_matches('.');
+    if stem.is_empty() { "chat".to_string() } else { stem.to_string() }
 }
 
 #[cfg(test)]
@@ -1003,6 +1858,7 @@ mod tests {
     use std::fmt::Write;
 
     use super::*;
+    use crate::common::{ChatRouter, MsgRole};
     use rusqlite::Connection;
 
     // Helper to create an in-memory DB and apply the schema
@@ -1039,6 +1895,653 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_preset_max_tokens_roundtrip() {
+        let conn = setup_db();
+
+        let mut preset = Preset {
+            title: "Short Answers".to_string(),
+            model: "llama3".to_string(),
+            ..Default::default()
+        };
+        preset.options.max_tokens = Some(256);
+        preset.options.presence_penalty = Some(0.5);
+        preset.id = save_preset(&conn, &mut preset)
+            .expect("failed to save preset");
+
+        let presets = load_presets_vec(&conn).expect("failed to load presets");
+        let loaded = presets.iter().find(|p| p.id == preset.id)
+            .expect("saved preset not found");
+        assert_eq!(loaded.options.max_tokens, Some(256));
+        assert_eq!(loaded.options.presence_penalty, Some(0.5));
+        assert_eq!(loaded.options.frequency_penalty, None);
+    }
+
+    #[test]
+    fn test_preset_openai_compatible_roundtrip() {
+        let conn = setup_db();
+
+        let mut preset = Preset {
+            title: "Local vLLM".to_string(),
+            chat_router: ChatRouter::OpenAICompatible,
+            model: "Qwen/Qwen3-8B".to_string(),
+            base_url: "http://localhost:8000/v1".to_string(),
+            ..Default::default()
+        };
+        preset.id = save_preset(&conn, &mut preset)
+            .expect("failed to save preset");
+
+        let presets = load_presets_vec(&conn).expect("failed to load presets");
+        let loaded = presets.iter().find(|p| p.id == preset.id)
+            .expect("saved preset not found");
+        assert_eq!(loaded.chat_router, ChatRouter::OpenAICompatible);
+        assert_eq!(loaded.base_url, "http://localhost:8000/v1");
+    }
+
+    #[test]
+    fn test_preset_category_roundtrip() {
+        let conn = setup_db();
+
+        let mut preset = Preset {
+            title: "Coder".to_string(),
+            category: Some(" Coding ".to_string()),
+            ..Default::default()
+        };
+        preset.id = save_preset(&conn, &mut preset)
+            .expect("failed to save preset");
+
+        let presets = load_presets_vec(&conn).expect("failed to load presets");
+        let loaded = presets.iter().find(|p| p.id == preset.id)
+            .expect("saved preset not found");
+        assert_eq!(loaded.category.as_deref(), Some("Coding"));
+
+        // a blank category is stored as none
+        preset.category = Some("  ".to_string());
+        save_preset(&conn, &mut preset).expect("failed to update preset");
+        let presets = load_presets_vec(&conn).expect("failed to load presets");
+        let loaded = presets.iter().find(|p| p.id == preset.id)
+            .expect("saved preset not found");
+        assert_eq!(loaded.category, None);
+    }
+
+    #[test]
+    fn test_favorite_presets_first() {
+        let conn = setup_db();
+
+        for (title, favorite) in [("Alpha", false), ("Zulu", true),
+                ("Beta", false), ("Yankee", true)] {
+            let mut preset = Preset {
+                title: title.to_string(),
+                favorite,
+                ..Default::default()
+            };
+            save_preset(&conn, &mut preset).expect("failed to save preset");
+        }
+
+        let titles: Vec<String> = load_presets_vec(&conn)
+            .expect("failed to load presets")
+            .into_iter()
+            .map(|p| p.title)
+            .filter(|t| ["Alpha", "Beta", "Yankee", "Zulu"].contains(&t.as_str()))
+            .collect();
+        assert_eq!(titles, ["Yankee", "Zulu", "Alpha", "Beta"]);
+    }
+
+    #[test]
+    fn test_preset_key_name() {
+        let conn = setup_db();
+
+        for (title, key_name) in [("Work", Some("work")), ("Blank", Some("  ")),
+                ("Default", None)] {
+            let mut preset = Preset {
+                title: title.to_string(),
+                key_name: key_name.map(str::to_string),
+                ..Default::default()
+            };
+            save_preset(&conn, &mut preset).expect("failed to save preset");
+        }
+
+        let presets = load_presets_vec(&conn).expect("failed to load presets");
+        let key_name = |title: &str| presets.iter()
+            .find(|p| p.title == title)
+            .and_then(|p| p.key_name.clone());
+        assert_eq!(key_name("Work").as_deref(), Some("work"));
+        // a blank name bills to the default key
+        assert_eq!(key_name("Blank"), None);
+        assert_eq!(key_name("Default"), None);
+    }
+
+    #[test]
+    fn test_preset_stop_sequences_json() {
+        let mut preset = Preset::default();
+        preset.options.stop = vec!["###".to_string(), "  ".to_string()];
+
+        let json = preset.to_json().expect("failed to serialize preset");
+        let restored = Preset::from_json(Some(&json))
+            .expect("failed to deserialize preset");
+        assert_eq!(restored.options.stop, preset.options.stop);
+        assert_eq!(restored.options.stop_sequences(), vec!["###".to_string()]);
+
+        // options saved before stop sequences existed must still load
+        let legacy: crate::common::ModelOptions = serde_json::from_str(r#"{"seed":42}"#)
+            .expect("failed to parse legacy options");
+        assert!(legacy.stop.is_empty());
+        assert!(legacy.frequency_penalty.is_none());
+    }
+
+    #[test]
+    fn test_delete_msg() {
+        let conn = setup_db();
+
+        let mut msg = ChatMsg {
+            content: "a bad answer".to_string(),
+            ..Default::default()
+        };
+        mk_msg(&conn, &mut msg).expect("failed to create msg");
+        delete_msg(&conn, msg.id).expect("failed to delete msg");
+
+        let count: i64 = conn.query_row("select count(*) from msg", [],
+            |row| row.get(0)).unwrap();
+        assert_eq!(count, 0);
+    }
+
+    #[test]
+    fn test_chat_to_markdown() {
+        let mut chat = Chat::default();
+        let user = ChatMsg {
+            id: 1,
+            content: "Hi".to_string(),
+            ..Default::default()
+        };
+        let assistant = ChatMsg {
+            id: 2,
+            msg_role: crate::common::MsgRole::Assistant,
+            content: "Hello!".to_string(),
+            reasoning: Some("greet back".to_string()),
+            name: Some("Agent1".to_string()),
+            ..Default::default()
+        };
+        chat.msg_pool.insert(1, user);
+        chat.msg_pool.insert(2, assistant);
+        chat.agents[0].msg_ids = vec![1, 2];
+
+        let md = chat_to_markdown(&chat);
+        assert!(md.contains("## user\n\nHi"));
+        assert!(md.contains("## assistant (Agent1)\n\n<details>"));
+        assert!(md.contains("greet back\n\n</details>\n\nHello!"));
+    }
+
+    #[test]
+    fn test_sandbox_json_roundtrip() {
+        let source = setup_db();
+        let mut preset = Preset {
+            title: "Shared".to_string(),
+            ..Default::default()
+        };
+        preset.id = save_preset(&source, &mut preset)
+            .expect("failed to save preset");
+
+        let mut chat = Chat::default();
+        for content in ["question", "answer"] {
+            let mut msg = ChatMsg {
+                content: content.to_string(),
+                preset_id: preset.id,
+                ..Default::default()
+            };
+            mk_msg(&source, &mut msg).expect("failed to create msg");
+            chat.agents[0].msg_ids.push(msg.id);
+            chat.agents[1].msg_ids.push(msg.id);
+        }
+        mk_chat(&source, &mut chat).expect("failed to create chat");
+        branch_chat(&source, chat.id, chat.agents[0].msg_ids[0], "branch")
+            .expect("failed to branch");
+        let json = export_sandbox_json(&source).expect("failed to export");
+
+        // the target already has messages, so the ids must be remapped
+        let target = setup_db();
+        let mut other = ChatMsg {
+            content: "unrelated".to_string(),
+            ..Default::default()
+        };
+        mk_msg(&target, &mut other).expect("failed to create msg");
+        assert_eq!(import_sandbox_json(&target, &json).expect("failed to import"), 2);
+        // importing again reuses the identical preset
+        assert_eq!(import_sandbox_json(&target, &json).expect("failed to import"), 2);
+
+        let presets = load_presets_vec(&target).expect("failed to load presets");
+        assert_eq!(presets.iter().filter(|p| p.title == "Shared").count(), 1);
+        let titles = fetch_chat_titles(&target).expect("failed to fetch titles");
+        assert_eq!(titles.len(), 4);
+        let branch = titles.iter()
+            .find(|c| c.parent.is_some()).expect("branch lost its parent");
+        assert!(titles.iter().any(|c| Some(c.id) == branch.parent));
+
+        let imported = titles.iter().find(|c| c.parent.is_none()).unwrap();
+        let imported = fetch_chat(&target, imported.id, &Presets::default())
+            .expect("failed to fetch chat");
+        let ids = imported.agents[1].msg_ids.clone();
+        assert_eq!(ids.len(), 2);
+        assert!(!ids.contains(&other.id));
+        assert_eq!(imported.msg_pool[&ids[1]].content, "answer");
+        let shared_id = presets.iter().find(|p| p.title == "Shared").unwrap().id;
+        assert_eq!(imported.msg_pool[&ids[0]].preset_id, shared_id);
+
+        assert!(import_sandbox_json(&target, "{}").is_err());
+    }
+
+    #[test]
+    fn test_sandbox_json_import_keeps_default_preset() {
+        let source = setup_db();
+        let mut imported = Preset {
+            title: "Imported".to_string(),
+            ..Default::default()
+        };
+        imported.id = save_preset(&source, &mut imported)
+            .expect("failed to save preset");
+        set_default_preset(&source, imported.id)
+            .expect("failed to set default preset");
+        let json = export_sandbox_json(&source).expect("failed to export");
+
+        let target = setup_db();
+        let mut current = Preset {
+            title: "Current".to_string(),
+            ..Default::default()
+        };
+        current.id = save_preset(&target, &mut current)
+            .expect("failed to save preset");
+        set_default_preset(&target, current.id)
+            .expect("failed to set default preset");
+        import_sandbox_json(&target, &json).expect("failed to import");
+
+        let defaults: i64 = target.query_row(
+            "select count(*) from preset where is_default = 1", [],
+            |r| r.get(0)).expect("failed to count defaults");
+        assert_eq!(defaults, 1);
+        assert_eq!(get_default_preset_id(&target)
+            .expect("failed to get default preset"), current.id);
+    }
+
+    #[test]
+    fn test_chat_trash() {
+        let conn = setup_db();
+        let mut chat = Chat::default();
+        let mut msg = ChatMsg {
+            content: "needle".to_string(),
+            ..Default::default()
+        };
+        mk_msg(&conn, &mut msg).expect("failed to create msg");
+        chat.agents[0].msg_ids.push(msg.id);
+        mk_chat(&conn, &mut chat).expect("failed to create chat");
+
+        delete_chat(&conn, chat.id).expect("failed to delete chat");
+        assert!(fetch_chat_titles(&conn).unwrap().is_empty());
+        assert!(search_chats(&conn, "needle").unwrap().is_empty());
+        let trash = fetch_deleted_chat_titles(&conn).unwrap();
+        assert_eq!(trash.len(), 1);
+        assert_eq!(trash[0].id, chat.id);
+
+        restore_chat(&conn, chat.id).expect("failed to restore chat");
+        assert_eq!(fetch_chat_titles(&conn).unwrap().len(), 1);
+        assert!(fetch_deleted_chat_titles(&conn).unwrap().is_empty());
+
+        // a branch outlives the purged chat, at the top level
+        let branch_id = branch_chat(&conn, chat.id, msg.id, "branch")
+            .expect("failed to branch chat");
+        delete_chat(&conn, chat.id).expect("failed to delete chat");
+        purge_chat(&conn, chat.id).expect("failed to purge chat");
+        assert!(fetch_deleted_chat_titles(&conn).unwrap().is_empty());
+        let titles = fetch_chat_titles(&conn).unwrap();
+        assert_eq!(titles.len(), 1);
+        assert_eq!(titles[0].id, branch_id);
+        assert_eq!(titles[0].parent, None);
+        // only the branch's copy of the message is left
+        let msgs: i64 = conn.query_row("select count(*) from msg", [],
+            |row| row.get(0)).unwrap();
+        assert_eq!(msgs, 1);
+        assert!(search_chats(&conn, "needle").unwrap().iter()
+            .all(|found| found.chat_id == branch_id));
+    }
+
+    #[test]
+    fn test_pinned_chats_first() {
+        let conn = setup_db();
+        let mut ids = Vec::new();
+        for _ in 0..3 {
+            let mut chat = Chat::default();
+            mk_chat(&conn, &mut chat).expect("failed to create chat");
+            ids.push(chat.id);
+        }
+        // same second, so the newest chat comes first by id
+        mod_chat_pinned(&conn, ids[0], true).expect("failed to pin");
+        let titles = fetch_chat_titles(&conn).unwrap();
+        let order: Vec<i64> = titles.iter().map(|c| c.id).collect();
+        assert_eq!(order, vec![ids[0], ids[2], ids[1]]);
+        assert!(titles[0].pinned && !titles[1].pinned);
+    }
+
+    #[test]
+    fn test_swap_agent_inds() {
+        let conn = setup_db();
+        let mut chat = Chat::default();
+        chat.add_agent_try_sync(&conn).expect("failed to add agent");
+        mk_chat(&conn, &mut chat).expect("failed to create chat");
+        let (first, second) = (chat.agents[1].id, chat.agents[2].id);
+
+        swap_agent_inds(&conn, first, second).expect("failed to swap");
+        let chat = fetch_chat(&conn, chat.id, &Presets::default())
+            .expect("failed to fetch chat");
+        assert_eq!(chat.agents[1].id, second);
+        assert_eq!(chat.agents[2].id, first);
+        assert_eq!(chat.agents[2].agent_ind, 2);
+    }
+
+    #[test]
+    fn test_import_preset_title_clash() {
+        let conn = setup_db();
+
+        let mut preset = Preset {
+            title: "Shared".to_string(),
+            ..Default::default()
+        };
+        save_preset(&conn, &mut preset).expect("failed to save preset");
+
+        let json = preset.to_json().expect("failed to serialize preset");
+        let first = import_preset(&conn, &json).expect("failed to import");
+        let second = import_preset(&conn, &json).expect("failed to import");
+
+        let presets = load_presets_vec(&conn).expect("failed to load presets");
+        let title_of = |id| presets.iter().find(|p| p.id == id).unwrap()
+            .title.clone();
+        assert_eq!(title_of(first), "Shared (imported)");
+        assert_eq!(title_of(second), "Shared (imported) (imported)");
+        assert!(import_preset(&conn, "not json").is_err());
+    }
+
+    #[test]
+    fn test_save_preset_as_new() {
+        let conn = setup_db();
+
+        let mut preset = Preset {
+            title: "Tuned".to_string(),
+            model: "some-model".to_string(),
+            ..Default::default()
+        };
+        let original_id = save_preset(&conn, &mut preset)
+            .expect("failed to save preset");
+        preset.id = original_id;
+
+        let mut copy = preset.clone();
+        copy.options.temperature = Some(0.2);
+        let id = save_preset_as_new(&conn, &mut copy, " (copy)")
+            .expect("failed to save copy");
+
+        assert_ne!(id, original_id);
+        assert_eq!(copy.id, id);
+        let presets = load_presets_vec(&conn).expect("failed to load presets");
+        let saved = presets.iter().find(|p| p.id == id).unwrap();
+        assert_eq!(saved.title, "Tuned (copy)");
+        assert_eq!(saved.options.temperature, Some(0.2));
+        let original = presets.iter().find(|p| p.id == original_id).unwrap();
+        assert_eq!(original.options.temperature, None);
+    }
+
+    #[test]
+    fn test_default_preset() {
+        let conn = setup_db();
+        assert_eq!(get_default_preset_id(&conn).unwrap(), 0);
+
+        let mut first = Preset {
+            title: "First".to_string(),
+            ..Default::default()
+        };
+        let first_id = save_preset(&conn, &mut first).unwrap();
+        let mut second = Preset {
+            title: "Second".to_string(),
+            ..Default::default()
+        };
+        let second_id = save_preset(&conn, &mut second).unwrap();
+
+        set_default_preset(&conn, first_id).unwrap();
+        assert_eq!(get_default_preset_id(&conn).unwrap(), first_id);
+        // only one preset is the default at a time
+        set_default_preset(&conn, second_id).unwrap();
+        assert_eq!(get_default_preset_id(&conn).unwrap(), second_id);
+        set_default_preset(&conn, 0).unwrap();
+        assert_eq!(get_default_preset_id(&conn).unwrap(), 0);
+
+        set_default_preset(&conn, second_id).unwrap();
+        delete_preset(&conn, second_id).unwrap();
+        assert_eq!(get_default_preset_id(&conn).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_search_messages() {
+        let conn = setup_db();
+
+        let mut chat = Chat::default();
+        for content in ["the quick brown fox", "lazy dog", "another fox"] {
+            let mut msg = ChatMsg {
+                content: content.to_string(),
+                ..Default::default()
+            };
+            mk_msg(&conn, &mut msg).expect("failed to create msg");
+            // Omnis and Agent1 both reference every message
+            chat.agents[0].msg_ids.push(msg.id);
+            chat.agents[1].msg_ids.push(msg.id);
+        }
+        mk_chat(&conn, &mut chat).expect("failed to create chat");
+
+        let hits = search_messages(&conn, "FOX").expect("search failed");
+        let msg_ids: Vec<i64> = hits.iter().map(|h| h.1).collect();
+        assert_eq!(msg_ids, vec![chat.agents[0].msg_ids[0],
+            chat.agents[0].msg_ids[2]]);
+        assert!(hits.iter().all(|h| h.0 == chat.id));
+
+        let chats = search_chats(&conn, "fox").expect("search failed");
+        assert_eq!(chats.len(), 1);
+        assert_eq!(chats[0].msg_id, chat.agents[0].msg_ids[0]);
+    }
+
+    #[test]
+    fn test_backup_sandbox_prunes_old_backups() {
+        let conn = setup_db();
+        let dir = std::env::temp_dir().join(format!("inforno_backup_test_{}",
+            std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let sandbox = dir.join("info.rno");
+
+        let mut last = PathBuf::new();
+        for _ in 0..SANDBOX_BACKUPS_KEPT + 2 {
+            last = backup_sandbox(&conn, &sandbox).expect("backup failed");
+            // backup names have millisecond resolution
+            std::thread::sleep(std::time::Duration::from_millis(2));
+        }
+
+        let kept = fs::read_dir(dir.join("backups")).unwrap().count();
+        assert_eq!(kept, SANDBOX_BACKUPS_KEPT);
+        let backup = Connection::open(&last).unwrap();
+        assert!(table_exists(&backup, "preset").unwrap());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_export_all_chats_markdown() {
+        let conn = setup_db();
+        let dir = std::env::temp_dir().join(format!("inforno_md_export_test_{}",
+            std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+
+        let mut ids = Vec::new();
+        for title in ["Plans: a/b?", "Plans: a/b?"] {
+            let mut chat = Chat { title: title.to_string(), ..Default::default() };
+            mk_chat(&conn, &mut chat).expect("failed to create chat");
+            ids.push(chat.id);
+        }
+
+        let count = export_all_chats_markdown(&conn, &dir, &Presets::default())
+            .expect("export failed");
+        assert_eq!(count, 2);
+        for id in &ids {
+            let md = fs::read_to_string(dir.join(format!("Plans_ a_b_ ({}).md", id)))
+                .expect("missing export");
+            assert!(md.starts_with("# Plans: a/b?"));
+        }
+
+        // a second export keeps the first one's files
+        export_all_chats_markdown(&conn, &dir, &Presets::default())
+            .expect("export failed");
+        assert!(dir.join(format!("Plans_ a_b_ ({}) 2.md", ids[0])).exists());
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 4);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_migrate_v1_to_current() {
+        let conn = setup_db();
+        // pretend this is a version 1 sandbox that lacks usage columns
+        conn.execute("alter table msg drop column cost", []).unwrap();
+        conn.execute("alter table agent drop column muted", []).unwrap();
+        conn.execute("alter table preset drop column base_url", []).unwrap();
+        conn.execute("alter table preset drop column category", []).unwrap();
+        conn.execute("alter table msg drop column elapsed_ms", []).unwrap();
+        conn.execute("alter table msg drop column tokens_per_sec", []).unwrap();
+        conn.execute("alter table chat drop column deleted", []).unwrap();
+        conn.execute("alter table msg drop column pinned", []).unwrap();
+        conn.execute("alter table msg drop column truncated", []).unwrap();
+        conn.execute("alter table msg drop column error", []).unwrap();
+        conn.execute("alter table msg drop column interrupted", []).unwrap();
+        conn.execute("alter table preset drop column is_default", []).unwrap();
+        conn.execute("alter table chat drop column pinned", []).unwrap();
+        conn.execute("alter table preset drop column favorite", []).unwrap();
+        conn.execute("alter table preset drop column key_name", []).unwrap();
+        conn.execute("update schema_version set version = 1", []).unwrap();
+
+        migrate_sandbox_db(&conn, 1, CURRENT_SANDBOX_VERSION)
+            .expect("migration failed");
+        assert!(column_exists(&conn, "msg", "cost").unwrap());
+        assert!(column_exists(&conn, "agent", "muted").unwrap());
+        assert!(column_exists(&conn, "preset", "base_url").unwrap());
+        assert!(column_exists(&conn, "preset", "category").unwrap());
+        assert!(column_exists(&conn, "msg", "elapsed_ms").unwrap());
+        assert!(column_exists(&conn, "msg", "tokens_per_sec").unwrap());
+        assert!(column_exists(&conn, "chat", "deleted").unwrap());
+        assert!(column_exists(&conn, "msg", "pinned").unwrap());
+        assert!(column_exists(&conn, "msg", "truncated").unwrap());
+        assert!(column_exists(&conn, "msg", "error").unwrap());
+        assert!(column_exists(&conn, "msg", "interrupted").unwrap());
+        assert!(column_exists(&conn, "preset", "is_default").unwrap());
+        assert!(column_exists(&conn, "chat", "pinned").unwrap());
+        assert!(column_exists(&conn, "preset", "favorite").unwrap());
+        assert!(column_exists(&conn, "preset", "key_name").unwrap());
+        let version: i32 = conn.query_row(
+            "select version from schema_version", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(version, CURRENT_SANDBOX_VERSION);
+
+        // running a step again must be harmless
+        migrate_sandbox_db(&conn, 1, CURRENT_SANDBOX_VERSION)
+            .expect("repeated migration failed");
+    }
+
+    #[test]
+    fn test_chat_stats() {
+        let conn = setup_db();
+
+        let mut chat = Chat::default();
+        for (role, model) in [(MsgRole::User, ""), (MsgRole::Assistant, "b"),
+                (MsgRole::Assistant, "a"), (MsgRole::Assistant, "b")] {
+            let mut msg = ChatMsg {
+                msg_role: role,
+                preset: Some(Preset {
+                    model: model.to_string(),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            };
+            mk_msg(&conn, &mut msg).expect("failed to create msg");
+            chat.agents[0].msg_ids.push(msg.id);
+            chat.agents[1].msg_ids.push(msg.id);
+        }
+        mk_chat(&conn, &mut chat).expect("failed to create chat");
+        conn.execute("update msg set cost = 0.25 where role = 'assistant'", [])
+            .unwrap();
+
+        let stats = chat_stats(&conn, chat.id).expect("failed to get stats");
+        assert_eq!(stats.msgs, 4);
+        assert_eq!(stats.agents, 1);
+        assert_eq!(stats.models, ["a", "b"]);
+        assert!(stats.ts_created.is_some());
+        assert!((stats.cost - 0.75).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_branch_chat() {
+        let conn = setup_db();
+
+        let mut chat = Chat::default();
+        for content in ["first", "second", "third"] {
+            let mut msg = ChatMsg {
+                content: content.to_string(),
+                ..Default::default()
+            };
+            mk_msg(&conn, &mut msg).expect("failed to create msg");
+            chat.agents[0].msg_ids.push(msg.id);
+            chat.agents[1].msg_ids.push(msg.id);
+        }
+        mk_chat(&conn, &mut chat).expect("failed to create chat");
+        let ids = chat.agents[0].msg_ids.clone();
+
+        let branch_id = branch_chat(&conn, chat.id, ids[1], "branch")
+            .expect("failed to branch");
+        let branch = fetch_chat(&conn, branch_id, &Presets::default())
+            .expect("failed to fetch branch");
+        assert_eq!(branch.agents.len(), 2);
+        let branch_ids = branch.agents[0].msg_ids.clone();
+        assert_eq!(branch_ids.len(), 2);
+        assert_eq!(branch.agents[1].msg_ids, branch_ids);
+        assert!(branch_ids.iter().all(|id| !ids.contains(id)));
+        assert_eq!(branch.msg_pool[&branch_ids[1]].content, "second");
+
+        let titles = fetch_chat_titles(&conn).expect("failed to fetch titles");
+        let branch_title = titles.iter().find(|c| c.id == branch_id).unwrap();
+        assert_eq!(branch_title.parent, Some(chat.id));
+    }
+
+    #[test]
+    fn test_duplicate_chat() {
+        let conn = setup_db();
+
+        let mut chat = Chat::default();
+        for content in ["question", "answer"] {
+            let mut msg = ChatMsg {
+                content: content.to_string(),
+                ..Default::default()
+            };
+            mk_msg(&conn, &mut msg).expect("failed to create msg");
+            chat.agents[0].msg_ids.push(msg.id);
+        }
+        let answer_id = chat.agents[0].msg_ids[1];
+        chat.agents[1].msg_ids.push(answer_id);
+        mk_chat(&conn, &mut chat).expect("failed to create chat");
+
+        let copy_id = duplicate_chat(&conn, chat.id, "copy").expect("failed to duplicate");
+        let copy = fetch_chat(&conn, copy_id, &Presets::default())
+            .expect("failed to fetch copy");
+        let copy_ids = copy.agents[0].msg_ids.clone();
+        assert_eq!(copy_ids.len(), 2);
+        assert_eq!(copy.agents[1].msg_ids, vec![copy_ids[1]]);
+        assert!(copy_ids.iter().all(|id| !chat.agents[0].msg_ids.contains(id)));
+        assert_eq!(copy.msg_pool[&copy_ids[1]].content, "answer");
+
+        // a copy is a new top level chat, listed first
+        let titles = fetch_chat_titles(&conn).expect("failed to fetch titles");
+        assert_eq!(titles[0].id, copy_id);
+        assert_eq!(titles[0].parent, None);
+    }
+
     #[test]
     fn test_agents() {
 /*        let conn = setup_db();
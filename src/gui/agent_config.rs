@@ -1,11 +1,12 @@
 use egui::{Vec2b, Window};
 use rust_i18n::t;
-use crate::common::ModelOptions;
-use crate::db::update_agent_preset_snapshot;
+use crate::common::{ChatRouter, ModelOptions, PresetSelection, err_color, load_presets, strong_color};
+use crate::db::{mod_agent_preset, save_preset_as_new, update_agent_preset_snapshot};
 use crate::gui::State;
+use crate::gui::autocomplete::AutoCompleteTextEdit;
 // Import the reusable components
 use crate::gui::preset_editor::{
-    PresetEditorState, render_common_options,
+    PresetEditorState, check_edited_options, render_common_options,
 };
 
 /// The state specific to the Agent Config Window
@@ -15,6 +16,9 @@ pub struct AgentConfigState {
     pub target_agent_id: Option<i64>, // the db id of the agent we are modifying
     pub target_agent_ind: Option<usize>, // index inside Chat
     pub editor_state: PresetEditorState, // reusing the editor state struct
+    pub renaming_agent_ind: Option<usize>, // agent whose name is being edited
+    pub rename_buffer: String,
+    pub moving_agent: Option<(usize, bool)>, // (agent index, up), applied after drawing
 }
 
 pub fn ui_agent_config(ctx: &egui::Context, state: &mut State) {
@@ -42,11 +46,14 @@ pub fn ui_agent_config(ctx: &egui::Context, state: &mut State) {
         match router {
             ChatRouter::Ollama => render_ollama_editor(ui, ctx, state),
             ChatRouter::Openrouter => render_openrouter_editor(ui, state),
+            ChatRouter::OpenAICompatible => render_openai_editor(ui, state),
         }*/
         ui.colored_label(
             ui.visuals().hyperlink_color, t!("config_editor_invitation"),
         );
 
+        render_model_edit(ui, state);
+
         if let Some(original_preset) = state.presets.get(
                     state.agent_config_state.editor_state.edited_preset.id) {
             render_common_options(ui, &mut state.agent_config_state.editor_state,
@@ -62,11 +69,28 @@ pub fn ui_agent_config(ctx: &egui::Context, state: &mut State) {
         ui.add_space(10.0);
         ui.separator();
         ui.horizontal(|ui| {
-            if ui.button(t!("agent_config_save_changes_btn")).clicked() {
+            // an invalid json schema keeps the window open to be fixed
+            let can_save = |state: &mut State| check_edited_options(
+                &state.agent_config_state.editor_state.edited_preset.options,
+                &mut state.error_msg);
+            let readonly = *state.perma.readonly.lock().unwrap();
+
+            if ui.add_enabled(!readonly,
+                    egui::Button::new(t!("agent_config_save_changes_btn")))
+                    .clicked()
+                    && can_save(state) {
                 save_agent_preset(state);
                 should_close = true;
             }
 
+            if ui.add_enabled(!readonly,
+                    egui::Button::new(t!("agent_config_save_as_preset_btn")))
+                    .on_hover_text(t!("agent_config_save_as_preset_tooltip"))
+                    .clicked() && can_save(state) {
+                save_agent_preset_as_new(state);
+                should_close = true;
+            }
+
             if ui.button(t!("cancel_btn")).clicked() {
                 should_close = true;
             }
@@ -81,6 +105,35 @@ pub fn ui_agent_config(ctx: &egui::Context, state: &mut State) {
     state.agent_config_state.is_open = is_open;
 }
 
+// swaps the model of the agent's snapshot, the preset itself stays as it is
+fn render_model_edit(ui: &mut egui::Ui, state: &mut State) {
+    let edited = &mut state.agent_config_state.editor_state.edited_preset;
+    // OpenAI compatible servers have no model list to suggest from
+    let no_models = Vec::new();
+    let names = match edited.chat_router {
+        ChatRouter::Ollama => &state.ollama_model_names,
+        ChatRouter::Openrouter => &state.openr_model_names,
+        ChatRouter::OpenAICompatible => &no_models,
+    };
+    let model_color = if names.is_empty() || names.contains(&edited.model) {
+        strong_color()
+    } else {
+        err_color()
+    };
+    ui.horizontal(|ui| {
+        ui.label(t!("select_a_model"));
+        ui.add(
+            AutoCompleteTextEdit::new(&mut edited.model, names)
+                .max_suggestions(10)
+                .highlight_matches(true)
+                .set_text_edit_properties(move |t| {
+                    t.text_color(model_color)
+                    .desired_width(f32::INFINITY)
+                }),
+        ).on_hover_text(t!("agent_model_tooltip"));
+    });
+}
+
 fn save_agent_preset(state: &mut State) {
     let edited = &state.agent_config_state.editor_state.edited_preset;
         let active_chat_id = state.active_chat_id.unwrap_or(0);
@@ -98,4 +151,36 @@ fn save_agent_preset(state: &mut State) {
             }
         }
     }
-}
\ No newline at end of file
+}
+
+// promotes the agent's tweaked snapshot to a real preset of its own and
+// switches the agent over to it
+fn save_agent_preset_as_new(state: &mut State) {
+    let active_chat_id = state.active_chat_id.unwrap_or(0);
+    let Some(agent_ind) = state.agent_config_state.target_agent_ind else {
+        return;
+    };
+    let mut preset = state.agent_config_state.editor_state.edited_preset.clone();
+    let id = match save_preset_as_new(&state.db_conn, &mut preset, " (copy)") {
+        Ok(id) => id,
+        Err(e) => {
+            state.error_msg = Some(format!("{}: {}",
+                t!("error_saving_preset_as_new"), e));
+            state.is_modal_open = true;
+            return;
+        }
+    };
+    load_presets(&state.db_conn, &mut state.presets);
+
+    let Some(agent) = state.open_chats.get_mut(&active_chat_id)
+            .and_then(|chat| chat.agents.get_mut(agent_ind)) else {
+        return;
+    };
+    agent.preset_selection = PresetSelection::from_id(id, &state.presets);
+    agent.preset = state.presets.get(id).cloned();
+    if mod_agent_preset(&state.db_conn, agent.id, id, agent.preset.as_ref()).is_err() {
+        state.error_msg =
+            Some(t!("error_saving_agent_config_to_sandbox").to_string());
+        state.is_modal_open = true;
+    }
+}
@@ -1,10 +1,11 @@
+use std::collections::HashMap;
 use std::sync::atomic::Ordering;
 
-use egui::{Key, Modifiers, Ui};
+use egui::{Key, Modifiers, RichText, Ui};
 use rusqlite::Connection;
 use rust_i18n::t;
 
-use crate::{common::{Agent, Attachment, PresetSelection, Presets, router_color, text_color}, db::mod_agent_preset, gui::{State, agent_config::AgentConfigState, reload_db_chats}};
+use crate::{common::{Agent, Attachment, ChatStreamEvent, PresetSelection, Presets, router_color, text_color}, db::{mod_agent_hidden, mod_agent_muted, mod_agent_name, mod_agent_preset}, gui::{State, agent_config::AgentConfigState, reload_db_chats}};
 
 use crate::bulat::editor::{Token, Syntax, TokenType};
 
@@ -18,22 +19,70 @@ pub struct BottomPanelState {
     pub system_prompt_edited: String,
     pub prompt_edited: String,
     pub show_system_prompt: bool,
+    pub developer_prompt: bool, // the prompt goes out as a developer message
     pub pending_attachments: Vec<Attachment>,
+    // openrouter model id -> context length, so the cache db isn't queried
+    // on every frame
+    pub context_lengths: HashMap<String, Option<f64>>,
+    // prompts sent this session, oldest first, recalled with Up/Down
+    pub prompt_history: Vec<String>,
+    pub history_pos: Option<usize>,
+    pub history_draft: String, // what was typed before browsing the history
 }
 
-impl Default for BottomPanelState {
+// the user's sizing of the panel, kept across restarts
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct BottomPanelLayout {
+    pub col1_width: f32,
+    pub col2_width: f32,
+    pub height: f32,
+    pub desired_rows: usize,
+}
+
+impl Default for BottomPanelLayout {
     fn default() -> Self {
         Self {
             col1_width: 80.0,
             col2_width: 250.0,
             height: 80.0,
+            desired_rows: 5,
+        }
+    }
+}
+
+impl Default for BottomPanelState {
+    fn default() -> Self {
+        Self::from_layout(&BottomPanelLayout::default())
+    }
+}
+
+impl BottomPanelState {
+    pub fn from_layout(layout: &BottomPanelLayout) -> Self {
+        Self {
+            col1_width: layout.col1_width,
+            col2_width: layout.col2_width,
+            height: layout.height,
             row_height: 0.0,
             height_modified: false,
-            desired_rows: 5,
+            desired_rows: layout.desired_rows,
             system_prompt_edited: String::new(),
             prompt_edited: String::new(),
             show_system_prompt: false,
+            developer_prompt: false,
             pending_attachments: Vec::new(),
+            context_lengths: HashMap::new(),
+            prompt_history: Vec::new(),
+            history_pos: None,
+            history_draft: String::new(),
+        }
+    }
+
+    pub fn layout(&self) -> BottomPanelLayout {
+        BottomPanelLayout {
+            col1_width: self.col1_width,
+            col2_width: self.col2_width,
+            height: self.height,
+            desired_rows: self.desired_rows,
         }
     }
 }
@@ -49,7 +98,7 @@ pub fn ui_bottom_panel(ctx: &egui::Context, state: &mut State) {
         .exact_height(panel_h)
         .show(ctx, |ui| {
 
-            if state.is_modal_open {
+            if state.is_modal_open || *state.perma.readonly.lock().unwrap() {
                 ui.disable();
             }
 
@@ -74,6 +123,22 @@ pub fn ui_bottom_panel(ctx: &egui::Context, state: &mut State) {
                                     !state.bottom_panel_state.show_system_prompt;
                         }
 
+                        // an instruction for a chat that is already going
+                        let can_add = state.active_chat_id.is_some_and(|id| id > 0)
+                            && !state.chat_streaming_state.streaming;
+                        if ui.add_enabled(can_add, egui::Button::new("💻+").small())
+                                .on_hover_text(t!("add_system_msg_tooltip"))
+                                .clicked() {
+                            state.add_system_msg = Some(String::new());
+                            state.is_modal_open = true;
+                        }
+
+                        // models that weigh developer instructions above the
+                        // user's get the prompt in that role
+                        ui.toggle_value(&mut state.bottom_panel_state.developer_prompt,
+                                egui::RichText::new("🛠").small())
+                            .on_hover_text(t!("developer_prompt_tooltip"));
+
                         ui.add_space(4.0);
 
                         // Attachment Menu Button
@@ -85,7 +150,7 @@ pub fn ui_bottom_panel(ctx: &egui::Context, state: &mut State) {
                         };
 
                         ui.menu_button(attach_text, |ui| {
-                            if ui.button("Attach 'src/' (.rs)").clicked() {
+                            if ui.button(t!("attach_src_btn")).clicked() {
                                 if let Some(root) = &state.project_root {
                                     let src_path = root.join("src");
 
@@ -117,7 +182,7 @@ pub fn ui_bottom_panel(ctx: &egui::Context, state: &mut State) {
                             }
 
                             // Generate and insert TOC
-                            if ui.button("Attach TOC of 'src/' (.rs)").clicked() {
+                            if ui.button(t!("attach_src_toc_btn")).clicked() {
                                 if let Some(root) = &state.project_root {
                                     let src_path = root.join("src");
                                     let mut toc = String::new();
@@ -140,7 +205,7 @@ pub fn ui_bottom_panel(ctx: &egui::Context, state: &mut State) {
                             }
 
                             // Attach Multiple Files and or Folders
-                            if ui.button("📄 Attach Files/Folders...").clicked() {
+                            if ui.button(t!("attach_files_btn")).clicked() {
                                 // 1. Reconfigure the dialog with the project root (if active)
                                 if let Some(root) = &state.project_root {
                                     state.file_dialog = egui_file_dialog::FileDialog::new()
@@ -154,13 +219,30 @@ pub fn ui_bottom_panel(ctx: &egui::Context, state: &mut State) {
                                 ui.close();
                             }
 
+                            // Same picker, but showing only the image types
+                            // that get sent to multimodal models
+                            if ui.button(t!("attach_images_btn"))
+                                    .on_hover_text(t!("attach_images_tooltip"))
+                                    .clicked() {
+                                let dialog = egui_file_dialog::FileDialog::new()
+                                    .add_file_filter_extensions("Images",
+                                        vec!["png", "jpg", "jpeg", "webp", "gif"])
+                                    .default_file_filter("Images");
+                                state.file_dialog = match &state.project_root {
+                                    Some(root) => dialog.initial_directory(root.clone()),
+                                    None => dialog,
+                                };
+                                state.file_dialog.pick_multiple();
+                                ui.close();
+                            }
+
                             if !state.bottom_panel_state.pending_attachments.is_empty() {
-                                if ui.button("Clear Attachments").clicked() {
+                                if ui.button(t!("clear_attachments_btn")).clicked() {
                                     state.bottom_panel_state.pending_attachments.clear();
                                     ui.close();
                                 }
                             }
-                        }).response.on_hover_text("Attachments");
+                        }).response.on_hover_text(t!("attachments_tooltip"));
                     });
 
                     // --- Column 1: System Prompt ---
@@ -239,9 +321,13 @@ fn render_user_prompt_col(ui: &mut Ui, state: &mut State, panel_height: f32) {
     egui::ScrollArea::vertical()
         .id_salt("prompt_scroll")
         .show(ui, |ui| {
+            let prompt_id = egui::Id::new("prompt_edit");
+            recall_prompt_history(ui, state, prompt_id);
+
             let response = ui.add(
                 egui::TextEdit::multiline(
                             &mut state.bottom_panel_state.prompt_edited)
+                    .id(prompt_id)
                     .desired_width(f32::INFINITY)
                     .desired_rows(state.bottom_panel_state.desired_rows)
                     .hint_text(t!("enter_prompt_here")),
@@ -255,18 +341,117 @@ fn render_user_prompt_col(ui: &mut Ui, state: &mut State, panel_height: f32) {
                 state.bottom_panel_state.height_modified = true;
             }
 
-            // Recalculate desired rows if panel height changed
+            // Recalculate desired rows if panel height changed, leaving one
+            // row for the size estimate below
             if state.bottom_panel_state.height_modified {
                 if state.bottom_panel_state.row_height > 0.0 {
                     state.bottom_panel_state.desired_rows =
-                            (panel_height / state.bottom_panel_state.row_height)
-                            as usize;
+                            ((panel_height / state.bottom_panel_state.row_height)
+                            as usize).saturating_sub(1).max(1);
                 }
                 state.bottom_panel_state.height_modified = false;
             }
+
+            render_prompt_size(ui, state);
         });
 }
 
+// like a shell: Up with the cursor at the very start of the prompt steps back
+// through the prompts sent this session, Down steps forward to the draft
+fn recall_prompt_history(ui: &mut Ui, state: &mut State, prompt_id: egui::Id) {
+    let bottom = &mut state.bottom_panel_state;
+    if bottom.prompt_history.is_empty() || !ui.memory(|m| m.has_focus(prompt_id)) {
+        return;
+    }
+    // checked before the TextEdit moves the cursor for this frame's keys
+    let at_start = bottom.prompt_edited.is_empty()
+        || egui::TextEdit::load_state(ui.ctx(), prompt_id)
+            .and_then(|s| s.cursor.char_range())
+            .is_some_and(|range| range.primary.index == 0 && range.secondary.index == 0);
+    if !at_start {
+        return;
+    }
+
+    let newest = bottom.prompt_history.len() - 1;
+    let next_pos = if ui.input_mut(|i| i.consume_key(Modifiers::NONE, Key::ArrowUp)) {
+        match bottom.history_pos {
+            None => {
+                bottom.history_draft = bottom.prompt_edited.clone();
+                Some(newest)
+            }
+            Some(pos) => Some(pos.saturating_sub(1)),
+        }
+    } else if bottom.history_pos.is_some()
+            && ui.input_mut(|i| i.consume_key(Modifiers::NONE, Key::ArrowDown)) {
+        bottom.history_pos.map(|pos| pos + 1).filter(|pos| *pos <= newest)
+    } else {
+        return;
+    };
+
+    bottom.prompt_edited = match next_pos {
+        Some(pos) => bottom.prompt_history[pos].clone(),
+        None => std::mem::take(&mut bottom.history_draft),
+    };
+    bottom.history_pos = next_pos;
+
+    // keep the cursor at the start so the next Up/Down keeps browsing
+    if let Some(mut edit_state) = egui::TextEdit::load_state(ui.ctx(), prompt_id) {
+        edit_state.cursor.set_char_range(Some(
+            egui::text::CCursorRange::one(egui::text::CCursor::new(0))));
+        edit_state.store(ui.ctx(), prompt_id);
+    }
+}
+
+// rough size of what is about to be sent: the usual ~4 characters per token
+fn render_prompt_size(ui: &mut Ui, state: &mut State) {
+    let bottom = &state.bottom_panel_state;
+    let mut chars = bottom.prompt_edited.chars().count();
+    if bottom.show_system_prompt {
+        chars += bottom.system_prompt_edited.chars().count();
+    }
+    chars += bottom.pending_attachments.iter()
+        .map(|att| att.content.chars().count())
+        .sum::<usize>();
+    let tokens = chars.div_ceil(4);
+
+    // the smallest context window among the agents that will answer
+    let active_chat_id = state.active_chat_id.unwrap_or(0);
+    let mut context_len: Option<f64> = None;
+    if let Some(chat) = state.open_chats.get(&active_chat_id) {
+        for agent in chat.agents.iter().skip(1)
+                .filter(|agent| !agent.deleted && !agent.muted) {
+            let Some(preset) = agent.preset.as_ref()
+                    .or_else(|| state.presets.get(agent.preset_selection.id))
+                    .filter(|p| p.chat_router == crate::common::ChatRouter::Openrouter) else {
+                continue;
+            };
+            let cache_conn = &state.cache_conn;
+            let len = *state.bottom_panel_state.context_lengths
+                .entry(preset.model.clone())
+                .or_insert_with(|| cache_conn.as_ref()
+                    .and_then(|conn| crate::db::cache::get_openr_model_info(
+                        conn, &preset.model).ok().flatten())
+                    .map(|info| info.context_length)
+                    .filter(|len| *len > 0.0));
+            if let Some(len) = len {
+                context_len = Some(context_len.map_or(len, |c| c.min(len)));
+            }
+        }
+    }
+
+    let text = match context_len {
+        Some(len) => format!("{} {} · ~{} / {} {}", chars, t!("chars"),
+            tokens, len as u64, t!("tokens")),
+        None => format!("{} {} · ~{} {}", chars, t!("chars"), tokens,
+            t!("tokens")),
+    };
+    let mut label = RichText::new(text).small().weak();
+    if context_len.is_some_and(|len| tokens as f64 > len) {
+        label = label.color(ui.visuals().error_fg_color);
+    }
+    ui.label(label).on_hover_text(t!("prompt_size_tooltip"));
+}
+
 fn render_actions_col(ui: &mut Ui, state: &mut State,  ctx: &egui::Context) {
     let mut do_send_prompt_now = false;
 
@@ -283,12 +468,35 @@ fn render_actions_col(ui: &mut Ui, state: &mut State,  ctx: &egui::Context) {
         t!("send_prompt_btn").to_string()
     };
 
-    let send_btn = egui::Button::new(button_text).wrap().selected(state.chat_streaming_state.streaming);
-    let send_clicked = ui.add_sized([80.0, actual_btn_height], send_btn).clicked();
+    // a soft guard against pasting a huge blob by accident, sending still works
+    let char_limit = *state.perma.prompt_char_limit.lock().unwrap();
+    let prompt_chars = state.bottom_panel_state.prompt_edited.chars().count();
+    let over_limit = char_limit > 0 && prompt_chars > char_limit
+        && !state.chat_streaming_state.streaming;
+
+    let mut send_btn = egui::Button::new(button_text).wrap().selected(state.chat_streaming_state.streaming);
+    if over_limit {
+        send_btn = send_btn.stroke(egui::Stroke::new(2.0, ui.visuals().warn_fg_color));
+    }
+    let mut send_response = ui.add_sized([80.0, actual_btn_height], send_btn);
+    if over_limit {
+        send_response = send_response.on_hover_text(RichText::new(
+            t!("prompt_limit_tooltip", chars = prompt_chars, limit = char_limit))
+            .color(ui.visuals().warn_fg_color));
+    }
+    let send_clicked = send_response.clicked();
+
+    let regenerate_btn = egui::Button::new(t!("regenerate_all_btn")).wrap();
+    if ui.add_enabled_ui(!state.chat_streaming_state.streaming, |ui| {
+        ui.add_sized([80.0, 0.0], regenerate_btn)
+            .on_hover_text(t!("regenerate_all_tooltip"))
+    }).inner.clicked() {
+        regenerate_all(state, ctx);
+    }
 
     if do_send_prompt_now || send_clicked {
         if state.chat_streaming_state.streaming {
-            if let Some(flag) = &state.chat_streaming_state.abort_flag {
+            for flag in state.chat_streaming_state.abort_flags.iter().flatten() {
                 flag.store(true, Ordering::Relaxed);
             }
             return;
@@ -307,11 +515,13 @@ fn render_actions_col(ui: &mut Ui, state: &mut State,  ctx: &egui::Context) {
         state.open_chats.insert(active_chat_id, crate::common::Chat::default());
     }
 
+    let mut run_agent = None;
     egui::ScrollArea::vertical().id_salt("agent_scroll").show(ui, |ui| {
         ui.horizontal_wrapped(|ui| {
             ui.spacing_mut().item_spacing = egui::vec2(2.0, 2.0);
             ui.vertical(|ui| {
                 let presets = &state.presets;
+                let bitmask = state.chat_streaming_state.bitmask;
                 // Scope the mutable borrow of the chat
                 let chat = state.open_chats.get_mut(&active_chat_id).unwrap();
 
@@ -322,13 +532,36 @@ fn render_actions_col(ui: &mut Ui, state: &mut State,  ctx: &egui::Context) {
                         ui.spacing_mut().item_spacing.x = 4.0;
                         let id_source = format!("chat_agent_{}", i);
                         ui.label(format!("{}", agent.id));
-                        render_agent(ui, agent, &mut state.agent_config_state, presets, &id_source, &state.db_conn);
+                        let streaming = bitmask & (1 << i as u128) != 0;
+                        render_agent(ui, agent, &mut state.agent_config_state, presets, &id_source, &state.db_conn, streaming);
                     });
                 }
 
+                // agents that have not answered the latest prompt, e.g. ones
+                // added after the others replied, can be run on their own
+                let waiting: Vec<usize> = (1..chat.agents.len().min(128))
+                    .filter(|i| awaits_last_prompt(chat, *i))
+                    .collect();
+                for i in waiting {
+                    let btn = egui::Button::new(t!("run_agent_btn", name = chat.agents[i].name));
+                    if ui.add_enabled(!state.chat_streaming_state.streaming, btn)
+                            .on_hover_text(t!("run_agent_tooltip")).clicked() {
+                        run_agent = Some(i);
+                    }
+                }
+
+                // the stream events address agents by index, so the order
+                // stays put until every agent is done
+                let moved = state.agent_config_state.moving_agent.take()
+                    .filter(|_| !state.chat_streaming_state.streaming)
+                    .map(|(ind, up)| chat.move_agent(&state.db_conn, ind, up));
+                if let Some(Err(e)) = moved {
+                    eprintln!("Failed to move agent: {}", e);
+                }
+
                 let is_full = chat.agents.len() >= 127;
                 if ui.add_enabled(!is_full, egui::Button::new("+"))
-                .on_hover_text(if is_full { "Max agents reached" } else { "Add another agent" })
+                .on_hover_text(if is_full { t!("max_agents_tooltip") } else { t!("add_agent_tooltip") })
                 .clicked() {
                     if let Err(e) = chat.add_agent_try_sync(&state.db_conn) {
                         eprintln!("Failed to add agent: {}", e);
@@ -336,12 +569,58 @@ fn render_actions_col(ui: &mut Ui, state: &mut State,  ctx: &egui::Context) {
                         state.is_modal_open = true;
                     }
                 }
+
+                ui.horizontal(|ui| {
+                    if ui.small_button(t!("mute_all_btn")).clicked() {
+                        for agent in chat.agents.iter_mut().skip(1) {
+                            set_agent_muted(&state.db_conn, agent, true);
+                        }
+                    }
+                    if ui.small_button(t!("unmute_all_btn")).clicked() {
+                        for agent in chat.agents.iter_mut().skip(1) {
+                            set_agent_muted(&state.db_conn, agent, false);
+                        }
+                    }
+                });
             });
         });
     });
+
+    if let Some(index) = run_agent {
+        run_agent_on_last_prompt(state, ctx, index);
+    }
+}
+
+/// Mutes or unmutes an agent and saves it, a muted agent sits out prompts
+fn set_agent_muted(conn: &Connection, agent: &mut Agent, muted: bool) {
+    if agent.deleted || agent.muted == muted {
+        return;
+    }
+    agent.muted = muted;
+    // agents of a chat that is not saved yet are stored on save
+    let saved = if agent.id == 0 {
+        Ok(())
+    } else {
+        mod_agent_muted(conn, agent.id, muted)
+    };
+    if let Err(e) = saved {
+        eprintln!("Failed to mute agent {}: {}", agent.id, e);
+    }
 }
 
 /// Renders a single agent's controls (Label + Preset Selector)
+// small outlined label next to an agent, e.g. an option it overrides
+fn badge(ui: &mut egui::Ui, text: impl Into<egui::WidgetText>,
+        color: egui::Color32) {
+    egui::Frame::new()
+        .stroke(egui::Stroke::new(1.0, color))
+        .inner_margin(egui::Margin::symmetric(3, 0))
+        .corner_radius(3.0)
+        .show(ui, |ui| {
+            ui.label(text);
+        });
+}
+
 fn render_agent(
     ui: &mut egui::Ui,
     agent: &mut Agent,
@@ -349,13 +628,82 @@ fn render_agent(
     presets: &Presets, // Assuming Presets type is defined
     id_source: &str,
     conn: &Connection,
+    streaming: bool, // the agent's bit is set, its reply is still coming
 ) {
-    // button that mutes/unmutes the agent
-    let btn_text = &agent.name;
-    let btn = egui::Button::new(btn_text).selected(!agent.muted);
+    if streaming {
+        // spins (and keeps repainting) until Finished or Error clears the bit
+        ui.add(egui::Spinner::new().size(12.0))
+            .on_hover_text(t!("agent_streaming_tooltip"));
+    }
+
+    if substate.renaming_agent_ind == Some(agent.agent_ind) {
+        // inline rename, Enter or clicking away saves, Escape cancels
+        let response = ui.add(egui::TextEdit::singleline(&mut substate.rename_buffer)
+            .desired_width(100.0));
+        response.request_focus();
+        if response.lost_focus() {
+            let name = substate.rename_buffer.trim();
+            if !name.is_empty() && !ui.input(|i| i.key_pressed(egui::Key::Escape)) {
+                agent.name = name.to_string();
+                // agents of a chat that is not saved yet get their name on save
+                let saved = if agent.id == 0 {
+                    Ok(())
+                } else {
+                    mod_agent_name(conn, agent.id, &agent.name)
+                };
+                if let Err(e) = saved {
+                    eprintln!("Failed to rename agent {}: {}", agent.id, e);
+                }
+            }
+            substate.renaming_agent_ind = None;
+        }
+    } else {
+        // mute has its own button, so the clicks of a double-click on the
+        // name only rename the agent
+        let speaker = if agent.muted { "🔇" } else { "🔊" };
+        if ui.add(egui::Button::new(speaker).selected(!agent.muted))
+                .on_hover_text(t!("mute_agent_tooltip")).clicked() {
+            set_agent_muted(conn, agent, !agent.muted);
+        }
 
-    if ui.add(btn).on_hover_text("Click to Mute or Unmute").clicked() {
-        agent.muted = !agent.muted;
+        let name = if agent.muted {
+            RichText::new(&agent.name).weak()
+        } else {
+            RichText::new(&agent.name).strong()
+        };
+        let response = ui.add(egui::Label::new(name).sense(egui::Sense::click()))
+            .on_hover_text(t!("agent_btn_tooltip"));
+        if response.double_clicked() {
+            substate.renaming_agent_ind = Some(agent.agent_ind);
+            substate.rename_buffer = agent.name.clone();
+        }
+    }
+
+    // a hidden agent keeps working, only its replies are left out of the chat
+    let eye = if agent.hidden {
+        RichText::new("👁").weak()
+    } else {
+        RichText::new("👁")
+    };
+    if ui.small_button(eye).on_hover_text(t!("hide_agent_tooltip")).clicked() {
+        agent.hidden = !agent.hidden;
+        // agents of a chat that is not saved yet are stored on save
+        let saved = if agent.id == 0 {
+            Ok(())
+        } else {
+            mod_agent_hidden(conn, agent.id, agent.hidden)
+        };
+        if let Err(e) = saved {
+            eprintln!("Failed to hide agent {}: {}", agent.id, e);
+        }
+    }
+
+    // reorder, the swap happens once all agents are drawn
+    if ui.small_button("⬆").on_hover_text(t!("move_agent_up_tooltip")).clicked() {
+        substate.moving_agent = Some((agent.agent_ind, true));
+    }
+    if ui.small_button("⬇").on_hover_text(t!("move_agent_down_tooltip")).clicked() {
+        substate.moving_agent = Some((agent.agent_ind, false));
     }
 
     // Sync the selection first (ensure title matches ID)
@@ -376,7 +724,7 @@ fn render_agent(
     }
 
     // wrench menu here 🔧
-    if ui.button("🔧").on_hover_text("Modify this preset").clicked() {
+    if ui.button("🔧").on_hover_text(t!("modify_preset_tooltip")).clicked() {
         // A. Get the current preset data
         if let Some(_current_preset) = presets.get(agent.preset_selection.id) {
             if let Some(agent_preset) = agent.preset.as_ref() {
@@ -385,16 +733,9 @@ fn render_agent(
                 substate.target_agent_id = Some(agent.id);
                 substate.target_agent_ind = Some(agent.agent_ind);
 
-                // C. Clone the preset into the editor state
-                substate.editor_state.edited_preset = agent_preset.clone();
-                substate.editor_state.router_changed = true; // Trigger validation refresh
-
-                // D. Set up UI strings (seed/temp) for the text inputs
-                substate.editor_state.seed_entered = agent_preset.options.seed
-                    .map(|s| s.to_string()).unwrap_or_default();
-                substate.editor_state.temperature_entered =
-                    agent_preset.options.temperature
-                    .map(|t| t.to_string()).unwrap_or_default();
+                // C. Load the preset into the editor state, which also
+                // fills the text inputs and triggers a validation refresh
+                substate.editor_state.load_preset(agent_preset.clone());
 
                 substate.is_open = true;
             }
@@ -403,45 +744,62 @@ fn render_agent(
 
     if let Some(current) = &agent.preset {
         if let Some(original) = presets.get(agent.preset_selection.id) {
-            if current.options.include_reasoning !=
-                    original.options.include_reasoning {
-                egui::Frame::new()
-                .stroke(egui::Stroke::new(1.0, text_color()))
-                .inner_margin(egui::Margin::symmetric(3, 0))
-                .corner_radius(3.0)
-                .show(ui, |ui| {
-                    ui.label(format!("{} {}", t!("reasoning_label"),
-                    current.options.include_reasoning
-                    .map_or(t!("unset").to_string(), |s| {
-                    if s { t!("yes").to_string() } else { t!("no").to_string()
-                    }})));
-                });
+            if crate::gui::preset_editor::reasoning_text(&current.options) !=
+                    crate::gui::preset_editor::reasoning_text(&original.options) {
+                badge(ui, format!("{} {}", t!("reasoning_label"),
+                    crate::gui::preset_editor::reasoning_text(&current.options)),
+                    text_color());
             }
 
             if current.options.temperature !=
                     original.options.temperature {
-                egui::Frame::new()
-                .stroke(egui::Stroke::new(1.0, text_color()))
-                .inner_margin(egui::Margin::symmetric(3, 0))
-                .corner_radius(3.0)
-                .show(ui, |ui| {
-                    ui.label(format!("{} {}", t!("temperature_label"),
+                badge(ui, format!("{} {}", t!("temperature_label"),
                     current.options.temperature.map(|t| t.to_string())
-                    .unwrap_or_else(|| t!("unset").to_string())));
-                });
+                    .unwrap_or_else(|| t!("unset").to_string())), text_color());
             }
 
             if current.options.seed !=
                    original.options.seed {
-                egui::Frame::new()
-                .stroke(egui::Stroke::new(1.0, text_color()))
-                .inner_margin(egui::Margin::symmetric(3, 0))
-                .corner_radius(3.0)
-                .show(ui, |ui| {
-                    ui.label(format!("{} {}", t!("seed_label"),
+                badge(ui, format!("{} {}", t!("seed_label"),
                     current.options.seed.map(|t| t.to_string())
-                    .unwrap_or_else(|| t!("unset").to_string())));
-                });
+                    .unwrap_or_else(|| t!("unset").to_string())), text_color());
+            }
+
+            if current.options.max_tokens !=
+                   original.options.max_tokens {
+                badge(ui, format!("{} {}", t!("max_tokens_label"),
+                    current.options.max_tokens.map(|m| m.to_string())
+                    .unwrap_or_else(|| t!("unset").to_string())), text_color());
+            }
+
+            if current.options.top_p != original.options.top_p {
+                badge(ui, format!("{} {}", t!("top_p_label"),
+                    current.options.top_p.map(|p| format!("{:.2}", p))
+                    .unwrap_or_else(|| t!("unset").to_string())), text_color());
+            }
+
+            if current.options.top_k != original.options.top_k {
+                badge(ui, format!("{} {}", t!("top_k_label"),
+                    current.options.top_k.map(|k| k.to_string())
+                    .unwrap_or_else(|| t!("unset").to_string())), text_color());
+            }
+
+            if current.options.frequency_penalty != original.options.frequency_penalty {
+                badge(ui, format!("{} {}", t!("frequency_penalty_label"),
+                    current.options.frequency_penalty.map(|p| format!("{:.2}", p))
+                    .unwrap_or_else(|| t!("unset").to_string())), text_color());
+            }
+
+            if current.options.presence_penalty != original.options.presence_penalty {
+                badge(ui, format!("{} {}", t!("presence_penalty_label"),
+                    current.options.presence_penalty.map(|p| format!("{:.2}", p))
+                    .unwrap_or_else(|| t!("unset").to_string())), text_color());
+            }
+
+            if current.options.stop_sequences() !=
+                   original.options.stop_sequences() {
+                badge(ui, format!("{} {}", t!("stop_sequences_label"),
+                    current.options.stop_sequences().len()), text_color());
             }
 /*
             // If we found changes, display the indicator
@@ -500,27 +858,99 @@ pub fn preset_combo_box(
 
     let mut changed = false;
 
-    let response = egui::ComboBox::from_id_salt(salt)
+    let combo_id = egui::Id::new(salt);
+
+    // presets without a category go first, under "General"
+    let category_of = |id: i64| presets.get(id)
+        .and_then(|p| p.category.as_deref());
+    let mut groups: Vec<Option<String>> = vec![None];
+    groups.extend(presets.categories().into_iter().map(Some));
+
+    let response = egui::ComboBox::from_id_salt(combo_id)
     .height(500.0)
     .selected_text(current_text_widget)
     .show_ui(ui, |ui| {
-        for (index, (id, title)) in presets.cache.iter().enumerate() {
-            let is_selected = selection.id == *id;
+        // the filter lives in egui memory, it is cleared once a preset is picked
+        let filter_id = combo_id.with("filter");
+        let mut filter: String = ui.data_mut(|d| d.get_temp(filter_id))
+            .unwrap_or_default();
+        let filter_response = ui.add(egui::TextEdit::singleline(&mut filter)
+            .hint_text(t!("preset_filter_hint"))
+            .desired_width(f32::INFINITY));
+        if ui.memory(|m| m.focused().is_none()) {
+            filter_response.request_focus();
+        }
+        let needle = filter.trim().to_lowercase();
+        let selected_id = selection.id;
+        // matches the title or the model, the selected preset always shows
+        let matches = |id: i64, title: &str| needle.is_empty()
+            || id == selected_id
+            || title.to_lowercase().contains(&needle)
+            || presets.get(id)
+                .is_some_and(|p| p.model.to_lowercase().contains(&needle));
+
+        let selected_category = category_of(selection.id);
+        let mut preset_item = |ui: &mut egui::Ui, index: usize, id: i64,
+                title: &String| {
+            let is_selected = selection.id == id;
 
             // Determine color for this specific item in the list
-            let mut label_text = egui::RichText::new(title);
-            if let Some(preset) = presets.get(*id) {
+            let favorite = presets.get(id).is_some_and(|p| p.favorite);
+            let mut label_text = if favorite {
+                egui::RichText::new(format!("★ {}", title))
+            } else {
+                egui::RichText::new(title)
+            };
+            if let Some(preset) = presets.get(id) {
                 label_text = label_text.color(router_color(
                     &preset.chat_router));
             }
 
             if ui.selectable_label(is_selected, label_text).clicked() {
                 selection.ind = index;
-                selection.id = *id;
+                selection.id = id;
                 selection.title = title.clone();
                 changed = true;
             }
+        };
+
+        ui.separator();
+
+        // a single group, or a filtered list, needs no headers
+        if groups.len() == 1 || !needle.is_empty() {
+            for (index, (id, title)) in presets.cache.iter().enumerate()
+                    .filter(|(_, (id, title))| matches(*id, title)) {
+                preset_item(ui, index, *id, title);
+            }
+            if changed {
+                filter.clear();
+            }
+            ui.data_mut(|d| d.insert_temp(filter_id, filter));
+            return;
         }
+
+        for group in &groups {
+            let header = group.clone()
+                .unwrap_or_else(|| t!("preset_category_general").to_string());
+            let members: Vec<_> = presets.cache.iter().enumerate()
+                .filter(|(_, (id, _))| category_of(*id) == group.as_deref())
+                .collect();
+            if members.is_empty() {
+                continue;
+            }
+            egui::CollapsingHeader::new(header)
+                .id_salt(combo_id.with(group))
+                .default_open(selected_category == group.as_deref())
+                .show(ui, |ui| {
+                    for (index, (id, title)) in members {
+                        preset_item(ui, index, *id, title);
+                    }
+                });
+        }
+        if changed {
+            filter.clear();
+        }
+        ui.data_mut(|d| d.insert_temp(filter_id, filter));
     });
 
     if !tooltip_text.is_empty() {
@@ -549,20 +979,33 @@ fn submit_prompt(state: &mut State, ctx: &egui::Context) {
         return;
     }
 
+    // an OpenRouter stream without a key only fails once it is underway
+    let missing_key = chat.agents.iter().skip(1)
+        .filter(|a| !a.deleted && !a.muted)
+        .filter_map(|a| a.preset.as_ref()
+            .or_else(|| state.presets.get(a.preset_selection.id)))
+        .any(|p| p.chat_router == crate::common::ChatRouter::Openrouter
+            && !state.openrouter_key(p.key_name.as_deref()).is_set);
+    if missing_key {
+        state.missing_key_prompt = true;
+        state.is_modal_open = true;
+        state.open_chats.insert(active_chat_id, chat);
+        return;
+    }
+
     let prompt_text = state.bottom_panel_state.prompt_edited.clone();
+    let history = &mut state.bottom_panel_state.prompt_history;
+    if history.last() != Some(&prompt_text) {
+        history.push(prompt_text.clone());
+    }
+    state.bottom_panel_state.history_pos = None;
     let rt_handle = state.perma.rt.clone();
     let tx_base = state.chat_streaming_state.tx.clone();
 
     let old_chat_id = chat.id;
 
     if chat.id <= 0 {
-        chat.title = state.bottom_panel_state.prompt_edited
-            .lines() // Split into lines
-            .find(|line| !line.trim().is_empty()) // Grab the first non-empty line
-            .unwrap_or("Unnamed Chat")
-            .chars()
-            .take(40)
-            .collect::<String>();
+        chat.title = prompt_title(&state.bottom_panel_state.prompt_edited);
 
         match crate::db::mk_chat(&state.db_conn, &mut chat) {
             Ok(()) => reload_db_chats(&state.db_conn, &mut state.db_chats),
@@ -586,23 +1029,34 @@ fn submit_prompt(state: &mut State, ctx: &egui::Context) {
         }
     }
 
+    // the system prompt lives for the whole chat: edit it in place if the chat
+    // already has one, otherwise put it in front of the history
     if state.bottom_panel_state.show_system_prompt {
-        let sys_content = state.bottom_panel_state.system_prompt_edited.trim();
-        if !sys_content.is_empty() {
+        let sys_content = state.bottom_panel_state.system_prompt_edited.clone();
+        let existing_id = chat.system_msg().map(|msg| msg.id);
+        if let Some(sys_id) = existing_id {
+            let changed = chat.msg_pool.get(&sys_id)
+                .is_some_and(|msg| msg.content != sys_content);
+            let saved = changed && !sys_content.trim().is_empty()
+                && crate::db::mod_msg_content(&state.db_conn, sys_id,
+                    &sys_content).is_ok();
+            if let Some(msg) = chat.msg_pool.get_mut(&sys_id).filter(|_| saved) {
+                msg.content = sys_content;
+            }
+        } else if !sys_content.trim().is_empty() {
             let mut sys_msg = crate::common::ChatMsg {
                 id: 0,
                 msg_role: crate::common::MsgRole::System,
-                content: state.bottom_panel_state.system_prompt_edited.clone(),
+                content: sys_content,
                 ..Default::default()
             };
 
             if let Ok(()) = crate::db::mk_msg(&state.db_conn, &mut sys_msg) {
                 chat.msg_pool.insert(sys_msg.id, sys_msg.clone());
                 for agent in chat.agents.iter_mut() {
-                    agent.msg_ids.push(sys_msg.id);
+                    agent.msg_ids.insert(0, sys_msg.id);
                     let _ = crate::db::mod_agent_msgs(&state.db_conn, agent.id, &agent.msg_ids);
                 }
-                state.bottom_panel_state.show_system_prompt = false;
             }
         }
     }
@@ -615,9 +1069,16 @@ fn submit_prompt(state: &mut State, ctx: &egui::Context) {
 
     state.bottom_panel_state.pending_attachments.clear();
 
+    // a developer message is a one-off, the next prompt is the user's again
+    let msg_role = if std::mem::take(
+            &mut state.bottom_panel_state.developer_prompt) {
+        crate::common::MsgRole::Developer
+    } else {
+        crate::common::MsgRole::User
+    };
     let mut usr_msg = crate::common::ChatMsg {
         id: 0,
-        msg_role: crate::common::MsgRole::User,
+        msg_role,
         content: prompt_text.clone(),
         details: details_json,
         ..Default::default()
@@ -633,19 +1094,7 @@ fn submit_prompt(state: &mut State, ctx: &egui::Context) {
     let usr_msg_id = usr_msg.id;
     chat.msg_pool.insert(usr_msg.id, usr_msg.clone());
 
-    state.chat_streaming_state.streaming = true;
-    state.chat_streaming_state.bitmask = 0;
-
-    let abort_flag = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
-    state.chat_streaming_state.abort_flag = Some(abort_flag.clone());
-
-    let agent_count = std::cmp::min(chat.agents.len(), 128);
-    state.chat_streaming_state.msg_ids.clear();
-    state.chat_streaming_state.msg_ids.resize_with(agent_count, || 0);
-    state.chat_streaming_state.content_buffers.clear();
-    state.chat_streaming_state.content_buffers.resize_with(agent_count, || String::new());
-    state.chat_streaming_state.reasoning_buffers.clear();
-    state.chat_streaming_state.reasoning_buffers.resize_with(agent_count, || String::new());
+    start_streaming(state, new_active_id, chat.agents.len());
 
     for (_index, agent) in chat.agents.iter_mut().enumerate() {
         agent.msg_ids.push(usr_msg_id);
@@ -670,7 +1119,7 @@ fn submit_prompt(state: &mut State, ctx: &egui::Context) {
         };
 
         if preset.chat_router == crate::common::ChatRouter::Openrouter {
-            preset.api_key = state.openrouter_api_key.clone();
+            preset.api_key = state.openrouter_key(preset.key_name.as_deref());
         }
 
         let tx = tx_base.clone();
@@ -719,14 +1168,18 @@ fn submit_prompt(state: &mut State, ctx: &egui::Context) {
         }
 
         state.chat_streaming_state.bitmask |= 1 << index as u128;
+        state.chat_streaming_state.start_times[index] = Some(std::time::Instant::now());
         let ctx_clone = ctx.clone();
-        let thread_abort = abort_flag.clone();
+        let thread_abort = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+        state.chat_streaming_state.abort_flags[index] = Some(thread_abort.clone());
 
         rt_handle.spawn(async move {
-            if let Err(e) = crate::common::run_chat_stream_router(que, tx.clone(), &ctx_clone, thread_abort).await {
+            if let Err(e) = crate::common::run_chat_stream_router(que, tx.clone(), &ctx_clone, thread_abort.clone()).await {
                 let _ = tx.send(crate::common::ChatStreamEvent::Error(index, format!("Error: {}", e)));
             }
-            let _ = tx.send(crate::common::ChatStreamEvent::Finished(index));
+            let interrupted = thread_abort.load(Ordering::Relaxed);
+            let _ = tx.send(crate::common::ChatStreamEvent::Finished(index,
+                interrupted));
         });
     }
 
@@ -735,6 +1188,500 @@ fn submit_prompt(state: &mut State, ctx: &egui::Context) {
     state.active_chat_id = Some(new_active_id);
 }
 
+/// Puts a system message in front of every agent's conversation, for an
+/// instruction that should have been there from the start. It goes after the
+/// system messages already there, so the chat's system prompt stays first.
+pub fn add_system_msg(state: &mut State, chat_id: i64, content: &str) {
+    let Some(chat) = state.open_chats.get_mut(&chat_id) else {
+        return;
+    };
+    let mut msg = crate::common::ChatMsg {
+        msg_role: crate::common::MsgRole::System,
+        content: content.to_string(),
+        ..Default::default()
+    };
+    if let Err(e) = crate::db::mk_msg(&state.db_conn, &mut msg) {
+        state.error_msg = Some(t!("error_saving_instruction", error = e).to_string());
+        return;
+    }
+
+    for agent in chat.agents.iter_mut() {
+        let pos = agent.msg_ids.iter()
+            .position(|id| chat.msg_pool.get(id).is_none_or(
+                |m| m.msg_role != crate::common::MsgRole::System))
+            .unwrap_or(agent.msg_ids.len());
+        agent.msg_ids.insert(pos, msg.id);
+        let _ = crate::db::mod_agent_msgs(&state.db_conn, agent.id,
+            &agent.msg_ids);
+    }
+    chat.msg_pool.insert(msg.id, msg);
+}
+
+// a new chat is titled after the first non-empty line of its first prompt
+fn prompt_title(prompt: &str) -> String {
+    prompt
+        .lines() // Split into lines
+        .find(|line| !line.trim().is_empty()) // Grab the first non-empty line
+        .unwrap_or("Unnamed Chat")
+        .chars()
+        .take(40)
+        .collect::<String>()
+}
+
+/// After the first reply of a chat, asks the model of the first agent for a
+/// short title in the background. The answer comes back as
+/// ChatStreamEvent::Title. Chats that were renamed are left alone.
+pub fn request_chat_title(state: &State, ctx: &egui::Context, chat_id: i64) {
+    let Some(chat) = state.open_chats.get(&chat_id) else {
+        return;
+    };
+    let Some(omnis) = chat.agents.first() else {
+        return;
+    };
+    let history: Vec<&crate::common::ChatMsg> = omnis.msg_ids.iter()
+        .filter_map(|id| chat.msg_pool.get(id))
+        .filter(|msg| msg.msg_role != crate::common::MsgRole::System)
+        .collect();
+    let is_first_exchange = history.iter()
+        .filter(|msg| msg.msg_role == crate::common::MsgRole::User).count() == 1;
+    let (Some(prompt), Some(reply)) = (
+        history.iter().find(|msg| msg.msg_role == crate::common::MsgRole::User),
+        history.iter().find(|msg| msg.msg_role == crate::common::MsgRole::Assistant
+            && !msg.content.trim().is_empty()),
+    ) else {
+        return;
+    };
+    if !is_first_exchange || chat.title != prompt_title(&prompt.content) {
+        return;
+    }
+    let Some(mut preset) = chat.agents.iter().skip(1)
+            .find(|a| !a.deleted && !a.muted)
+            .and_then(|a| a.preset.clone()) else {
+        return;
+    };
+    if preset.chat_router == crate::common::ChatRouter::Openrouter {
+        preset.api_key = state.openrouter_key(preset.key_name.as_deref());
+    }
+    // the title is short, no need to think about it
+    preset.options.include_reasoning = Some(false);
+
+    let excerpt = |text: &str| text.chars().take(1000).collect::<String>();
+    let mut title_chat = crate::common::Chat::default();
+    title_chat.msg_pool.insert(1, crate::common::ChatMsg {
+        id: 1,
+        msg_role: crate::common::MsgRole::User,
+        content: format!("Write a concise title of 3 to 6 words for the \
+            conversation below. Reply with the title only, no quotes.\n\n\
+            User: {}\n\nAssistant: {}", excerpt(&prompt.content),
+            excerpt(&reply.content)),
+        ..Default::default()
+    });
+    title_chat.agents[1].msg_ids.push(1);
+    let que = crate::common::ChatQue {
+        agent_ind: 1,
+        preset,
+        chat: std::sync::Arc::new(title_chat),
+    };
+
+    let old_title = chat.title.clone();
+    let tx = state.chat_streaming_state.tx.clone();
+    let ctx = ctx.clone();
+    state.perma.rt.spawn(async move {
+        // collect the streamed title on a channel of its own
+        let (title_tx, title_rx) = std::sync::mpsc::channel();
+        let abort = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+        if let Err(e) = crate::common::run_chat_stream_router(que, title_tx,
+                &ctx, abort).await {
+            eprintln!("Could not generate a title for chat {}: {}", chat_id, e);
+            return;
+        }
+        let title: String = title_rx.try_iter().filter_map(|event| match event {
+            ChatStreamEvent::Content(_, text) => Some(text),
+            _ => None,
+        }).collect();
+        let title = title.trim().trim_matches(|c| c == '"' || c == '\'' || c == '*')
+            .trim().chars().take(80).collect::<String>();
+        if !title.is_empty() {
+            let _ = tx.send(ChatStreamEvent::Title(chat_id, old_title, title));
+            ctx.request_repaint();
+        }
+    });
+}
+
+/// Re-runs the agent that wrote `msg_id`, giving it the history up to (but not
+/// including) that message. The new reply overwrites the old one in place.
+pub fn regenerate_agent_msg(state: &mut State, ctx: &egui::Context,
+        chat_id: i64, msg_id: i64) {
+    if state.chat_streaming_state.streaming {
+        state.error_msg = Some(t!("error_busy_streaming").to_string());
+        state.is_modal_open = true;
+        return;
+    }
+    let Some(index) = reply_agent(state, chat_id, msg_id) else {
+        return;
+    };
+    let Some(preset) = agent_preset(state, chat_id, index) else {
+        return;
+    };
+    let agent_count = state.open_chats.get(&chat_id)
+        .map_or(0, |chat| chat.agents.len());
+    start_streaming(state, chat_id, agent_count);
+    let Some(history) = reset_reply(state, chat_id, index, msg_id, &preset)
+    else {
+        state.chat_streaming_state.streaming = false;
+        return;
+    };
+    stream_agent_reply(state, ctx, index, msg_id, preset, history, false);
+}
+
+/// Copies the OpenRouter request that produced a reply as a cURL command,
+/// with the history the agent had and the preset it answered with
+pub fn copy_msg_curl(state: &mut State, ctx: &egui::Context,
+        chat_id: i64, msg_id: i64) {
+    let Some(index) = reply_agent(state, chat_id, msg_id) else {
+        return;
+    };
+    let Some(chat) = state.open_chats.get(&chat_id) else {
+        return;
+    };
+    let Some(preset) = chat.msg_pool.get(&msg_id)
+            .and_then(|msg| msg.preset.clone()) else {
+        return;
+    };
+    let mut history = chat.clone();
+    if let Some(pos) = history.agents[index].msg_ids.iter()
+            .position(|id| *id == msg_id) {
+        history.agents[index].msg_ids.truncate(pos);
+    }
+    let que = crate::common::ChatQue {
+        agent_ind: index,
+        preset,
+        chat: std::sync::Arc::new(history),
+    };
+    match crate::openr::curl_command(&que) {
+        Ok(command) => ctx.copy_text(command),
+        Err(e) => {
+            state.error_msg = Some(t!("error_building_curl", error = e).to_string());
+            state.is_modal_open = true;
+        }
+    }
+}
+
+/// Re-runs every agent that takes part in the active chat, e.g. after the
+/// prompt was edited. Each agent's latest reply is overwritten in place, the
+/// same way regenerate_agent_msg does it for one of them.
+pub fn regenerate_all(state: &mut State, ctx: &egui::Context) {
+    if state.chat_streaming_state.streaming {
+        state.error_msg = Some(t!("error_busy_streaming").to_string());
+        state.is_modal_open = true;
+        return;
+    }
+    let chat_id = state.active_chat_id.unwrap_or(0);
+    let Some(chat) = state.open_chats.get(&chat_id) else {
+        return;
+    };
+
+    // the latest reply of each agent that is not muted, the stream events
+    // address agents by a 128 bit mask so the rest are left out
+    let replies: Vec<(usize, i64)> = chat.agents.iter().enumerate()
+        .take(128).skip(1)
+        .filter(|(_, agent)| !agent.deleted && !agent.muted)
+        .filter_map(|(index, agent)| agent.msg_ids.last()
+            .filter(|id| chat.msg_pool.get(id).is_some_and(
+                |msg| msg.msg_role == crate::common::MsgRole::Assistant))
+            .map(|id| (index, *id)))
+        .collect();
+    if replies.is_empty() {
+        return;
+    }
+
+    let agent_count = chat.agents.len();
+    start_streaming(state, chat_id, agent_count);
+    for (index, msg_id) in replies {
+        let Some(preset) = agent_preset(state, chat_id, index) else {
+            continue;
+        };
+        let Some(history) = reset_reply(state, chat_id, index, msg_id, &preset)
+        else {
+            continue;
+        };
+        stream_agent_reply(state, ctx, index, msg_id, preset, history, false);
+    }
+    if state.chat_streaming_state.bitmask == 0 {
+        state.chat_streaming_state.streaming = false;
+    }
+}
+
+// sent after a reply that max_tokens cut off, never saved to the chat
+const CONTINUE_PROMPT: &str = "Continue exactly where your previous reply \
+    stopped, mid-word if need be. Do not repeat anything and do not add any \
+    preamble.";
+const CONTINUE_MSG_ID: i64 = -1;
+
+/// Asks the agent to carry on a reply that max_tokens cut off. The partial
+/// reply goes back as the agent's own words followed by a request to go on,
+/// and the new tokens are appended to the same message.
+pub fn continue_agent_msg(state: &mut State, ctx: &egui::Context,
+        chat_id: i64, msg_id: i64) {
+    if state.chat_streaming_state.streaming {
+        state.error_msg = Some(t!("error_busy_streaming").to_string());
+        state.is_modal_open = true;
+        return;
+    }
+    let Some(index) = reply_agent(state, chat_id, msg_id) else {
+        return;
+    };
+    let Some(preset) = agent_preset(state, chat_id, index) else {
+        return;
+    };
+    let Some(chat) = state.open_chats.get_mut(&chat_id) else {
+        return;
+    };
+    // only the agent's latest reply can be continued
+    if chat.agents[index].msg_ids.last() != Some(&msg_id) {
+        return;
+    }
+
+    if let Some(msg) = chat.msg_pool.get_mut(&msg_id) {
+        msg.truncated = false;
+        let _ = crate::db::mod_msg_truncated(&state.db_conn, msg_id, false);
+    }
+
+    let mut history = chat.clone();
+    history.msg_pool.insert(CONTINUE_MSG_ID, crate::common::ChatMsg {
+        id: CONTINUE_MSG_ID,
+        msg_role: crate::common::MsgRole::User,
+        content: CONTINUE_PROMPT.to_string(),
+        ..Default::default()
+    });
+    history.agents[index].msg_ids.push(CONTINUE_MSG_ID);
+
+    start_streaming(state, chat_id, history.agents.len());
+    stream_agent_reply(state, ctx, index, msg_id, preset, history, true);
+}
+
+// the latest prompt of the chat, the user's or a developer message, as its
+// position in Omnis' history
+fn last_prompt_pos(chat: &crate::common::Chat) -> Option<usize> {
+    chat.agents.first()?.msg_ids.iter().rposition(|id| chat.msg_pool.get(id)
+        .is_some_and(|msg| matches!(msg.msg_role,
+            crate::common::MsgRole::User | crate::common::MsgRole::Developer)))
+}
+
+// a saved chat's agent that has neither replied to the latest prompt nor is
+// streaming a reply to it. Muted agents sit prompts out on purpose.
+fn awaits_last_prompt(chat: &crate::common::Chat, index: usize) -> bool {
+    let Some(agent) = chat.agents.get(index).filter(|a| !a.deleted && !a.muted) else {
+        return false;
+    };
+    let Some(pos) = last_prompt_pos(chat).filter(|_| chat.id > 0) else {
+        return false;
+    };
+    let prompt_id = chat.agents[0].msg_ids[pos];
+    match agent.msg_ids.iter().position(|id| *id == prompt_id) {
+        Some(at) => !agent.msg_ids[at..].iter().any(|id| chat.msg_pool.get(id)
+            .is_some_and(|msg| msg.msg_role == crate::common::MsgRole::Assistant)),
+        None => true,
+    }
+}
+
+/// Streams a reply to the chat's latest prompt from a single agent, e.g. one
+/// added after the others answered. An agent that missed prompts is given
+/// them first, without the other agents' replies.
+pub fn run_agent_on_last_prompt(state: &mut State, ctx: &egui::Context,
+        index: usize) {
+    if state.chat_streaming_state.streaming {
+        state.error_msg = Some(t!("error_busy_streaming").to_string());
+        return;
+    }
+    let chat_id = state.active_chat_id.unwrap_or(0);
+    if !state.open_chats.get(&chat_id).is_some_and(|chat| awaits_last_prompt(chat, index)) {
+        return;
+    }
+    let Some(preset) = agent_preset(state, chat_id, index) else {
+        return;
+    };
+    let Some(chat) = state.open_chats.get_mut(&chat_id) else {
+        return;
+    };
+    let Some(pos) = last_prompt_pos(chat) else {
+        return;
+    };
+
+    // the prompts it missed, in the order Omnis has them
+    let missed: Vec<i64> = chat.agents[0].msg_ids[..=pos].iter()
+        .filter(|id| !chat.agents[index].msg_ids.contains(id))
+        .filter(|id| chat.msg_pool.get(id).is_some_and(
+            |msg| msg.msg_role != crate::common::MsgRole::Assistant))
+        .copied()
+        .collect();
+    let agent = &mut chat.agents[index];
+    if !missed.is_empty() {
+        agent.msg_ids.extend(missed);
+        let _ = crate::db::mod_agent_msgs(&state.db_conn, agent.id, &agent.msg_ids);
+    }
+    if agent.preset.is_none() {
+        agent.preset = Some(preset.clone());
+        let _ = crate::db::update_agent_preset_snapshot(&state.db_conn, agent.id,
+            agent.preset.as_ref());
+    }
+    let history = chat.clone();
+
+    let mut reply = crate::common::ChatMsg {
+        msg_role: crate::common::MsgRole::Assistant,
+        preset: Some(preset.clone()),
+        preset_id: chat.agents[index].preset_selection.id,
+        name: Some(chat.agents[index].name.clone()),
+        ..Default::default()
+    };
+    if let Err(e) = crate::db::mk_msg(&state.db_conn, &mut reply) {
+        eprintln!("CRITICAL DB ERROR (save msg): {}", e);
+        return;
+    }
+    for ind in [0, index] {
+        let agent = &mut chat.agents[ind];
+        agent.msg_ids.push(reply.id);
+        let _ = crate::db::mod_agent_msgs(&state.db_conn, agent.id, &agent.msg_ids);
+    }
+    let msg_id = reply.id;
+    chat.msg_pool.insert(msg_id, reply);
+
+    start_streaming(state, chat_id, history.agents.len());
+    stream_agent_reply(state, ctx, index, msg_id, preset, history, false);
+}
+
+// the agent that wrote the message
+fn reply_agent(state: &State, chat_id: i64, msg_id: i64) -> Option<usize> {
+    let chat = state.open_chats.get(&chat_id)?;
+
+    // Omnis (0) holds every message, we need the agent that wrote it
+    chat.agents.iter().skip(1)
+        .position(|a| a.msg_ids.contains(&msg_id)).map(|i| i + 1)
+        .filter(|index| *index < 128)
+}
+
+// the preset the agent answers with, if it has none the user is told so
+fn agent_preset(state: &mut State, chat_id: i64, index: usize)
+        -> Option<crate::common::Preset> {
+    let agent = state.open_chats.get(&chat_id)?.agents.get(index)?;
+    let preset = agent.preset.clone()
+            .or_else(|| state.presets.get(agent.preset_selection.id).cloned());
+    if preset.is_none() {
+        state.error_msg = Some(t!("error_no_agent_preset_selected").to_string());
+        state.is_modal_open = true;
+    }
+    preset
+}
+
+// clears a reply so the stream can fill it back in, and returns the history
+// the agent saw when it first answered. Only the one on screen is cleared,
+// the stored reply is overwritten once the new one finishes without an error.
+// Called after start_streaming, which makes room in replaced_msgs.
+fn reset_reply(state: &mut State, chat_id: i64, index: usize, msg_id: i64,
+        preset: &crate::common::Preset) -> Option<crate::common::Chat> {
+    let chat = state.open_chats.get_mut(&chat_id)?;
+
+    if let Some(msg) = chat.msg_pool.get_mut(&msg_id) {
+        if let Some(slot) = state.chat_streaming_state.replaced_msgs.get_mut(index) {
+            *slot = Some(msg.clone());
+        }
+        msg.content.clear();
+        msg.reasoning = None;
+        msg.prompt_tokens = None;
+        msg.completion_tokens = None;
+        msg.cost = None;
+        msg.elapsed_ms = None;
+        msg.tokens_per_sec = None;
+        msg.truncated = false;
+        msg.error = None;
+        msg.interrupted = false;
+        msg.preset = Some(preset.clone());
+    }
+
+    let mut history = chat.clone();
+    if let Some(pos) = history.agents[index].msg_ids.iter()
+            .position(|id| *id == msg_id) {
+        history.agents[index].msg_ids.truncate(pos);
+    }
+    Some(history)
+}
+
+// empties the per-agent buffers before one or more agents of the chat start
+// streaming
+fn start_streaming(state: &mut State, chat_id: i64, agent_count: usize) {
+    let agent_count = std::cmp::min(agent_count, 128);
+    let streaming_state = &mut state.chat_streaming_state;
+    streaming_state.streaming = true;
+    streaming_state.chat_id = chat_id;
+    streaming_state.bitmask = 0;
+    streaming_state.msg_ids.clear();
+    streaming_state.msg_ids.resize_with(agent_count, || 0);
+    streaming_state.content_buffers.clear();
+    streaming_state.content_buffers.resize_with(agent_count, String::new);
+    streaming_state.reasoning_buffers.clear();
+    streaming_state.reasoning_buffers.resize_with(agent_count, String::new);
+    streaming_state.usage_buffers.clear();
+    streaming_state.usage_buffers.resize_with(agent_count, || None);
+    streaming_state.start_times.clear();
+    streaming_state.start_times.resize_with(agent_count, || None);
+    streaming_state.abort_flags.clear();
+    streaming_state.abort_flags.resize_with(agent_count, || None);
+    streaming_state.replaced_msgs.clear();
+    streaming_state.replaced_msgs.resize_with(agent_count, || None);
+    streaming_state.failed = 0;
+    streaming_state.abandoned = 0;
+}
+
+// streams a single agent's reply into msg_id. When `resume` is set the
+// buffers start from what the message already has, so the new tokens
+// append to it and its time keeps counting from where it stopped
+fn stream_agent_reply(state: &mut State, ctx: &egui::Context, index: usize,
+        msg_id: i64, mut preset: crate::common::Preset,
+        history: crate::common::Chat, resume: bool) {
+    if preset.chat_router == crate::common::ChatRouter::Openrouter {
+        preset.api_key = state.openrouter_key(preset.key_name.as_deref());
+    }
+
+    let resumed = history.msg_pool.get(&msg_id).filter(|_| resume);
+    // the last stream's warnings don't belong to this one
+    if let Some(msg_ui) = state.chat_msg_ui.get_mut(&msg_id) {
+        msg_ui.warnings.clear();
+    }
+    let streaming_state = &mut state.chat_streaming_state;
+    streaming_state.bitmask |= 1 << index as u128;
+    streaming_state.msg_ids[index] = msg_id;
+    let mut start = std::time::Instant::now();
+    if let Some(msg) = resumed {
+        streaming_state.content_buffers[index] = msg.content.clone();
+        streaming_state.reasoning_buffers[index] =
+            msg.reasoning.clone().unwrap_or_default();
+        let elapsed = std::time::Duration::from_millis(
+            msg.elapsed_ms.unwrap_or(0).max(0) as u64);
+        start = start.checked_sub(elapsed).unwrap_or(start);
+    }
+    streaming_state.start_times[index] = Some(start);
+
+    let abort_flag = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+    streaming_state.abort_flags[index] = Some(abort_flag.clone());
+
+    let tx = state.chat_streaming_state.tx.clone();
+    let que = crate::common::ChatQue {
+        preset,
+        chat: std::sync::Arc::new(history),
+        agent_ind: index,
+    };
+    let ctx_clone = ctx.clone();
+
+    state.perma.rt.spawn(async move {
+        if let Err(e) = crate::common::run_chat_stream_router(que, tx.clone(), &ctx_clone, abort_flag.clone()).await {
+            let _ = tx.send(crate::common::ChatStreamEvent::Error(index, format!("Error: {}", e)));
+        }
+        let interrupted = abort_flag.load(Ordering::Relaxed);
+        let _ = tx.send(crate::common::ChatStreamEvent::Finished(index,
+            interrupted));
+    });
+}
+
 fn vertical_splitter(ui: &mut egui::Ui, width: &mut f32) {
     // 1. Allocate a thin strip of space for the handle
     let (rect, response) = ui.allocate_exact_size(
@@ -12,7 +12,7 @@ use crate::gui::SplitButton;
 
 use crate::{
     common::{
-        ChatMsg, ChatMsgUi, MsgRole,
+        ChatMsg, ChatMsgUi, MsgAction, MsgRole, Preset,
     },
     gui::{State},
 };
@@ -21,12 +21,23 @@ use crate::{
 
 enum ContentChunk<'a> {
     Markdown(&'a str),
-    RustCode {
+    Code {
+        lang: &'a str, // the fence tag, empty when there is none
         code: &'a str,
         filepath: Option<String>,
     }
 }
 
+// the editor highlighting for a fence tag, plain text when there is no tag
+// or the editor does not know the language
+fn fence_syntax(ctx: &egui::Context, lang: &str) -> Syntax {
+    match lang.to_ascii_lowercase().as_str() {
+        "rust" | "rs" => Syntax::rust(),
+        "c" | "h" => Syntax::get_or_load(ctx, "c"),
+        _ => Syntax::text(),
+    }
+}
+
 fn apply_llm_diffs(original: &str, snippet: &str) -> Option<String> {
     static RE_DIFF: OnceLock<Regex> = OnceLock::new();
     let re_diff = RE_DIFF.get_or_init(|| {
@@ -186,6 +197,8 @@ fn parse_chunks(text: &str) -> Vec<ContentChunk<'_>> {
         // Safe extraction of the full match
         let full_match = if let Some(m) = caps.get(0) { m } else { continue; };
 
+        let lang = caps.get(1).map_or("", |m| m.as_str());
+
         if full_match.start() > last_end {
             let md_text = &text[last_end..full_match.start()];
             chunks.push(ContentChunk::Markdown(md_text));
@@ -214,7 +227,8 @@ fn parse_chunks(text: &str) -> Vec<ContentChunk<'_>> {
             }
         }
 
-        chunks.push(ContentChunk::RustCode {
+        chunks.push(ContentChunk::Code {
+            lang,
             code: code_match,
             filepath: current_filepath.clone(),
         });
@@ -343,6 +357,148 @@ pub fn ui_chat(ctx: &egui::Context, state: &mut State) {
     });
 }
 
+// --- Find in Chat ---
+
+// Ctrl+F bar over the active chat; it searches the loaded messages only
+#[derive(Default)]
+pub struct FindBar {
+    pub open: bool,
+    pub focus: bool, // put the cursor in the term field on the next frame
+    term: String,
+    chat_id: i64, // the chat the hits belong to
+    hits: Vec<i64>, // matching message ids, in chat order
+    current: usize,
+}
+
+pub fn render_find_bar(ui: &mut egui::Ui, state: &mut State, chat_id: i64) {
+    let find = &mut state.find_bar;
+    let (mut changed, mut step, mut close) = (false, 0isize, false);
+    ui.horizontal(|ui| {
+        let response = ui.add(egui::TextEdit::singleline(&mut find.term)
+            .hint_text(t!("find_hint"))
+            .desired_width(200.0));
+        if std::mem::take(&mut find.focus) {
+            response.request_focus();
+        }
+        changed = response.changed() || find.chat_id != chat_id;
+        if response.lost_focus() {
+            let (enter, shift, escape) = ui.input(|i| (i.key_pressed(egui::Key::Enter),
+                i.modifiers.shift, i.key_pressed(egui::Key::Escape)));
+            if enter {
+                step = if shift { -1 } else { 1 };
+                response.request_focus(); // so that Enter keeps stepping
+            }
+            close = escape;
+        }
+
+        if !find.term.is_empty() && !changed {
+            let label = if find.hits.is_empty() {
+                t!("find_no_hits").to_string()
+            } else {
+                format!("{}/{}", find.current + 1, find.hits.len())
+            };
+            ui.label(RichText::new(label).small().weak());
+        }
+        if ui.button("⏶").on_hover_text(t!("find_prev_tooltip")).clicked() {
+            step = -1;
+        }
+        if ui.button("⏷").on_hover_text(t!("find_next_tooltip")).clicked() {
+            step = 1;
+        }
+        close |= ui.button("✖").clicked();
+    });
+
+    if close {
+        state.find_bar.open = false;
+        state.find_bar.term.clear();
+        update_find_hits(state, chat_id);
+    } else if changed {
+        update_find_hits(state, chat_id);
+        scroll_to_find_hit(state);
+    } else if step != 0 && !state.find_bar.hits.is_empty() {
+        let find = &mut state.find_bar;
+        let len = find.hits.len() as isize;
+        find.current = (find.current as isize + step).rem_euclid(len) as usize;
+        scroll_to_find_hit(state);
+    }
+}
+
+// marks the messages of the chat that contain the term, forgetting the old hits
+fn update_find_hits(state: &mut State, chat_id: i64) {
+    let find = &mut state.find_bar;
+    for msg_id in find.hits.drain(..) {
+        if let Some(msg_ui) = state.chat_msg_ui.get_mut(&msg_id) {
+            msg_ui.find_term = None;
+        }
+    }
+    find.chat_id = chat_id;
+    find.current = 0;
+    let term = find.term.to_lowercase();
+    let Some(chat) = state.open_chats.get(&chat_id).filter(|_| !term.is_empty()) else {
+        return;
+    };
+    // Omnis (agent 0) holds every message in the order they are shown
+    for msg_id in chat.agents.first().map(|a| a.msg_ids.as_slice()).unwrap_or_default() {
+        if chat.msg_pool.get(msg_id)
+                .is_some_and(|msg| !find_ranges(&msg.content, &term).is_empty()) {
+            find.hits.push(*msg_id);
+            state.chat_msg_ui.entry(*msg_id).or_default().find_term = Some(term.clone());
+        }
+    }
+}
+
+fn scroll_to_find_hit(state: &mut State) {
+    let find = &state.find_bar;
+    if let Some(msg_id) = find.hits.get(find.current) {
+        state.chat_msg_ui.entry(*msg_id).or_default().scroll_to = true;
+    }
+}
+
+// byte ranges of the case-insensitive occurrences of a lowercased term
+fn find_ranges(text: &str, term: &str) -> Vec<std::ops::Range<usize>> {
+    let mut ranges = Vec::new();
+    if term.is_empty() {
+        return ranges;
+    }
+    let mut from = 0;
+    for (start, _) in text.char_indices() {
+        if start < from {
+            continue;
+        }
+        let mut chars = text[start..].char_indices();
+        let matched = term.chars().all(|t| chars.next()
+            .is_some_and(|(_, c)| c.to_lowercase().eq(t.to_lowercase())));
+        if matched {
+            from = chars.next().map_or(text.len(), |(i, _)| start + i);
+            ranges.push(start..from);
+        }
+    }
+    ranges
+}
+
+// plain text with the find bar's matches painted over
+fn highlighted_text(ui: &egui::Ui, text: &str, term: &str, strong: bool)
+        -> egui::text::LayoutJob {
+    let format = egui::TextFormat {
+        font_id: egui::TextStyle::Body.resolve(ui.style()),
+        color: if strong { ui.visuals().strong_text_color() } else { ui.visuals().text_color() },
+        ..Default::default()
+    };
+    let hit_format = egui::TextFormat {
+        background: ui.visuals().selection.bg_fill,
+        ..format.clone()
+    };
+    let mut job = egui::text::LayoutJob::default();
+    let mut last = 0;
+    for range in find_ranges(text, term) {
+        job.append(&text[last..range.start], 0.0, format.clone());
+        job.append(&text[range.clone()], 0.0, hit_format.clone());
+        last = range.end;
+    }
+    job.append(&text[last..], 0.0, format);
+    job
+}
+
 // --- Message Rendering ---
 
 #[tracing::instrument(skip_all)]
@@ -355,6 +511,19 @@ pub fn render_chat_messages(ui: &mut egui::Ui, state: &mut State, chat_id: i64,
 
     // We clone the Rc pointer here (very cheap)
     let math_cache = state.math_cache.clone();
+    let view = ChatViewOptions {
+        expand_reasoning: *state.perma.expand_reasoning.lock().unwrap(),
+        readonly: *state.perma.readonly.lock().unwrap(),
+        column_widths: *state.perma.column_widths.lock().unwrap(),
+        compact: *state.perma.compact_chat.lock().unwrap(),
+        stack: *state.perma.stack_agents.lock().unwrap(),
+    };
+    if view.compact {
+        for font_id in ui.style_mut().text_styles.values_mut() {
+            font_id.size *= COMPACT_TEXT_SCALE;
+        }
+        ui.spacing_mut().item_spacing *= 0.5;
+    }
 
     // Fetch the specific chat being rendered
     let Some(chat) = state.open_chats.get(&chat_id) else {
@@ -378,17 +547,33 @@ pub fn render_chat_messages(ui: &mut egui::Ui, state: &mut State, chat_id: i64,
 
     let active_agent_ind = 0;
     let mut assistant_batch: Vec<i64> = Vec::new();
+    // streams still running in this chat, by the message they write to
+    let streaming = &state.chat_streaming_state;
+    let stop_flags: std::collections::HashMap<i64, std::sync::Arc<std::sync::atomic::AtomicBool>> =
+        streaming.abort_flags.iter().enumerate()
+        .filter(|(ind, _)| streaming.bitmask & (1 << *ind as u128) != 0)
+        .filter_map(|(ind, flag)| Some((*streaming.msg_ids.get(ind)?, flag.clone()?)))
+        .collect();
+    // keep each agent in its own column no matter which stream finished first
+    let sort_batch = |batch: &mut Vec<i64>| batch.sort_by_key(|id|
+        (chat.agent_ind_of(*id).unwrap_or(usize::MAX), *id));
+
+    // replies of agents the user hid from the chat
+    let is_hidden = |msg_id: i64| chat.agent_ind_of(msg_id)
+        .and_then(|ind| chat.agents.get(ind))
+        .is_some_and(|agent| agent.hidden);
 
     if let Some(agent) = chat.agents.get(active_agent_ind) {
         for &msg_id in &agent.msg_ids {
             if let Some(msg) = msg_pool.get(&msg_id) {
                 match msg.msg_role {
-                    MsgRole::User | MsgRole::System => {
+                    MsgRole::User | MsgRole::System | MsgRole::Developer => {
                         if !assistant_batch.is_empty() {
+                            sort_batch(&mut assistant_batch);
                             // Pass a clone of the cache pointer
                             render_assistant_grid(ui, cache, msg_pool,
                                 msg_ui_map, &assistant_batch, total_width, math_cache.clone(),
-                            project_root, &op_tx);
+                            project_root, &op_tx, &stop_flags, view);
                             assistant_batch.clear();
                         }
 
@@ -396,22 +581,244 @@ pub fn render_chat_messages(ui: &mut egui::Ui, state: &mut State, chat_id: i64,
                                 .or_insert(ChatMsgUi::default());
                         // Pass a clone of the cache pointer
                         render_user_msg(ui, cache, msg, msg_ui, total_width, math_cache.clone(),
-                            project_root, &op_tx);
+                            project_root, &op_tx, view);
                     }
                     _ => {
-                        assistant_batch.push(msg_id);
+                        if !is_hidden(msg_id) {
+                            assistant_batch.push(msg_id);
+                        }
                     }
                 }
             }
         }
 
         if !assistant_batch.is_empty() {
+            sort_batch(&mut assistant_batch);
             // Pass a clone of the cache pointer
             render_assistant_grid(ui, cache, msg_pool, msg_ui_map,
                     &assistant_batch, total_width, math_cache.clone(),
-                    project_root, &op_tx);
+                    project_root, &op_tx, &stop_flags, view);
         }
     }
+
+    // carry out whatever was requested from the message headers
+    let pending: Vec<(i64, MsgAction)> = state.chat_msg_ui.iter_mut()
+        .filter_map(|(id, msg_ui)| msg_ui.action.take().map(|a| (*id, a)))
+        .filter(|(_, action)| !view.readonly || *action == MsgAction::CopyCurl)
+        .collect();
+    for (msg_id, action) in pending {
+        match action {
+            MsgAction::SaveEdit => save_msg_edit(state, chat_id, msg_id),
+            MsgAction::Regenerate => crate::gui::bottom_panel::regenerate_agent_msg(
+                state, ui.ctx(), chat_id, msg_id),
+            MsgAction::Delete => delete_chat_msg(state, chat_id, msg_id),
+            MsgAction::Branch => branch_from_msg(state, chat_id, msg_id),
+            MsgAction::TogglePin => toggle_msg_pin(state, chat_id, msg_id),
+            MsgAction::Continue => crate::gui::bottom_panel::continue_agent_msg(
+                state, ui.ctx(), chat_id, msg_id),
+            MsgAction::CopyCurl => crate::gui::bottom_panel::copy_msg_curl(
+                state, ui.ctx(), chat_id, msg_id),
+            MsgAction::SavePreset => save_msg_preset(state, chat_id, msg_id),
+        }
+    }
+}
+
+// view settings shared by every message of the chat being rendered
+#[derive(Clone, Copy)]
+struct ChatViewOptions {
+    expand_reasoning: bool, // thought process starts unfolded
+    readonly: bool, // hides everything that changes the chat
+    column_widths: (f32, f32), // (min, max) of the reply columns
+    compact: bool, // tighter margins and spacing, smaller text
+    stack: bool, // replies one under another instead of side by side
+}
+
+// frame of developer messages, apart from the user's and the assistants'
+const DEVELOPER_MSG_COLOR: egui::Color32 = egui::Color32::from_rgb(170, 120, 220);
+
+// text size in compact mode, relative to the normal one
+const COMPACT_TEXT_SCALE: f32 = 0.9;
+
+// height of a reply column while the batch scrolls in sync
+const SYNC_SCROLL_HEIGHT: f32 = 500.0;
+
+/// Folds or unfolds the thought process of every reply in the chat
+pub fn set_all_reasoning_open(state: &mut State, chat_id: i64, open: bool) {
+    let Some(chat) = state.open_chats.get(&chat_id) else {
+        return;
+    };
+    for msg in chat.msg_pool.values()
+            .filter(|msg| msg.reasoning.as_ref().is_some_and(|r| !r.is_empty())) {
+        state.chat_msg_ui.entry(msg.id).or_default().reasoning_open = Some(open);
+    }
+}
+
+// forks the chat at this message and switches the tab to the new branch
+fn branch_from_msg(state: &mut State, chat_id: i64, msg_id: i64) {
+    if state.chat_streaming_state.streaming {
+        state.error_msg = Some(t!("error_busy_streaming").to_string());
+        return;
+    }
+    let new_chat_id = match crate::db::branch_chat(&state.db_conn, chat_id, msg_id,
+            &t!("chat_branch_suffix")) {
+        Ok(id) => id,
+        Err(e) => {
+            eprintln!("Error branching chat {} at message {}: {}", chat_id, msg_id, e);
+            return;
+        }
+    };
+    crate::gui::reload_db_chats(&state.db_conn, &mut state.db_chats);
+    let branch = crate::db::fetch_chat(&state.db_conn, new_chat_id, &state.presets)
+        .unwrap_or_default();
+    state.open_chats.insert(new_chat_id, branch);
+    crate::gui::panes::open_chat_in_tab(state, new_chat_id);
+}
+
+// how long the Undo button stays up after a message is deleted
+const UNDO_DELETE_SECS: f32 = 8.0;
+
+// the last deleted message, kept so that a misclick can be undone
+pub struct DeletedMsg {
+    chat_id: i64,
+    msg: ChatMsg,
+    positions: Vec<(i64, usize)>, // (agent id, index in its msg_ids)
+    deleted_at: std::time::Instant,
+}
+
+// removes the message from every agent that references it, then from the db
+fn delete_chat_msg(state: &mut State, chat_id: i64, msg_id: i64) {
+    if state.chat_streaming_state.streaming {
+        state.error_msg = Some(t!("error_busy_streaming").to_string());
+        return;
+    }
+    let Some(chat) = state.open_chats.get_mut(&chat_id) else {
+        return;
+    };
+    // Omnis and the authoring agent both hold the id, so check them all
+    let mut positions = Vec::new();
+    for agent in chat.agents.iter_mut() {
+        if let Some(pos) = agent.msg_ids.iter().position(|id| *id == msg_id) {
+            positions.push((agent.id, pos));
+            agent.msg_ids.retain(|id| *id != msg_id);
+            let _ = crate::db::mod_agent_msgs(&state.db_conn, agent.id,
+                &agent.msg_ids);
+        }
+    }
+    if let Err(e) = crate::db::delete_msg(&state.db_conn, msg_id) {
+        eprintln!("Error deleting message {}: {}", msg_id, e);
+    }
+    state.deleted_msg = chat.msg_pool.remove(&msg_id).map(|msg| DeletedMsg {
+        chat_id,
+        msg,
+        positions,
+        deleted_at: std::time::Instant::now(),
+    });
+    state.chat_msg_ui.remove(&msg_id);
+}
+
+// puts the last deleted message back where it was, under a new id
+fn undo_delete_msg(state: &mut State) {
+    let Some(DeletedMsg { chat_id, mut msg, positions, .. }) =
+            state.deleted_msg.take() else {
+        return;
+    };
+    let Some(chat) = state.open_chats.get_mut(&chat_id) else {
+        return;
+    };
+    let result = crate::db::mk_msg(&state.db_conn, &mut msg)
+        .and_then(|()| crate::db::mod_msg(&state.db_conn, &msg));
+    if let Err(e) = result {
+        eprintln!("Error restoring deleted message: {}", e);
+        return;
+    }
+    for (agent_id, pos) in positions {
+        if let Some(agent) = chat.agents.iter_mut().find(|a| a.id == agent_id) {
+            let pos = pos.min(agent.msg_ids.len());
+            agent.msg_ids.insert(pos, msg.id);
+            let _ = crate::db::mod_agent_msgs(&state.db_conn, agent.id,
+                &agent.msg_ids);
+        }
+    }
+    chat.msg_pool.insert(msg.id, msg);
+}
+
+/// Floating Undo button, shown for a few seconds after a message is deleted
+pub fn render_undo_delete(ctx: &egui::Context, state: &mut State) {
+    let Some(deleted) = &state.deleted_msg else {
+        return;
+    };
+    let remaining = UNDO_DELETE_SECS - deleted.deleted_at.elapsed().as_secs_f32();
+    if remaining <= 0.0 || !state.open_chats.contains_key(&deleted.chat_id) {
+        state.deleted_msg = None;
+        return;
+    }
+    let mut undo = false;
+    egui::Area::new(egui::Id::new("undo_delete_msg"))
+        .anchor(egui::Align2::CENTER_BOTTOM, [0.0, -120.0])
+        .order(egui::Order::Foreground)
+        .show(ctx, |ui| {
+            egui::Frame::popup(ui.style()).show(ui, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label(t!("msg_deleted"));
+                    undo = ui.button(t!("undo_btn")).clicked();
+                    if ui.small_button("✖").clicked() {
+                        state.deleted_msg = None;
+                    }
+                });
+            });
+        });
+    if undo {
+        undo_delete_msg(state);
+    }
+    ctx.request_repaint_after(std::time::Duration::from_secs_f32(remaining));
+}
+
+// pinned messages are sent even when the preset trims the history
+fn toggle_msg_pin(state: &mut State, chat_id: i64, msg_id: i64) {
+    let Some(msg) = state.open_chats.get_mut(&chat_id)
+            .and_then(|chat| chat.msg_pool.get_mut(&msg_id)) else {
+        return;
+    };
+    match crate::db::mod_msg_pinned(&state.db_conn, msg_id, !msg.pinned) {
+        Ok(()) => msg.pinned = !msg.pinned,
+        Err(e) => eprintln!("Error pinning message {}: {}", msg_id, e),
+    }
+}
+
+// writes the edited content to the db and to the rendered chat
+fn save_msg_edit(state: &mut State, chat_id: i64, msg_id: i64) {
+    let Some(msg_ui) = state.chat_msg_ui.get_mut(&msg_id) else {
+        return;
+    };
+    let content = std::mem::take(&mut msg_ui.edit_buffer);
+    if let Err(e) = crate::db::mod_msg_content(&state.db_conn, msg_id, &content) {
+        eprintln!("Error saving edited message {}: {}", msg_id, e);
+        msg_ui.edit_buffer = content; // keep the edit so it is not lost
+        return;
+    }
+    msg_ui.editing = false;
+    if let Some(msg) = state.open_chats.get_mut(&chat_id)
+            .and_then(|chat| chat.msg_pool.get_mut(&msg_id)) {
+        msg.content = content;
+    }
+}
+
+fn save_msg_preset(state: &mut State, chat_id: i64, msg_id: i64) {
+    let Some(msg_ui) = state.chat_msg_ui.get(&msg_id) else {
+        return;
+    };
+    // the window only offers Save for json that parses
+    let Ok(preset) = serde_json::from_str::<Preset>(&msg_ui.preset_buffer) else {
+        return;
+    };
+    if let Err(e) = crate::db::mod_msg_preset(&state.db_conn, msg_id, &preset) {
+        state.error_msg = Some(t!("error_saving_preset_snapshot", error = e).to_string());
+        return;
+    }
+    if let Some(msg) = state.open_chats.get_mut(&chat_id)
+            .and_then(|chat| chat.msg_pool.get_mut(&msg_id)) {
+        msg.preset = Some(preset);
+    }
 }
 
 fn render_assistant_grid(
@@ -424,11 +831,12 @@ fn render_assistant_grid(
     math_cache: std::rc::Rc<std::cell::RefCell<std::collections::HashMap<String, std::sync::Arc<[u8]>>>>,
     project_root: &Option<std::path::PathBuf>,
     op_tx: &std::sync::mpsc::Sender<crate::common::FileOpMsg>, // <-- New
+    stop_flags: &std::collections::HashMap<i64, std::sync::Arc<std::sync::atomic::AtomicBool>>,
+    view: ChatViewOptions,
 ) {
     let effective_width = total_width - 38.0;
-    let item_min_width = 400.0;
-    let item_max_width = 900.0;
-    let spacing = 10.0;
+    let (item_min_width, item_max_width) = view.column_widths;
+    let spacing = if view.compact { 4.0 } else { 10.0 };
 
     let max_cols = (((effective_width + spacing) / (item_min_width + spacing)).floor() as usize).max(1);
     let divisor = if batch_ids.len() < max_cols { batch_ids.len() as f32 } else { max_cols as f32 };
@@ -437,26 +845,78 @@ fn render_assistant_grid(
     let rounding_buffer = divisor * 2.0;
 
     let raw_item_width = (effective_width - total_spacing - rounding_buffer) / divisor;
-    let item_width = raw_item_width.clamp(item_min_width, item_max_width);
-    let cols = max_cols;
+    // stacked replies take the whole width, one per row
+    let (item_width, cols) = if view.stack {
+        (effective_width, 1)
+    } else {
+        (raw_item_width.clamp(item_min_width, item_max_width), max_cols)
+    };
+
+    // several replies can be compared in fixed-height columns that scroll
+    // together, the offset is shared through the first reply's id
+    let sync_scroll = batch_ids.len() > 1 && !view.stack && {
+        let first_ui = msg_ui_map.entry(batch_ids[0]).or_default();
+        ui.toggle_value(&mut first_ui.sync_scroll, t!("sync_scroll_btn"))
+            .on_hover_text(t!("sync_scroll_tooltip"));
+        first_ui.sync_scroll
+    };
+    let sync_id = egui::Id::new(("sync_scroll", batch_ids[0]));
+    let shared_offset = ui.data(|d| d.get_temp::<f32>(sync_id)).unwrap_or(0.0);
+    let mut new_offset = shared_offset;
 
     for (row_idx, row_ids) in batch_ids.chunks(cols).enumerate() {
+        // with nothing beside it, a stacked reply needs its agent named
+        if let Some(name) = row_ids.first().filter(|_| view.stack)
+                .and_then(|id| msg_pool.get(id))
+                .and_then(|msg| msg.name.as_deref()) {
+            ui.label(egui::RichText::new(name).strong().heading());
+        }
         ui.horizontal_top(|ui| {
             ui.spacing_mut().item_spacing.x = spacing;
             for &msg_id in row_ids {
                 if let Some(msg) = msg_pool.get(&msg_id) {
                     let msg_ui = msg_ui_map.entry(msg_id).or_insert(ChatMsgUi::default());
+                    let streaming = stop_flags.contains_key(&msg_id);
 
-                    ui.allocate_ui_with_layout(
+                    let column = ui.allocate_ui_with_layout(
                         egui::vec2(item_width, 0.0),
                         egui::Layout::top_down(egui::Align::Min),
                         |ui| {
                             ui.set_width(item_width);
-                            render_assistant_msg(
-                                    ui, cache, msg, msg_ui, item_width, math_cache.clone(),
-                                    project_root, op_tx);
+                            if !sync_scroll {
+                                render_assistant_msg(
+                                        ui, cache, msg, msg_ui, item_width, math_cache.clone(),
+                                        project_root, op_tx, view, streaming);
+                                return;
+                            }
+                            let output = egui::ScrollArea::vertical()
+                                .id_salt(("sync_scroll_col", msg_id))
+                                .max_height(SYNC_SCROLL_HEIGHT)
+                                .vertical_scroll_offset(shared_offset)
+                                .show(ui, |ui| {
+                                    render_assistant_msg(
+                                            ui, cache, msg, msg_ui, item_width, math_cache.clone(),
+                                            project_root, op_tx, view, streaming);
+                                });
+                            // the column under the pointer leads, the shorter
+                            // ones just stop at their end
+                            if ui.rect_contains_pointer(output.inner_rect) {
+                                new_offset = output.state.offset.y;
+                            }
                         }
                     );
+
+                    // stop just this agent, overlaid on the column's corner
+                    if let Some(flag) = stop_flags.get(&msg_id) {
+                        let stopping = flag.load(std::sync::atomic::Ordering::Relaxed);
+                        let size = egui::vec2(24.0, 20.0);
+                        let rect = egui::Rect::from_min_size(
+                            column.response.rect.right_bottom() - size, size);
+                        if ui.put(rect, egui::Button::new("⏹").selected(!stopping))
+                                .on_hover_text(t!("stop_agent_tooltip")).clicked() {
+                            flag.store(true, std::sync::atomic::Ordering::Relaxed);
+                        }
+                    }
                 }
             }
             ui.allocate_space(egui::vec2(ui.available_width(), 0.0));
@@ -466,7 +926,10 @@ fn render_assistant_grid(
              ui.add_space(spacing);
         }
     }
-    ui.add_space(15.0);
+    if sync_scroll {
+        ui.data_mut(|d| d.insert_temp(sync_id, new_offset));
+    }
+    ui.add_space(if view.compact { 6.0 } else { 15.0 });
 }
 
 fn render_user_msg(
@@ -478,9 +941,16 @@ fn render_user_msg(
     math_cache: std::rc::Rc<std::cell::RefCell<std::collections::HashMap<String, std::sync::Arc<[u8]>>>>,
     project_root: &Option<std::path::PathBuf>,
     op_tx: &std::sync::mpsc::Sender<crate::common::FileOpMsg>, // <-- New
+    view: ChatViewOptions,
 ) {
     let effective_width = total_width - 30.0;
-    let max_w = effective_width.clamp(400.0, 800.0);
+    // developer instructions span the whole chat, set apart from the prompts
+    let developer = msg.msg_role == MsgRole::Developer;
+    let max_w = if developer {
+        effective_width.max(400.0)
+    } else {
+        effective_width.clamp(400.0, 800.0)
+    };
 
     let scroll_area = egui::ScrollArea::horizontal();
 
@@ -488,26 +958,47 @@ fn render_user_msg(
         ui.set_max_width(max_w);
         ui.horizontal(|ui| {
             ui.vertical(|ui| {
-                let max_w = effective_width.clamp(400.0, 800.0);
                 ui.set_max_width(max_w);
 
+                // pinned prompts stand out, they are always sent
+                let stroke = if msg.pinned {
+                    Stroke { width: 2.0, color: ui.visuals().warn_fg_color }
+                } else if developer {
+                    Stroke { width: 1.5, color: DEVELOPER_MSG_COLOR }
+                } else {
+                    Stroke { width: 1.0, color: ui.visuals().strong_text_color() }
+                };
+                let left = match (developer, view.compact) {
+                    (true, _) => 0,
+                    (false, true) => 24,
+                    (false, false) => 127,
+                };
+                let (outer_margin, inner_margin) = if view.compact {
+                    (Margin { top: 0, right: 0, bottom: 6, left }, 4.0)
+                } else {
+                    (Margin { top: 0, right: 0, bottom: 15, left }, 10.0)
+                };
                 egui::Frame::default()
-                .stroke(Stroke { width: 1.0, color: ui.visuals().strong_text_color() })
-                .outer_margin(Margin { top: 0, right: 0, bottom: 15, left: 127 })
-                .inner_margin(10.0)
+                .stroke(stroke)
+                .outer_margin(outer_margin)
+                .inner_margin(inner_margin)
                 .corner_radius(5.0)
                 .fill(ui.visuals().extreme_bg_color)
                 .show(ui, |ui| {
-                    render_msg_header(ui, msg_ui, &msg.msg_role.to_string(), msg);
-                    render_msg_content(ui, cache, msg, msg_ui, (max_w - 20.0) as usize, math_cache.clone(),
-                        project_root, op_tx);
+                    render_msg_header(ui, msg_ui, &msg.msg_role.to_string(), msg, view.readonly);
+                    if msg_ui.editing && !view.readonly {
+                        render_msg_editor(ui, msg_ui);
+                    } else {
+                        render_msg_content(ui, cache, msg, msg_ui, (max_w - 20.0) as usize, math_cache.clone(),
+                            project_root, op_tx);
+                    }
 
                     // --- Render JSON Attachments as Spoilers or Images ---
                     if let Some(details_json) = &msg.details {
                         if let Ok(attachments) = serde_json::from_str::<Vec<Attachment>>(details_json) {
                             if !attachments.is_empty() {
                                 ui.add_space(8.0);
-                                egui::CollapsingHeader::new(egui::RichText::new(format!("📎 {} Attached Files", attachments.len())).strong())
+                                egui::CollapsingHeader::new(egui::RichText::new(t!("attached_files", count = attachments.len())).strong())
                                     .id_salt(format!("details_collapse_{}", msg.id))
                                     .show(ui, |ui| {
                                         // Iterate through the array of attachments
@@ -547,7 +1038,7 @@ fn render_user_msg(
                                                                 Ok(egui::load::TexturePoll::Pending { .. }) => {
                                                                     ui.horizontal(|ui| {
                                                                         ui.spinner();
-                                                                        ui.label("Decoding image...");
+                                                                        ui.label(t!("decoding_image"));
                                                                     });
                                                                 }
                                                                 Ok(egui::load::TexturePoll::Ready { texture }) => {
@@ -563,7 +1054,7 @@ fn render_user_msg(
                                                                 }
                                                             }
                                                         } else {
-                                                            ui.colored_label(ui.visuals().error_fg_color, "Failed to decode image data.");
+                                                            ui.colored_label(ui.visuals().error_fg_color, t!("image_decode_failed"));
                                                         }
                                                     } else {
                                                         // Standard Text Rendering
@@ -602,11 +1093,13 @@ fn render_assistant_msg(
     math_cache: std::rc::Rc<std::cell::RefCell<std::collections::HashMap<String, std::sync::Arc<[u8]>>>>,
     project_root: &Option<std::path::PathBuf>,
     op_tx: &std::sync::mpsc::Sender<crate::common::FileOpMsg>, // <-- New
+    view: ChatViewOptions,
+    streaming: bool, // the reply is still coming in
 ) {
     egui::Frame::default()
     .stroke(Stroke { width: 1.0, color: ui.visuals().hyperlink_color })
     .outer_margin(Margin::ZERO)
-    .inner_margin(10.0)
+    .inner_margin(if view.compact { 4.0 } else { 10.0 })
     .corner_radius(5.0)
     .fill(ui.visuals().faint_bg_color)
     .show(ui, |ui| {
@@ -617,7 +1110,7 @@ fn render_assistant_msg(
             ui.set_max_width(item_width - 25.0);
 
             let label = format!("{}:", msg.name.as_deref().unwrap_or("assistant"));
-            render_msg_header(ui, msg_ui, &label, msg);
+            render_msg_header(ui, msg_ui, &label, msg, view.readonly);
 
             if let Some(reasoning) = &msg.reasoning {
                 if !reasoning.is_empty() {
@@ -625,26 +1118,98 @@ fn render_assistant_msg(
                         ui.label(format!("{}: \n{}", t!("thought_process"), reasoning));
                         ui.separator();
                     } else {
-                        render_reasoning_block(ui, reasoning, msg.id);
+                        let open = msg_ui.reasoning_open
+                            .get_or_insert(view.expand_reasoning);
+                        render_reasoning_block(ui, reasoning, msg.id,
+                            open, streaming);
                     }
                 }
             }
 
+            // the model may think for a while before the answer starts
+            if streaming && msg.content.is_empty() {
+                render_thinking_label(ui);
+            }
+
             let content_width = (item_width - 25.0).max(100.0);
-            render_msg_content(ui, cache, msg, msg_ui, content_width as usize, math_cache,
-                project_root, op_tx);
+            if msg_ui.editing && !view.readonly {
+                render_msg_editor(ui, msg_ui);
+            } else {
+                render_msg_content(ui, cache, msg, msg_ui, content_width as usize, math_cache,
+                    project_root, op_tx);
+            }
+
+            if let Some(err) = &msg.error {
+                render_stream_error(ui, err);
+            }
+            for warning in &msg_ui.warnings {
+                ui.label(RichText::new(format!("⚠ {}", warning)).small()
+                    .color(ui.visuals().warn_fg_color));
+            }
         });
     });
 }
 
+/// The reason a stream failed, with a hint on what to do about it
+pub fn render_stream_error(ui: &mut egui::Ui, err: &str) {
+    let color = ui.visuals().error_fg_color;
+    egui::Frame::default()
+    .stroke(Stroke { width: 1.0, color })
+    .inner_margin(6.0)
+    .corner_radius(3.0)
+    .show(ui, |ui| {
+        let (title, hint) = stream_error_kind(err);
+        ui.label(RichText::new(format!("⚠ {}", title)).strong().color(color));
+        if let Some(hint) = hint {
+            ui.label(hint);
+        }
+        ui.label(RichText::new(err).small().weak());
+    });
+}
+
+// sorts the error text of either router into the failures users can act on
+fn stream_error_kind(err: &str) -> (String, Option<String>) {
+    let lower = err.to_lowercase();
+    let has = |words: &[&str]| words.iter().any(|w| lower.contains(w));
+    if has(&["connection refused", "tcp connect", "error sending request",
+            "is not running", "dns error"]) {
+        (t!("stream_error_connection").to_string(),
+            Some(t!("stream_error_connection_hint").to_string()))
+    } else if lower.contains("timed out") {
+        (t!("stream_error_timeout").to_string(),
+            Some(t!("stream_error_timeout_hint").to_string()))
+    } else if has(&["401", "403", "unauthorized", "api key", "no auth"]) {
+        (t!("stream_error_auth").to_string(),
+            Some(t!("stream_error_auth_hint").to_string()))
+    } else if has(&["429", "rate limit", "rate-limit", "too many requests"]) {
+        (t!("stream_error_rate_limit").to_string(),
+            Some(t!("stream_error_rate_limit_hint").to_string()))
+    } else if lower.contains("model") && has(&["not found", "404",
+            "does not exist", "no endpoints"]) {
+        (t!("stream_error_model_not_found").to_string(),
+            Some(t!("stream_error_model_not_found_hint").to_string()))
+    } else {
+        (t!("stream_error").to_string(), None)
+    }
+}
+
 fn render_msg_header(
     ui: &mut egui::Ui,
     msg_ui: &mut ChatMsgUi,
     label: &str,
     msg: &ChatMsg, // Changed from msg_id: i64 to msg: &ChatMsg
+    readonly: bool, // hides everything that changes the chat
 ) {
     ui.horizontal(|ui| {
-        ui.label(RichText::new(label).strong());
+        // markdown is not highlighted, so a find hit marks its header instead
+        let mut label_text = RichText::new(label).strong();
+        if msg_ui.find_term.is_some() {
+            label_text = label_text.background_color(ui.visuals().selection.bg_fill);
+        }
+        let label_response = ui.label(label_text);
+        if std::mem::take(&mut msg_ui.scroll_to) {
+            label_response.scroll_to_me(Some(egui::Align::TOP));
+        }
 
         #[cfg(debug_assertions)]
         ui.label(RichText::new(format!("msg_id: {}", msg.id)).strong());
@@ -652,17 +1217,214 @@ fn render_msg_header(
         ui.with_layout(
             egui::Layout::right_to_left(egui::Align::Center),
             |ui| {
-                // Add the Copy button first (it will be on the far right)
-                if ui.button("🗐").on_hover_text("Copy raw message to clipboard").clicked() {
+                // Add the Copy button first (it will be on the far right).
+                // It copies the markdown source in raw and rendered mode
+                // alike; code blocks carry their own copy buttons.
+                if ui.button("📋").on_hover_text(t!("copy_msg_tooltip")).clicked() {
                     ui.ctx().copy_text(msg.content.clone());
                 }
 
                 if ui.toggle_value(&mut msg_ui.show_raw, "Raw").clicked() {
                     println!("Raw button clicked");
                 }
+
+                let mut markdown = msg_ui.renders_markdown(msg);
+                if msg.msg_role != MsgRole::Assistant && !msg_ui.show_raw
+                        && ui.toggle_value(&mut markdown, "Md")
+                        .on_hover_text(t!("render_markdown_tooltip")).changed() {
+                    msg_ui.render_markdown = Some(markdown);
+                }
+
+                if msg_ui.show_raw {
+                    let mut wrap = !msg_ui.no_wrap;
+                    if ui.toggle_value(&mut wrap, "↩")
+                            .on_hover_text(t!("wrap_raw_tooltip")).changed() {
+                        msg_ui.no_wrap = !wrap;
+                    }
+                }
+
+                if !readonly {
+                    ui.menu_button("🗑", |ui| {
+                        if ui.button(RichText::new(t!("delete_msg_btn"))
+                                .color(ui.visuals().error_fg_color))
+                                .on_hover_text(t!("delete_msg_tooltip")).clicked() {
+                            msg_ui.action = Some(MsgAction::Delete);
+                            ui.close();
+                        }
+                    });
+
+                    if msg.id > 0 && ui.button("🔀")
+                            .on_hover_text(t!("branch_msg_tooltip")).clicked() {
+                        msg_ui.action = Some(MsgAction::Branch);
+                    }
+
+                    let mut pinned = msg.pinned;
+                    if msg.id > 0 && ui.toggle_value(&mut pinned, "📌")
+                            .on_hover_text(t!("pin_msg_tooltip")).clicked() {
+                        msg_ui.action = Some(MsgAction::TogglePin);
+                    }
+
+                    if msg.msg_role == MsgRole::Assistant
+                            && ui.button("♻").on_hover_text(t!("regenerate_msg_tooltip"))
+                            .clicked() {
+                        msg_ui.action = Some(MsgAction::Regenerate);
+                    }
+
+                    if msg.msg_role == MsgRole::Assistant && msg.truncated
+                            && ui.button("⏩").on_hover_text(t!("continue_msg_tooltip"))
+                            .clicked() {
+                        msg_ui.action = Some(MsgAction::Continue);
+                    }
+
+                    if ui.toggle_value(&mut msg_ui.editing, "✏")
+                            .on_hover_text(t!("edit_msg_tooltip")).clicked()
+                            && msg_ui.editing {
+                        msg_ui.edit_buffer = msg.content.clone();
+                    }
+                }
+
+                // the request behind an OpenRouter reply, to share a repro
+                if msg.preset.as_ref().is_some_and(
+                        |p| p.chat_router == crate::common::ChatRouter::Openrouter)
+                        && ui.button("cURL").on_hover_text(t!("copy_curl_tooltip"))
+                        .clicked() {
+                    msg_ui.action = Some(MsgAction::CopyCurl);
+                }
+
+                if let Some(preset) = &msg.preset
+                        && ui.toggle_value(&mut msg_ui.inspecting, "🔍")
+                        .on_hover_text(t!("inspect_preset_tooltip")).clicked()
+                        && msg_ui.inspecting {
+                    msg_ui.preset_buffer = preset_snapshot_json(preset);
+                }
+
+                if msg.interrupted {
+                    ui.label(RichText::new(t!("msg_stopped")).small()
+                        .color(ui.visuals().warn_fg_color))
+                        .on_hover_text(t!("msg_stopped_tooltip"));
+                }
+
+                if let Some(usage) = format_usage(msg) {
+                    ui.label(RichText::new(usage).small().weak());
+                }
+
+                if let Some(tokens_per_sec) = msg.tokens_per_sec {
+                    ui.label(RichText::new(format!("{:.0} tok/s",
+                        tokens_per_sec)).small().weak())
+                        .on_hover_text(t!("tokens_per_sec_tooltip"));
+                }
+
+                // ticks while streaming, frozen once the reply is done
+                if let Some(elapsed_ms) = msg.elapsed_ms {
+                    ui.label(RichText::new(format!("{:.1}s",
+                        elapsed_ms as f64 / 1000.0)).small().weak())
+                        .on_hover_text(t!("elapsed_tooltip"));
+                }
+
+                if let Some(ts_created) = &msg.ts_created {
+                    render_msg_time(ui, ts_created);
+                }
             },
         );
     });
+
+    if let Some(preset) = msg.preset.as_ref().filter(|_| msg_ui.inspecting) {
+        render_preset_snapshot(ui.ctx(), msg_ui, msg.id, preset, readonly);
+    }
+}
+
+// "2h ago", with the local date and time on hover
+fn render_msg_time(ui: &mut egui::Ui, ts_created: &str) {
+    let Ok(created) = chrono::NaiveDateTime::parse_from_str(ts_created,
+            "%Y-%m-%d %H:%M:%S").map(|t| t.and_utc()) else {
+        return;
+    };
+    let age = chrono::Utc::now() - created;
+    let text = if age.num_minutes() < 1 {
+        t!("time_just_now").to_string()
+    } else if age.num_hours() < 1 {
+        t!("time_minutes_ago", n = age.num_minutes()).to_string()
+    } else if age.num_days() < 1 {
+        t!("time_hours_ago", n = age.num_hours()).to_string()
+    } else if age.num_days() < 30 {
+        t!("time_days_ago", n = age.num_days()).to_string()
+    } else {
+        created.with_timezone(&chrono::Local).format("%Y-%m-%d").to_string()
+    };
+    ui.label(RichText::new(text).small().weak()).on_hover_text(
+        created.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M:%S").to_string());
+}
+
+fn preset_snapshot_json(preset: &Preset) -> String {
+    serde_json::to_string_pretty(preset).unwrap_or_else(|e| e.to_string())
+}
+
+// the settings a reply was produced with, as stored alongside the message
+// (overrides included). The json can be edited and saved back unless the
+// sandbox is read-only.
+fn render_preset_snapshot(ctx: &egui::Context, msg_ui: &mut ChatMsgUi,
+        msg_id: i64, preset: &Preset, readonly: bool) {
+    let mut open = msg_ui.inspecting;
+    egui::Window::new(t!("inspect_preset_title", title = preset.title))
+        .id(egui::Id::new(("preset_snapshot", msg_id)))
+        .open(&mut open)
+        .default_width(420.0)
+        .show(ctx, |ui| {
+            egui::ScrollArea::vertical().max_height(500.0).show(ui, |ui| {
+                ui.add(egui::TextEdit::multiline(&mut msg_ui.preset_buffer)
+                    .code_editor()
+                    .interactive(!readonly)
+                    .desired_width(f32::INFINITY));
+            });
+            let parsed = serde_json::from_str::<Preset>(&msg_ui.preset_buffer);
+            if let Err(e) = &parsed {
+                ui.colored_label(ui.visuals().error_fg_color,
+                    t!("invalid_preset_json", error = e));
+            }
+            ui.horizontal(|ui| {
+                if ui.button("📋").on_hover_text(t!("copy_msg_tooltip")).clicked() {
+                    ui.ctx().copy_text(msg_ui.preset_buffer.clone());
+                }
+                if readonly {
+                    return;
+                }
+                if ui.add_enabled(parsed.is_ok(),
+                        egui::Button::new(t!("save_msg_edit_btn"))).clicked() {
+                    msg_ui.action = Some(MsgAction::SavePreset);
+                }
+                if ui.button(t!("revert_btn")).clicked() {
+                    msg_ui.preset_buffer = preset_snapshot_json(preset);
+                }
+            });
+        });
+    msg_ui.inspecting = open;
+}
+
+// replaces the rendered content while a message is being edited
+fn render_msg_editor(ui: &mut egui::Ui, msg_ui: &mut ChatMsgUi) {
+    ui.add(
+        egui::TextEdit::multiline(&mut msg_ui.edit_buffer)
+            .desired_width(f32::INFINITY)
+            .desired_rows(4)
+    );
+    ui.horizontal(|ui| {
+        if ui.button(t!("save_msg_edit_btn")).clicked() {
+            msg_ui.action = Some(MsgAction::SaveEdit);
+        }
+        if ui.button(t!("cancel_btn")).clicked() {
+            msg_ui.editing = false;
+            msg_ui.edit_buffer.clear();
+        }
+    });
+}
+
+// e.g. "123→456 tok, $0.0012", cost is left out when unknown (Ollama)
+fn format_usage(msg: &ChatMsg) -> Option<String> {
+    let (prompt, completion) = (msg.prompt_tokens?, msg.completion_tokens?);
+    Some(match msg.cost {
+        Some(cost) => format!("{}→{} tok, ${:.4}", prompt, completion, cost),
+        None => format!("{}→{} tok", prompt, completion),
+    })
 }
 
 fn render_msg_content(
@@ -676,7 +1438,23 @@ fn render_msg_content(
     op_tx: &std::sync::mpsc::Sender<crate::common::FileOpMsg>, // <-- New
 ) {
     if msg_ui.show_raw {
-        ui.label(RichText::new(format!("{}", msg.content)).strong());
+        let raw_text: egui::WidgetText = match &msg_ui.find_term {
+            Some(term) => highlighted_text(ui, &msg.content, term, true).into(),
+            None => RichText::new(&msg.content).strong().into(),
+        };
+        if msg_ui.no_wrap {
+            egui::ScrollArea::horizontal()
+                .id_salt(("raw_msg", msg.id))
+                .show(ui, |ui| ui.add(egui::Label::new(raw_text).extend()));
+        } else {
+            ui.add(egui::Label::new(raw_text).wrap());
+        }
+    } else if !msg_ui.renders_markdown(msg) {
+        match &msg_ui.find_term {
+            Some(term) => ui.add(egui::Label::new(
+                highlighted_text(ui, &msg.content, term, false)).wrap()),
+            None => ui.add(egui::Label::new(&msg.content).wrap()),
+        };
     } else {
         // Break the content into pieces
         let chunks = parse_chunks(&msg.content);
@@ -695,6 +1473,8 @@ fn render_msg_content(
                     ui.push_id(format!("md_{}_{}", msg.id, i), |ui| {
                         CommonMarkViewer::new()
                             .max_image_width(Some(max_image_width))
+                            .syntax_theme_dark("base16-ocean.dark")
+                            .syntax_theme_light("InspiredGitHub")
                             .render_math_fn(Some(&mut move |ui, math, is_inline| {
                                 let mut cache_map = local_math_cache.borrow_mut();
                                 let svg_bytes = cache_map.entry(math.to_string()).or_insert_with(|| {
@@ -746,7 +1526,7 @@ fn render_msg_content(
                     });
                 }
 
-                ContentChunk::RustCode { code, filepath } => {
+                ContentChunk::Code { lang, code, filepath } => {
                     let mut code_buffer = code.to_string();
                     let num_lines = code_buffer.lines().count().max(1);
 
@@ -773,12 +1553,12 @@ fn render_msg_content(
                             ui.spacing_mut().item_spacing.x = 6.0;
 
                             let mut btn_text = format!("📄 {}", display_path);
-                            let mut tooltip = "Open file in editor".to_string();
+                            let mut tooltip = t!("open_in_editor_tooltip").to_string();
 
                             if autocorrected {
                                 btn_text.push_str(" ⚠️");
                                 let rel_path = path.strip_prefix(project_root.as_ref().unwrap()).unwrap_or(&path).display();
-                                tooltip = format!("File path autocorrected to:\n{}", rel_path);
+                                tooltip = t!("path_autocorrected_tooltip", path = rel_path).to_string();
                             }
 
                             let (open_main, open_arrow) = SplitButton::new(btn_text)
@@ -871,7 +1651,7 @@ fn render_msg_content(
 
                         // Right side: Copy Button
                         ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
-                            if ui.button("🗐").on_hover_text("Copy to clipboard").clicked() {
+                            if ui.button("🗐").on_hover_text(t!("copy_code_tooltip")).clicked() {
                                 ui.ctx().copy_text(code.to_string());
                             }
                         });
@@ -879,8 +1659,12 @@ fn render_msg_content(
 
                     CodeEditor::default()
                         .id_source(format!("code_block_{}_{}", msg.id, i))
-                        .with_theme(ColorTheme::SV)
-                        .with_syntax(Syntax::rust())
+                        .with_theme(if ui.visuals().dark_mode {
+                            ColorTheme::SV
+                        } else {
+                            ColorTheme::GITHUB_LIGHT
+                        })
+                        .with_syntax(fence_syntax(ui.ctx(), lang))
                         .with_numlines(false)
                         .with_rows(num_lines)
                         // Disable internal scroll so the parent chat window handles scrolling natively
@@ -894,27 +1678,52 @@ fn render_msg_content(
     }
 }
 
+// tallest the thought process gets before it scrolls
+const REASONING_MAX_HEIGHT: f32 = 300.0;
+
+// the open state lives in ChatMsgUi so that it can be set for all messages
 fn render_reasoning_block(ui: &mut egui::Ui, text: &str,
-        id_salt: impl std::hash::Hash) {
-    egui::CollapsingHeader::new(
+        id_salt: impl std::hash::Hash + Copy, open: &mut bool, streaming: bool) {
+    let header = egui::CollapsingHeader::new(
         egui::RichText::new(t!("thought_process")).italics().weak()
     )
     .id_salt(id_salt)
-    .default_open(true)
+    .open(Some(*open))
     .show(ui, |ui| {
-        egui::Frame::new()
-            .inner_margin(8.0)
+        // follows the newest thoughts while streaming, once done the
+        // scroll position is left where the user put it
+        egui::ScrollArea::vertical()
+            .id_salt(("reasoning_scroll", id_salt))
+            .max_height(REASONING_MAX_HEIGHT)
+            .auto_shrink([false, true])
+            .stick_to_bottom(streaming)
             .show(ui, |ui| {
-                ui.label(
-                    egui::RichText::new(text)
-                        .italics()
-                        .color(ui.visuals().weak_text_color())
-                );
+                egui::Frame::new()
+                    .inner_margin(8.0)
+                    .show(ui, |ui| {
+                        ui.label(
+                            egui::RichText::new(text)
+                                .italics()
+                                .color(ui.visuals().weak_text_color())
+                        );
+                    });
             });
     });
+    if header.header_response.clicked() {
+        *open = !*open;
+    }
     ui.add_space(10.0);
 }
 
+// "Thinking..." with the dots counting up while no answer has arrived
+fn render_thinking_label(ui: &mut egui::Ui) {
+    let dots = (ui.input(|i| i.time) * 3.0) as usize % 4;
+    ui.label(egui::RichText::new(format!("{}{}", t!("thinking_label"), ".".repeat(dots)))
+        .italics()
+        .color(ui.visuals().weak_text_color()));
+    ui.ctx().request_repaint_after(std::time::Duration::from_millis(300));
+}
+
 // when LLM sends only one function, we want to pre-merge it with the target
 // file before sending it to the GUI merge tool
 fn find_function_spans(code: &str, fn_name: &str) -> Vec<(usize, usize)> {
@@ -1,10 +1,13 @@
+use std::sync::{Arc, Mutex};
+
 use eframe::egui::{self, RichText, TextEdit, Vec2b};
 use keyring::Entry;
 use rust_i18n::t;
 use secrecy::ExposeSecret;
 use secrecy::zeroize::Zeroize;
 
-use crate::common::{ApiKey, KEYRING_INFO};
+use crate::common::{ApiKey, KEYRING_INFO, KeyCheck};
+use crate::openr::{is_key_rejected, openr_check_key};
 use super::State;
 
 pub fn ui_key_manager(ctx: &egui::Context, state: &mut State) {
@@ -56,7 +59,11 @@ pub fn ui_key_manager(ctx: &egui::Context, state: &mut State) {
                     .desired_width(15.0)
                     .horizontal_align(egui::Align::Center)
             );
-            response.request_focus();
+            // grab the focus when the window opens, but leave the other
+            // fields usable
+            if ui.memory(|m| m.focused().is_none()) {
+                response.request_focus();
+            }
 
             ui.add_space(10.0);
 
@@ -72,6 +79,8 @@ pub fn ui_key_manager(ctx: &egui::Context, state: &mut State) {
                 };
                 state.api_key_entered.zeroize();
                 state.openrouter_api_key = new_key;
+                start_key_check(ctx, state.key_check.clone(),
+                    &state.openrouter_api_key, &state.perma.rt);
             }
 
             ui.add_space(10.0);
@@ -97,8 +106,151 @@ pub fn ui_key_manager(ctx: &egui::Context, state: &mut State) {
                     }
                 }
                 state.openrouter_api_key = new_key;
+                start_key_check(ctx, state.key_check.clone(),
+                    &state.openrouter_api_key, &state.perma.rt);
             }
+
+            render_key_check(ui, &state.key_check,
+                &mut state.openrouter_api_key);
         });
 
+        ui.separator();
+        render_named_keys(ui, &mut state.named_keys, &mut state.key_name_entered,
+            &mut state.named_key_entered, &state.perma.api_key_names);
+    });
+}
+
+/// The keyring entry of a named OpenRouter key, next to the default one
+pub fn named_key_entry(name: &str) -> keyring::Result<Entry> {
+    Entry::new(KEYRING_INFO[0], &format!("{}:{}", KEYRING_INFO[1], name))
+}
+
+// extra keys, e.g. work and personal accounts, that presets pick by name
+fn render_named_keys(ui: &mut egui::Ui,
+        named_keys: &mut std::collections::BTreeMap<String, ApiKey>,
+        key_name_entered: &mut String, named_key_entered: &mut String,
+        key_names: &Mutex<Vec<String>>) {
+    ui.label(RichText::new(t!("named_keys_heading")).strong());
+    ui.label(t!("named_keys_instruction"));
+    ui.add_space(10.0);
+
+    let mut deleted = None;
+    egui::Grid::new("named_keys_grid").striped(true).show(ui, |ui| {
+        for (name, key) in &*named_keys {
+            ui.label(name);
+            if key.is_set {
+                ui.label(RichText::new(t!("key_active"))
+                    .color(ui.visuals().strong_text_color()));
+            } else {
+                ui.colored_label(ui.visuals().error_fg_color,
+                    t!("named_key_missing"));
+            }
+            if ui.button("🗑").on_hover_text(t!("delete_named_key_tooltip"))
+                    .clicked() {
+                deleted = Some(name.clone());
+            }
+            ui.end_row();
+        }
+    });
+    if let Some(name) = deleted {
+        if let Err(error) = named_key_entry(&name)
+                .and_then(|entry| entry.delete_credential()) {
+            println!("{}: {}", t!("error_deleting_key_from_keyring"), error);
+        }
+        named_keys.remove(&name);
+        key_names.lock().unwrap().retain(|n| *n != name);
+    }
+
+    ui.add_space(10.0);
+    ui.horizontal(|ui| {
+        ui.add(TextEdit::singleline(key_name_entered)
+            .hint_text(t!("named_key_name_hint"))
+            .desired_width(120.0));
+        ui.add(TextEdit::singleline(named_key_entered)
+            .hint_text(t!("named_key_hint"))
+            .password(true)
+            .desired_width(200.0));
+        let name = key_name_entered.trim().to_string();
+        let enabled = !name.is_empty() && named_key_entered.len() > 3;
+        if ui.add_enabled(enabled, egui::Button::new(t!("save_named_key_btn")))
+                .clicked() {
+            let new_key = ApiKey {
+                key: named_key_entered.clone().into(),
+                is_set: true,
+            };
+            named_key_entered.zeroize();
+            match named_key_entry(&name)
+                    .and_then(|entry| entry.set_password(new_key.key.expose_secret())) {
+                Ok(()) => println!("{}", t!("key_saved_to_keyring")),
+                Err(error) => println!("{}: {}",
+                    t!("error_saving_key_to_keyring"), error),
+            }
+            let mut names = key_names.lock().unwrap();
+            if !names.contains(&name) {
+                names.push(name.clone());
+            }
+            // usable for this session even if the keyring refused it
+            named_keys.insert(name, new_key);
+            key_name_entered.clear();
+        }
+    });
+}
+
+// asks OpenRouter whether the current key works, the answer lands in
+// state.key_check
+fn start_key_check(ctx: &egui::Context, key_check: Arc<Mutex<KeyCheck>>,
+        api_key: &ApiKey, rt: &tokio::runtime::Handle) {
+    *key_check.lock().unwrap() = KeyCheck::Checking;
+    let api_key = api_key.clone();
+    let ctx = ctx.clone();
+    rt.spawn(async move {
+        let result = match openr_check_key(&api_key).await {
+            Ok(balance) => KeyCheck::Valid(balance),
+            Err(error) if is_key_rejected(&error) =>
+                KeyCheck::Rejected(error.to_string()),
+            Err(error) => KeyCheck::Unreachable(error.to_string()),
+        };
+        *key_check.lock().unwrap() = result;
+        ctx.request_repaint();
     });
 }
+
+fn render_key_check(ui: &mut egui::Ui, key_check: &Mutex<KeyCheck>,
+        api_key: &mut ApiKey) {
+    let key_check = {
+        let mut key_check = key_check.lock().unwrap();
+        // a rejected key must not be used for requests, it is disabled once
+        // when the answer arrives
+        if let KeyCheck::Rejected(error) = &*key_check {
+            api_key.is_set = false;
+            *key_check = KeyCheck::Invalid(error.clone());
+        }
+        key_check.clone()
+    };
+    ui.add_space(10.0);
+    match key_check {
+        KeyCheck::Idle => {}
+        KeyCheck::Checking => {
+            ui.horizontal(|ui| {
+                ui.spinner();
+                ui.label(t!("key_checking"));
+            });
+        }
+        KeyCheck::Valid(balance) => {
+            let text = match balance {
+                Some(balance) => format!("{} {}: ${:.2}", t!("key_valid"),
+                    t!("key_balance"), balance),
+                None => t!("key_valid").to_string(),
+            };
+            ui.label(RichText::new(text).color(ui.visuals().strong_text_color()));
+        }
+        KeyCheck::Rejected(error) | KeyCheck::Invalid(error) => {
+            ui.label(RichText::new(format!("{}: {}", t!("key_invalid"), error))
+                .color(ui.visuals().error_fg_color));
+        }
+        KeyCheck::Unreachable(error) => {
+            ui.label(RichText::new(t!("key_unreachable", error = error))
+                .color(ui.visuals().warn_fg_color));
+        }
+    }
+}
@@ -4,19 +4,23 @@ use std::path::PathBuf;
 use std::sync::atomic::AtomicBool;
 use std::sync::{Arc, Mutex};
 use std::sync::mpsc::{Receiver, Sender, channel};
+use std::time::Instant;
 use dotenv::dotenv;
 use eframe::egui::{self};
 use egui_commonmark::CommonMarkCache;
 use keyring::Entry;
 use rusqlite::Connection;
+use rust_i18n::t;
 use tokio::runtime::Handle;
 use crate::common::{self, ApiKey, ChatMsgUi, ChatStreamEvent, DbChat, FileOp, FileOpMsg, KEYRING_INFO, OllamaDownloading, Presets, THEME_COLORS, load_presets};
-use crate::db::{fetch_chat, fetch_chat_titles, get_sandbox_db_conn, is_table_empty, mod_msg_content_reasoning};
-use crate::db::cache::{get_cache_db_conn, get_ollama_model_installed, get_ollama_model_names, get_openr_model_names, populate_ollama_installed, populate_openr_model};
+use crate::db::{fetch_chat, fetch_chat_titles, get_sandbox_db_conn, is_table_empty, mod_msg_content_reasoning, mod_msg_elapsed, mod_msg_error, mod_msg_interrupted, mod_msg_truncated, mod_msg_usage, reset_sandbox_db, backup_sandbox};
+use crate::db::cache::{get_cache_db_conn, get_models_last_updated, get_ollama_model_installed, get_ollama_model_names, get_openr_model_info, get_openr_model_names, populate_ollama_installed, populate_openr_model};
 use crate::gui::agent_config::{AgentConfigState, ui_agent_config};
 use crate::gui::bottom_panel::{BottomPanelState, ui_bottom_panel};
+pub use crate::gui::bottom_panel::BottomPanelLayout;
 use crate::gui::chat::ui_chat;
 use crate::gui::key_manager::ui_key_manager;
+use crate::gui::settings::ui_settings;
 use crate::gui::preset_editor::{PresetEditorState, ui_preset_editor};
 use crate::gui::right_panel::ui_right_panel;
 use crate::gui::side_panel::ui_side_panel;
@@ -30,6 +34,7 @@ mod side_panel;
 mod right_panel;
 mod preset_editor;
 mod key_manager;
+mod settings;
 mod bottom_panel;
 mod chat;
 mod agent_config;
@@ -39,11 +44,32 @@ mod panes;
 pub mod split_button;
 pub use split_button::SplitButton;
 
+pub const DEFAULT_MODELS_STALE_DAYS: i64 = 30;
+// narrowest and widest a reply column of the assistant grid gets
+pub const DEFAULT_COLUMN_WIDTHS: (f32, f32) = (400.0, 900.0);
+pub const COLUMN_WIDTH_RANGE: std::ops::RangeInclusive<f32> = 200.0..=2000.0;
+// files dropped onto the window are cut to this many characters
+pub const MAX_DROPPED_CHARS: usize = 200_000;
+
 pub struct MyAppPermanent {
     pub rt: Handle,
     pub sandbox: Option<PathBuf>,
     pub pending_project_init: Mutex<Option<PathBuf>>,
     pub app_language: Mutex<String>,
+    // cached model lists older than this are flagged in the preset editor
+    pub models_stale_days: Mutex<i64>,
+    pub theme_preference: Mutex<egui::ThemePreference>,
+    pub auto_title: Mutex<bool>, // let the model title new chats
+    pub expand_reasoning: Mutex<bool>, // thought process starts unfolded
+    pub column_widths: Mutex<(f32, f32)>, // (min, max) of the reply columns
+    pub compact_chat: Mutex<bool>, // less space around the messages
+    pub stack_agents: Mutex<bool>, // replies one under another
+    pub prompt_char_limit: Mutex<usize>, // Send warns above it, 0 is off
+    pub api_key_names: Mutex<Vec<String>>, // the named keys in the keyring
+    pub abort_all_on_error: Mutex<bool>, // one failing agent stops the rest
+    pub bottom_panel_layout: Mutex<BottomPanelLayout>,
+    // nothing is sent, edited or written to the Sandbox (--readonly)
+    pub readonly: Mutex<bool>,
 }
 
 pub struct ChatStreamingState {
@@ -52,7 +78,16 @@ pub struct ChatStreamingState {
     pub msg_ids: Vec<i64>,
     pub content_buffers: Vec<String>, // used when chat streaming
     pub reasoning_buffers: Vec<String>,
-    pub abort_flag: Option<Arc<AtomicBool>>,
+    pub usage_buffers: Vec<Option<common::MsgUsage>>,
+    pub start_times: Vec<Option<Instant>>, // when each agent started streaming
+    pub abort_flags: Vec<Option<Arc<AtomicBool>>>, // per agent, to stop one
+    // stored replies being regenerated, they come back if the new one fails
+    pub replaced_msgs: Vec<Option<common::ChatMsg>>,
+    pub failed: u128, // each bit flags an agent whose stream ended in an error
+    // each bit flags an agent stopped by another one's error, whatever its
+    // stream still sends is ignored until its Finished
+    pub abandoned: u128,
+    pub chat_id: i64, // the chat the replies are streamed into
     pub rx: Receiver<ChatStreamEvent>,
     pub tx: Sender<ChatStreamEvent>,
  }
@@ -75,21 +110,37 @@ pub struct State {
     dragging_chat: Option<i64>,
     chat_msg_ui: HashMap<i64, ChatMsgUi>,
     chat_to_rename: Option<i64>,
+    chat_details: Option<(String, crate::common::ChatStats)>, // title, stats
     chat_rename_buffer: String,
     common_mark_cache: CommonMarkCache,
     presets: Presets,
     cache_conn: Option<rusqlite::Connection>, // connection to cache db
+    cache_in_memory: bool, // cache file unusable, cache_conn is the bundled copy in memory
+    models_last_updated: Option<chrono::DateTime<chrono::Utc>>,
     db_conn: rusqlite::Connection, // connection to main db
     db_chats: Vec<DbChat>, // chat titles fetched from the main db
+    trash_chats: Vec<DbChat>, // deleted chats that can still be restored
     show_key_manager: bool,
+    missing_key_prompt: bool, // an OpenRouter agent was about to run keyless
+    show_settings: bool,
+    key_check: Arc<Mutex<common::KeyCheck>>,
     show_preset_editor: bool,
     api_key_entered: String,
+    ollama_host_entered: String,
+    ollama_port_entered: String,
     openrouter_api_key: ApiKey,
     keyring_used: bool,
+    // extra OpenRouter keys presets can bill to, by name
+    named_keys: std::collections::BTreeMap<String, ApiKey>,
+    key_name_entered: String,
+    named_key_entered: String,
     preset_editor_state: PresetEditorState,
     openr_model_names: Vec<String>,
     ollama_model_names: Vec<String>,
     ollama_model_names_installed: Vec<String>,
+    // installed Ollama models fetched in the background, or the error to show
+    ollama_models_tx: Sender<Result<Vec<common::DbOllamaModel>, String>>,
+    ollama_models_rx: Receiver<Result<Vec<common::DbOllamaModel>, String>>,
     op_tx: Sender<FileOpMsg>,
     pending_file_dialog_op: Option<FileOp>,
     pending_export_content: Option<String>,
@@ -100,6 +151,18 @@ pub struct State {
     chat_streaming_state: ChatStreamingState,
     // error modal's content:
     error_msg: Option<String>,
+    // info modal's content, e.g. the outcome of a bulk export
+    info_msg: Option<String>,
+    // (chats, presets) shown while asking to confirm Sandbox → Clear
+    confirm_clear: Option<(i64, i64)>,
+    // (model, bytes on disk) shown while asking to confirm deleting a model
+    confirm_delete_model: Option<(String, i64)>,
+    // an instruction being written, to go in front of the whole active chat
+    add_system_msg: Option<String>,
+    // kept for a few seconds so that the deletion can be undone
+    deleted_msg: Option<chat::DeletedMsg>,
+    find_bar: chat::FindBar,
+    last_backup: Option<PathBuf>,
     is_modal_open: bool,
     bottom_panel_state: BottomPanelState,
     agent_config_state: AgentConfigState,
@@ -137,7 +200,9 @@ impl State {
         let mut presets = Presets::default();
 
         // 1. Establish Connection or Die
-        let (conn, sandbox) = match get_sandbox_db_conn(&updated_sandbox) {
+        let readonly = *permanent.readonly.lock().unwrap();
+        let (conn, sandbox) = match get_sandbox_db_conn(&updated_sandbox,
+                readonly) {
             Ok(tuple) => tuple,
             Err(error) => {
                 eprintln!("Error opening the default Sandbox: {}", error);
@@ -145,6 +210,16 @@ impl State {
             }
         };
 
+        // keep a copy of the sandbox as it was before this session, a
+        // read-only session leaves it as it is
+        let last_backup = if readonly {
+            None
+        } else {
+            backup_sandbox(&conn, &sandbox)
+                .inspect_err(|e| eprintln!("Could not back up the Sandbox: {}", e))
+                .ok()
+        };
+
         let mut project_root = None;
         if let Some(parent) = sandbox.parent() {
             if parent.file_name().and_then(|n| n.to_str()) == Some(".inforno") {
@@ -156,11 +231,16 @@ impl State {
 
         // 2. Load Initial Data (using the valid 'conn')
         load_presets(&conn, &mut presets);
+        common::DEFAULT_PRESET_ID.store(
+            crate::db::get_default_preset_id(&conn).unwrap_or(0),
+            std::sync::atomic::Ordering::Relaxed);
 
         chats = fetch_chat_titles(&conn).unwrap_or_else(|e| {
             eprintln!("CRITICAL: Could not fetch chat titles: {}", e);
             std::process::exit(1);
         });
+        let mut trash_chats = vec![];
+        reload_trash_chats(&conn, &mut trash_chats);
 
         let mut open_chats: HashMap<i64, common::Chat> = HashMap::new();
         let mut active_chat_id: Option<i64> = None;
@@ -201,12 +281,31 @@ impl State {
             }
         }
 
+        // named keys have a keyring entry each, their names are kept
+        // with the settings since the keyring can not list them
+        let named_keys = permanent.api_key_names.lock().unwrap().iter()
+            .map(|name| {
+                let key = key_manager::named_key_entry(name)
+                    .and_then(|entry| entry.get_password())
+                    .map(|key| ApiKey { key: key.into(), is_set: true })
+                    .unwrap_or_else(|error| {
+                        println!("Failed to retrieve key '{}': {}", name, error);
+                        ApiKey::default()
+                    });
+                (name.clone(), key)
+            })
+            .collect();
+
         // --- 3. Cache Database and Async Model Fetching ---
+        let ollama_host = common::OLLAMA_HOST.read().unwrap().clone();
         let mut cache_conn: Option<rusqlite::Connection> = None;
+        let mut cache_in_memory = false;
+        let mut models_last_updated = None;
 
         match get_cache_db_conn() {
-            Ok(mut value) => {
+            Ok((mut value, in_memory)) => {
                 println!("Cache Local Database connection established");
+                cache_in_memory = in_memory;
 
                 permanent.rt.block_on(async {
                     // Task A: Fetch OpenRouter Models if table is empty
@@ -224,35 +323,32 @@ impl State {
                     }
 
                     // Task B: Fetch Ollama Installed Models
-                    if let Ok(ollama_models) = ollama_fetch_models().await {
-                        println!("Fetched {} Ollama models", ollama_models.len());
-                        match populate_ollama_installed(&mut value, &ollama_models) {
-                            Ok(_) => println!("... success!"),
-                            Err(error) => println!("Error: {}", error),
+                    match ollama_fetch_models().await {
+                        Ok(ollama_models) => {
+                            println!("Fetched {} Ollama models", ollama_models.len());
+                            match populate_ollama_installed(&mut value, &ollama_models) {
+                                Ok(_) => println!("... success!"),
+                                Err(error) => println!("Error: {}", error),
+                            }
+                            ollama_model_names_installed = ollama_models.into_iter()
+                                .map(|m| m.name).collect();
                         }
-                        ollama_model_names_installed = ollama_models.into_iter()
-                            .map(|m| m.name).collect();
-                    } else {
-                        // Fallback to cache if live fetch fails
-                        if let Ok(names) = get_ollama_model_installed(&mut value) {
-                            ollama_model_names_installed = names;
+                        Err(error) => {
+                            eprintln!("Could not fetch Ollama models from {}:{}: {}",
+                                ollama_host.host, ollama_host.port, error);
+                            // Fallback to cache if live fetch fails
+                            if let Ok(names) = get_ollama_model_installed(&mut value) {
+                                ollama_model_names_installed = names;
+                            }
                         }
                     }
                 });
 
                 // Task C: Retrieve names from Cache DB (Sync operations)
-                if let Ok(names) = get_openr_model_names(&mut value) {
-                    openr_model_names = names;
-                }
-
-                if let Ok(names) = get_ollama_model_names(&mut value) {
-                    ollama_model_names = names;
-                    // Merge installed models into available models list
-                    ollama_model_names.extend(ollama_model_names_installed.iter().cloned());
-                    ollama_model_names.sort();
-                    ollama_model_names.dedup();
-                }
+                (openr_model_names, ollama_model_names) = read_cached_model_names(
+                    &mut value, &ollama_model_names_installed);
 
+                models_last_updated = get_models_last_updated(&value);
                 cache_conn = Some(value);
             },
             Err(error) => {
@@ -262,9 +358,11 @@ impl State {
 
         // create the communication channel for streaming chat messages
         let (chat_tx, chat_rx) = channel();
+        let (ollama_models_tx, ollama_models_rx) = channel();
 
         // Grab the init request from permanent state
         let pending_init = permanent.pending_project_init.lock().unwrap().take();
+        let bottom_panel_layout = permanent.bottom_panel_layout.lock().unwrap().clone();
         let show_project_init = pending_init.is_some();
 
         // attempt to load the layout from the .ron file
@@ -309,17 +407,29 @@ impl State {
             dragging_chat: None,
             chat_msg_ui: HashMap::new(),
             chat_to_rename: None,
+            chat_details: None,
             chat_rename_buffer: String::new(),
             common_mark_cache: CommonMarkCache::default(),
             presets,
             cache_conn,
+            cache_in_memory,
+            models_last_updated,
             db_conn: conn,
             db_chats: chats,
+            trash_chats,
             show_key_manager: false,
+            missing_key_prompt: false,
+            show_settings: false,
+            key_check: Arc::new(Mutex::new(common::KeyCheck::default())),
             show_preset_editor: false,
             api_key_entered: String::new(),
+            ollama_host_entered: ollama_host.host.clone(),
+            ollama_port_entered: ollama_host.port.to_string(),
             openrouter_api_key: api_key,
             keyring_used: is_keyring_used,
+            named_keys,
+            key_name_entered: String::new(),
+            named_key_entered: String::new(),
             preset_editor_state: PresetEditorState {
                 ollama_downloading: Arc::new(
                 Mutex::new(OllamaDownloading::default())),
@@ -328,6 +438,8 @@ impl State {
             openr_model_names,
             ollama_model_names,
             ollama_model_names_installed,
+            ollama_models_tx,
+            ollama_models_rx,
             op_tx,
             pending_file_dialog_op: None,
             pending_export_content: None,
@@ -339,13 +451,27 @@ impl State {
                 msg_ids: vec![],
                 content_buffers: vec![],
                 reasoning_buffers: vec![],
-                abort_flag: None,
+                usage_buffers: vec![],
+                start_times: vec![],
+                abort_flags: vec![],
+                replaced_msgs: vec![],
+                failed: 0,
+                abandoned: 0,
+                chat_id: 0,
                 rx: chat_rx,
                 tx: chat_tx,
             },
-            error_msg: None, // if there is an error, modal will auto open
+            // if there is an error, modal will auto open
+            error_msg: ollama_host.url().err().map(|e| e.to_string()),
+            info_msg: None,
+            deleted_msg: None,
+            find_bar: chat::FindBar::default(),
+            confirm_clear: None,
+            confirm_delete_model: None,
+            add_system_msg: None,
+            last_backup,
             is_modal_open: false, // if file dialog is open this needs to be true
-            bottom_panel_state: BottomPanelState::default(),
+            bottom_panel_state: BottomPanelState::from_layout(&bottom_panel_layout),
             agent_config_state: AgentConfigState::default(),
             math_cache: std::rc::Rc::new(std::cell::RefCell::new(std::collections::HashMap::new())),
             show_project_init_modal: show_project_init,
@@ -363,7 +489,21 @@ impl State {
     /// Useful if you want to refresh DB connections or reload API keys
     /// without restarting the application.
     pub fn reload(&mut self, sandbox: Option<PathBuf>) {
+        // a model pull keeps running across sandboxes, so does its progress
+        let downloading = self.preset_editor_state.ollama_downloading.clone();
+        *self.perma.bottom_panel_layout.lock().unwrap() =
+            self.bottom_panel_state.layout();
         *self = Self::new(self.perma.clone(), sandbox, self.op_tx.clone());
+        self.preset_editor_state.ollama_downloading = downloading;
+    }
+
+    /// The OpenRouter key a preset bills to: its named key if it has one,
+    /// otherwise the default key
+    pub fn openrouter_key(&self, key_name: Option<&str>) -> ApiKey {
+        match key_name {
+            Some(name) => self.named_keys.get(name).cloned().unwrap_or_default(),
+            None => self.openrouter_api_key.clone(),
+        }
     }
 }
 
@@ -390,9 +530,103 @@ impl MyApp {
     }
 }
 
+/// Every locale in the locales folder as (code, its own name for itself),
+/// sorted by code
+pub fn app_languages() -> Vec<(String, String)> {
+    let mut locales = rust_i18n::available_locales!();
+    locales.sort();
+    locales.into_iter().map(|locale| {
+        let name = t!("language_name", locale = locale);
+        // a locale without the key gets its code
+        let name = if name.ends_with("language_name") {
+            locale.to_string()
+        } else {
+            name.to_string()
+        };
+        (locale.to_string(), name)
+    }).collect()
+}
+
+/// Switches the interface language right away, it is saved on exit
+pub fn set_app_language(perma: &MyAppPermanent, locale: &str) {
+    rust_i18n::set_locale(locale);
+    *perma.app_language.lock().unwrap() = locale.to_string();
+}
+
+// Ctrl+N opens a new chat, Ctrl+PageUp/PageDown step through the saved chats
+// in side panel order. Text fields and modal windows keep their keys, except
+// for Ctrl+F (find in chat), which text fields have no use for.
+fn handle_shortcuts(ctx: &egui::Context, state: &mut State) {
+    if state.is_modal_open {
+        return;
+    }
+    if ctx.input_mut(|i| i.consume_key(egui::Modifiers::COMMAND, egui::Key::F)) {
+        state.find_bar.open = true;
+        state.find_bar.focus = true;
+    }
+    if ctx.wants_keyboard_input() {
+        return;
+    }
+    let (new_chat, prev_chat, next_chat) = ctx.input_mut(|i| (
+        i.consume_key(egui::Modifiers::COMMAND, egui::Key::N),
+        i.consume_key(egui::Modifiers::COMMAND, egui::Key::PageUp),
+        i.consume_key(egui::Modifiers::COMMAND, egui::Key::PageDown),
+    ));
+
+    if new_chat {
+        side_panel::start_new_chat(state);
+    } else if (prev_chat || next_chat) && !state.db_chats.is_empty() {
+        let order: Vec<i64> = side_panel::chat_tree_order(&state.db_chats)
+            .into_iter().map(|(ind, _)| state.db_chats[ind].id).collect();
+        let len = order.len();
+        let pos = state.active_chat_id
+            .and_then(|id| order.iter().position(|&c| c == id));
+        let target = match (pos, next_chat) {
+            (Some(pos), true) => (pos + 1) % len,
+            (Some(pos), false) => (pos + len - 1) % len,
+            (None, true) => 0,
+            (None, false) => len - 1,
+        };
+        side_panel::open_saved_chat(state, order[target]);
+    }
+}
+
 impl eframe::App for MyApp {
     fn save(&mut self, storage: &mut dyn eframe::Storage) {
         eframe::set_value(storage, "app_language", &self.perma.app_language);
+        eframe::set_value(storage, "models_stale_days",
+            &*self.perma.models_stale_days.lock().unwrap());
+        eframe::set_value(storage, "auto_title",
+            &*self.perma.auto_title.lock().unwrap());
+        eframe::set_value(storage, "expand_reasoning",
+            &*self.perma.expand_reasoning.lock().unwrap());
+        eframe::set_value(storage, "column_widths",
+            &*self.perma.column_widths.lock().unwrap());
+        eframe::set_value(storage, "compact_chat",
+            &*self.perma.compact_chat.lock().unwrap());
+        eframe::set_value(storage, "stack_agents",
+            &*self.perma.stack_agents.lock().unwrap());
+        eframe::set_value(storage, "prompt_char_limit",
+            &*self.perma.prompt_char_limit.lock().unwrap());
+        eframe::set_value(storage, "api_key_names",
+            &*self.perma.api_key_names.lock().unwrap());
+        eframe::set_value(storage, "abort_all_on_error",
+            &*self.perma.abort_all_on_error.lock().unwrap());
+        eframe::set_value(storage, "bottom_panel_layout",
+            &self.state.bottom_panel_state.layout());
+        eframe::set_value(storage, "theme_preference",
+            &*self.perma.theme_preference.lock().unwrap());
+        eframe::set_value(storage, "ollama_host",
+            &*common::OLLAMA_HOST.read().unwrap());
+    }
+
+    // Cancel in-flight streams so their connections get closed; the runtime
+    // is given a moment to wind them down once the window is gone (main.rs).
+    // A dangling Ollama request would otherwise keep the model loaded.
+    fn on_exit(&mut self, _gl: Option<&eframe::glow::Context>) {
+        for flag in self.state.chat_streaming_state.abort_flags.iter().flatten() {
+            flag.store(true, std::sync::atomic::Ordering::Relaxed);
+        }
     }
 
     #[tracing::instrument(skip_all, name = "MyApp::ui")]
@@ -407,6 +641,9 @@ impl eframe::App for MyApp {
             colors.strong = visuals.strong_text_color();
             colors.err = visuals.error_fg_color;
         }
+        // remember the theme however it was changed, for saving
+        *self.perma.theme_preference.lock().unwrap() =
+            ctx.options(|o| o.theme_preference);
 
         let state = &mut self.state;
 
@@ -415,6 +652,8 @@ impl eframe::App for MyApp {
         state.tile_labels = labels;
         state.chat_locations = locs;
 
+        load_dropped_files(ctx, state);
+
         if state.error_msg.is_some() {
             state.is_modal_open = true;
         }
@@ -527,16 +766,103 @@ impl eframe::App for MyApp {
                         }
                     }
                 }
-                FileOp::ExportChat => {
+                FileOp::ExportChat | FileOp::ExportPreset | FileOp::ExportSandbox => {
                     // The actual file writing is handled immediately when the file
                     // is picked via state.file_dialog.take_picked().
                     // This arm simply satisfies Rust's exhaustive match requirement.
                 }
+                FileOp::ImportSandbox => {
+                    let path = file_op_msg.path
+                        .filter(|_| !file_op_msg.cancelled);
+                    if let Some(path) = path {
+                        let result = std::fs::read_to_string(&path)
+                            .map_err(|e| e.to_string())
+                            .and_then(|json| crate::db::import_sandbox_json(
+                                &state.db_conn, &json)
+                                .map_err(|e| e.to_string()));
+                        match result {
+                            Ok(_) => {
+                                load_presets(&state.db_conn, &mut state.presets);
+                                reload_db_chats(&state.db_conn,
+                                    &mut state.db_chats);
+                                reload_trash_chats(&state.db_conn,
+                                    &mut state.trash_chats);
+                            }
+                            Err(e) => {
+                                state.error_msg = Some(format!(
+                                    "{}: {}", rust_i18n::t!("error_importing_sandbox"), e));
+                                state.is_modal_open = true;
+                            }
+                        }
+                    }
+                }
+                FileOp::ExportAllChats => {
+                    let path = file_op_msg.path
+                        .filter(|_| !file_op_msg.cancelled);
+                    if let Some(path) = path {
+                        match crate::db::export_all_chats_markdown(
+                                &state.db_conn, &path, &state.presets) {
+                            Ok(count) => {
+                                state.info_msg = Some(rust_i18n::t!(
+                                    "exported_all_chats", count = count,
+                                    path = path.display()).to_string());
+                            }
+                            Err(e) => {
+                                state.error_msg = Some(format!(
+                                    "{}: {}", rust_i18n::t!("error_exporting_chats"), e));
+                            }
+                        }
+                        state.is_modal_open = true;
+                    }
+                }
+                FileOp::ImportPreset => {
+                    let path = file_op_msg.path
+                        .filter(|_| !file_op_msg.cancelled);
+                    if let Some(path) = path {
+                        let result = std::fs::read_to_string(&path)
+                            .map_err(|e| e.to_string())
+                            .and_then(|json| crate::db::import_preset(
+                                &state.db_conn, &json)
+                                .map_err(|e| e.to_string()));
+                        match result {
+                            Ok(id) => {
+                                load_presets(&state.db_conn, &mut state.presets);
+                                state.preset_editor_state.selected_preset =
+                                    common::PresetSelection::from_id(id, &state.presets);
+                            }
+                            Err(e) => {
+                                state.error_msg = Some(format!(
+                                    "{}: {}", rust_i18n::t!("error_importing_preset"), e));
+                                state.is_modal_open = true;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        while let Ok(fetched) = state.ollama_models_rx.try_recv() {
+            apply_ollama_models(state, fetched);
+        }
+
+        // tick the elapsed time of the replies that are still coming
+        for ind in 0..state.chat_streaming_state.start_times.len() {
+            if state.chat_streaming_state.bitmask & (1 << ind as u128) != 0 {
+                update_elapsed(state, ind);
             }
         }
 
         while let Ok(event) = state.chat_streaming_state.rx.try_recv() {
-            let active_chat_id = state.active_chat_id.unwrap_or(0);
+            // the tab may have moved on since the replies were requested
+            let stream_chat_id = state.chat_streaming_state.chat_id;
+
+            if let Some(ind) = event.agent_ind() && state.chat_streaming_state
+                    .abandoned & (1 << ind as u128) != 0 {
+                if let ChatStreamEvent::Finished(..) = event {
+                    state.chat_streaming_state.abandoned &= !(1 << ind as u128);
+                }
+                continue;
+            }
 
             match event {
                 ChatStreamEvent::Content(ind, text) => {
@@ -545,7 +871,7 @@ impl eframe::App for MyApp {
                         buf.push_str(&text);
                         // update the message in the chat.msg_pool
                         let msg_id = state.chat_streaming_state.msg_ids[ind];
-                        if let Some(chat) = state.open_chats.get_mut(&active_chat_id) {
+                        if let Some(chat) = state.open_chats.get_mut(&stream_chat_id) {
                             if let Some(msg) = chat.msg_pool.get_mut(&msg_id) {
                                 msg.content = buf.clone();
                             }
@@ -558,73 +884,121 @@ impl eframe::App for MyApp {
                         buf.push_str(&text);
                         // update the message in the chat.msg_pool
                         let msg_id = state.chat_streaming_state.msg_ids[ind];
-                        if let Some(chat) = state.open_chats.get_mut(&active_chat_id) {
+                        if let Some(chat) = state.open_chats.get_mut(&stream_chat_id) {
                             if let Some(msg) = chat.msg_pool.get_mut(&msg_id) {
                                 msg.reasoning = Some(buf.clone());
                             }
                         }
                     }
                 }
-                ChatStreamEvent::Finished(ind) => {
+                ChatStreamEvent::Usage(ind, usage) => {
+                    if let Some(buf) = state.chat_streaming_state
+                                .usage_buffers.get_mut(ind) {
+                        *buf = Some(usage);
+                    }
+                }
+                ChatStreamEvent::Truncated(ind) => {
+                    let msg_id = state.chat_streaming_state.msg_ids[ind];
+                    if let Some(msg) = state.open_chats.get_mut(&stream_chat_id)
+                            .and_then(|chat| chat.msg_pool.get_mut(&msg_id)) {
+                        msg.truncated = true;
+                    }
+                    let _ = mod_msg_truncated(&state.db_conn, msg_id, true);
+                }
+                ChatStreamEvent::Finished(ind, interrupted) => {
                     // tur off the bit for this agent
                     state.chat_streaming_state.bitmask &= !(1 << ind as u128);
-                    // persist the result to db
-                    let content = state.chat_streaming_state
-                            .content_buffers[ind].clone();
-                    let reasoning = state.chat_streaming_state
-                            .reasoning_buffers[ind].clone();
-
-                    // save the message content and reasoning to the database
-                    let _ = mod_msg_content_reasoning(
-                            &state.db_conn,
-                            state.chat_streaming_state.msg_ids[ind],
-                            &content, &reasoning);
+                    finish_agent_reply(state, stream_chat_id, ind, interrupted);
 
                     // check if all agents are done
                     if state.chat_streaming_state.bitmask == 0 {
                         state.chat_streaming_state.streaming = false;
                         println!("Streaming finished");
+                        if *state.perma.auto_title.lock().unwrap() {
+                            bottom_panel::request_chat_title(state, ctx,
+                                stream_chat_id);
+                        }
+                    }
+                }
+                ChatStreamEvent::Title(chat_id, old_title, title) => {
+                    apply_generated_title(state, chat_id, &old_title, &title);
+                }
+                ChatStreamEvent::Warning(ind, warning) => {
+                    // e.g. a chunk that failed to parse, the stream goes on
+                    // and Finished still turns off the bit
+                    eprintln!("Agent {} Warning: {}", ind, warning);
+                    if let Some(&msg_id) = state.chat_streaming_state
+                            .msg_ids.get(ind) {
+                        state.chat_msg_ui.entry(msg_id).or_default()
+                            .warnings.push(warning);
                     }
                 }
                 ChatStreamEvent::Error(ind, err) => {
-                    // Check for minor serialization error first
-                    if err.starts_with("Serialization error") {
-                        eprintln!("Agent {} Ignored Error: {}", ind, err);
-                        // Do nothing else here - this keeps the agent "streaming"
-                        // and does not clear the bitmask.
-                    } else {
-                        // Handle actual fatal errors
-                        eprintln!("Agent {} Error: {}", ind, err);
-
-                        if let Some(buf) = state.chat_streaming_state
-                                    .content_buffers.get_mut(ind) {
-                            buf.push_str(&format!(
-                            "**Error! Is the Server Running? Details: {}**\n", err));
-                            // update the message in the chat.msg_pool
-                            let msg_id = state.chat_streaming_state.msg_ids[ind];
-                            if let Some(chat) = state.open_chats.get_mut(&active_chat_id) {
-                                if let Some(msg) = chat.msg_pool.get_mut(&msg_id) {
-                                    msg.content = buf.clone();
-                                }
-                            }
-                        }
+                    eprintln!("Agent {} Error: {}", ind, err);
+
+                    // kept apart from the content, which stays as streamed
+                    let msg_id = state.chat_streaming_state.msg_ids[ind];
+                    let err = err.strip_prefix("Error: ").unwrap_or(&err)
+                            .to_string();
+                    state.chat_streaming_state.failed |= 1 << ind as u128;
+                    // a failed regeneration leaves the stored reply alone
+                    let regenerating = state.chat_streaming_state.replaced_msgs
+                            .get(ind).is_some_and(Option::is_some);
+                    if !regenerating {
+                        let _ = mod_msg_error(&state.db_conn, msg_id, Some(&err));
+                    }
+                    // the clock stops here, not when Finished comes in
+                    let elapsed_ms = update_elapsed(state, ind);
+                    if let Some(start) = state.chat_streaming_state
+                            .start_times.get_mut(ind) {
+                        *start = None;
+                    }
+                    if let Some(elapsed_ms) = elapsed_ms.filter(|_| !regenerating) {
+                        let _ = mod_msg_elapsed(&state.db_conn, msg_id, elapsed_ms);
+                    }
+                    if let Some(msg) = state.open_chats.get_mut(&stream_chat_id)
+                            .and_then(|chat| chat.msg_pool.get_mut(&msg_id)) {
+                        msg.error = Some(err);
+                    }
 
-                        state.chat_streaming_state.bitmask &= !(1 << ind as u128);
-                        println!("Stream {} finished with error", ind);
-                        if state.chat_streaming_state.bitmask == 0 {
-                            state.chat_streaming_state.streaming = false;
+                    state.chat_streaming_state.bitmask &= !(1 << ind as u128);
+                    println!("Stream {} finished with error", ind);
+                    if state.chat_streaming_state.bitmask == 0 {
+                        state.chat_streaming_state.streaming = false;
+                    } else if *state.perma.abort_all_on_error.lock().unwrap() {
+                        // like pressing Stop for the others, but without
+                        // waiting for their Finished: their partial replies
+                        // are saved now and the whole chat stops streaming.
+                        // The failed one is not marked stopped.
+                        let others = state.chat_streaming_state.bitmask;
+                        for i in 0..state.chat_streaming_state.abort_flags.len() {
+                            if others & (1 << i as u128) == 0 {
+                                continue;
+                            }
+                            if let Some(flag) = &state.chat_streaming_state
+                                    .abort_flags[i] {
+                                flag.store(true, std::sync::atomic::Ordering::Relaxed);
+                            }
+                            finish_agent_reply(state, stream_chat_id, i, true);
                         }
+                        state.chat_streaming_state.abandoned |= others;
+                        state.chat_streaming_state.bitmask = 0;
+                        state.chat_streaming_state.streaming = false;
                     }
                 }
             }
         }
 
+        handle_shortcuts(ctx, state);
+
         ui_top_panel(ctx, state);
 
         ui_side_panel(ctx, state);
 
         ui_key_manager(ctx, state);
 
+        ui_settings(ctx, state);
+
         ui_preset_editor(ctx, state);
 
         ui_agent_config(ctx, state);
@@ -635,6 +1009,8 @@ impl eframe::App for MyApp {
 
         ui_chat(ctx, state);
 
+        chat::render_undo_delete(ctx, state);
+
         // File Dialog Start
         state.file_dialog.update(ctx);
 
@@ -642,7 +1018,8 @@ impl eframe::App for MyApp {
         if let Some(path) = state.file_dialog.take_picked() {
             if let Some(op) = state.pending_file_dialog_op.take() {
                 // If it's an export, write it directly and skip the channel
-                if matches!(op, crate::common::FileOp::ExportChat) {
+                if matches!(op, FileOp::ExportChat | FileOp::ExportPreset
+                        | FileOp::ExportSandbox) {
                     if let Some(content) = state.pending_export_content.take() {
                         let _ = std::fs::write(&path, content);
                     }
@@ -779,25 +1156,225 @@ impl eframe::App for MyApp {
             }
         }
 
+        if let Some(msg) = &state.info_msg {
+            let msg_text = msg.clone();
+            let mut open = true;
+            let mut confirmed = false;
+
+            egui::Window::new(t!("info_title"))
+                .collapsible(false)
+                .resizable(false)
+                .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+                .open(&mut open)
+                .show(ctx, |ui| {
+                    ui.set_min_width(300.0);
+
+                    ui.vertical_centered(|ui| {
+                        ui.add_space(10.0);
+                        ui.label(&msg_text);
+                        ui.add_space(20.0);
+                        confirmed = ui.button(t!("ok_btn")).clicked();
+                    });
+                });
+
+            if confirmed || !open {
+                state.info_msg = None;
+                state.is_modal_open = state.error_msg.is_some();
+            }
+        }
+
+        if state.missing_key_prompt {
+            let mut open = true;
+            let mut closed = false;
+
+            egui::Window::new(t!("missing_key_title"))
+                .collapsible(false)
+                .resizable(false)
+                .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+                .open(&mut open)
+                .show(ctx, |ui| {
+                    ui.set_min_width(300.0);
+
+                    ui.vertical_centered(|ui| {
+                        ui.add_space(10.0);
+                        ui.label(t!("missing_key_msg"));
+                        ui.add_space(20.0);
+                        ui.horizontal(|ui| {
+                            if ui.button(t!("missing_key_open_btn")).clicked() {
+                                state.show_key_manager = true;
+                                closed = true;
+                            }
+                            if ui.button(t!("cancel_btn")).clicked() {
+                                closed = true;
+                            }
+                        });
+                    });
+                });
+
+            if closed || !open {
+                state.missing_key_prompt = false;
+                state.is_modal_open = state.error_msg.is_some();
+            }
+        }
+
+        // Sandbox Clear Confirmation Modal
+        if let Some((chats, presets)) = state.confirm_clear {
+            let mut open = true;
+            let mut confirmed = false;
+            let mut cancelled = false;
+
+            egui::Window::new(t!("clear_sandbox_title"))
+                .collapsible(false)
+                .resizable(false)
+                .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+                .open(&mut open)
+                .show(ctx, |ui| {
+                    ui.set_min_width(300.0);
+
+                    ui.vertical_centered(|ui| {
+                        ui.add_space(10.0);
+                        ui.label(t!("clear_sandbox_confirm", chats = chats,
+                            presets = presets));
+                        ui.add_space(20.0);
+
+                        ui.horizontal(|ui| {
+                            confirmed = ui.button(
+                                egui::RichText::new(t!("clear_sandbox_btn"))
+                                .color(ui.visuals().error_fg_color)).clicked();
+                            cancelled = ui.button(t!("cancel_btn")).clicked();
+                        });
+                    });
+                });
+
+            if confirmed {
+                // one last chance to undo, by restoring the backup by hand,
+                // so nothing is cleared without it
+                let cleared = backup_sandbox(&state.db_conn, &state.sandbox)
+                    .map_err(|e| format!("Could not back up the Sandbox: {}", e))
+                    .and_then(|_| reset_sandbox_db(&state.db_conn)
+                        .map_err(|e| format!("Could not clear the sandbox: {}", e)));
+                match cleared {
+                    Ok(()) => {
+                        let _ = state.op_tx.send(FileOpMsg {
+                            op: FileOp::Clear,
+                            cancelled: false,
+                            path: None,
+                            attachments: None,
+                            left_content: None,
+                            right_content: None,
+                        });
+                    }
+                    Err(e) => state.error_msg = Some(e),
+                }
+            }
+            if confirmed || cancelled || !open {
+                state.confirm_clear = None;
+                state.is_modal_open = state.error_msg.is_some();
+            }
+        }
+
+        // Ollama Model Deletion Confirmation Modal
+        if let Some((name, size)) = state.confirm_delete_model.clone() {
+            let mut open = true;
+            let mut confirmed = false;
+            let mut cancelled = false;
+
+            egui::Window::new(t!("ollama_delete_model_title"))
+                .collapsible(false)
+                .resizable(false)
+                .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+                .open(&mut open)
+                .show(ctx, |ui| {
+                    ui.set_min_width(300.0);
+
+                    ui.vertical_centered(|ui| {
+                        ui.add_space(10.0);
+                        // cached before sizes were recorded
+                        let size = if size > 0 {
+                            common::format_bytes(size as u64)
+                        } else {
+                            "?".to_string()
+                        };
+                        ui.label(t!("ollama_delete_model_confirm", model = name,
+                            size = size));
+                        ui.add_space(20.0);
+
+                        ui.horizontal(|ui| {
+                            confirmed = ui.button(
+                                egui::RichText::new(t!("ollama_delete_model_btn"))
+                                .color(ui.visuals().error_fg_color)).clicked();
+                            cancelled = ui.button(t!("cancel_btn")).clicked();
+                        });
+                    });
+                });
+
+            if confirmed {
+                crate::gui::preset_editor::delete_ollama_model(ctx, state, &name);
+            }
+            if confirmed || cancelled || !open {
+                state.confirm_delete_model = None;
+                state.is_modal_open = state.error_msg.is_some();
+            }
+        }
+
+        // Add Instruction Modal
+        if let Some(mut content) = state.add_system_msg.clone() {
+            let mut open = true;
+            let mut confirmed = false;
+            let mut cancelled = false;
+
+            egui::Window::new(t!("add_system_msg_title"))
+                .collapsible(false)
+                .resizable(false)
+                .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+                .open(&mut open)
+                .show(ctx, |ui| {
+                    ui.set_min_width(400.0);
+                    ui.label(t!("add_system_msg_explanation"));
+                    ui.add(egui::TextEdit::multiline(&mut content)
+                        .desired_width(f32::INFINITY)
+                        .desired_rows(6)
+                        .hint_text(t!("add_system_msg_hint")));
+                    ui.add_space(10.0);
+
+                    ui.horizontal(|ui| {
+                        confirmed = ui.add_enabled(!content.trim().is_empty(),
+                            egui::Button::new(t!("add_system_msg_btn")))
+                            .clicked();
+                        cancelled = ui.button(t!("cancel_btn")).clicked();
+                    });
+                });
+
+            state.add_system_msg = Some(content.clone());
+            if confirmed {
+                let chat_id = state.active_chat_id.unwrap_or(0);
+                bottom_panel::add_system_msg(state, chat_id, content.trim());
+            }
+            if confirmed || cancelled || !open {
+                state.add_system_msg = None;
+                state.is_modal_open = state.error_msg.is_some();
+            }
+        }
+
         // Project Initialization Modal
         if state.show_project_init_modal {
             let mut open = true;
 
-            egui::Window::new("Initialize Project Sandbox")
+            egui::Window::new(t!("init_project_title"))
                 .collapsible(false)
                 .resizable(false)
                 .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
                 .open(&mut open)
                 .show(ctx, |ui| {
-                    ui.label("A project directory was provided, but no Sandbox was found.");
-                    ui.label("Would you like to initialize an '.inforno' directory here?");
+                    ui.label(t!("init_project_no_sandbox"));
+                    ui.label(t!("init_project_question"));
 
                     ui.add_space(10.0);
-                    ui.checkbox(&mut state.copy_presets_checked, "Copy Presets from Home Sandbox");
+                    ui.checkbox(&mut state.copy_presets_checked, t!("init_project_copy_presets"));
                     ui.add_space(10.0);
 
                     ui.horizontal(|ui| {
-                        if ui.button("Create Sandbox").clicked() {
+                        if ui.button(t!("init_project_create_btn")).clicked() {
                             if let Some(proj_dir) = &state.project_dir_to_init {
                                 if let Err(e) = crate::db::init_project_sandbox(proj_dir, state.copy_presets_checked) {
                                     state.error_msg = Some(format!("Failed to create project sandbox: {}", e));
@@ -814,7 +1391,7 @@ impl eframe::App for MyApp {
                             state.show_project_init_modal = false;
                         }
 
-                        if ui.button("Cancel").clicked() {
+                        if ui.button(t!("cancel_btn")).clicked() {
                             // Proceed using the default sandbox without creating the project one
                             state.show_project_init_modal = false;
                         }
@@ -829,6 +1406,24 @@ impl eframe::App for MyApp {
     }
 }
 
+// renames the chat to the generated title, unless the user renamed it while
+// the title was being generated
+fn apply_generated_title(state: &mut State, chat_id: i64, old_title: &str,
+        title: &str) {
+    let Some(db_chat) = state.db_chats.iter_mut()
+            .find(|c| c.id == chat_id && c.title == old_title) else {
+        return;
+    };
+    if let Err(error) = crate::db::mod_chat_title(&state.db_conn, chat_id, title) {
+        eprintln!("Error: could not save the generated chat title: {}", error);
+        return;
+    }
+    db_chat.title = title.to_string();
+    if let Some(chat) = state.open_chats.get_mut(&chat_id) {
+        chat.title = title.to_string();
+    }
+}
+
 pub fn reload_db_chats(conn: &Connection, db_chats: &mut Vec<DbChat>) {
     let titles = crate::db::fetch_chat_titles(conn).unwrap_or_else(|e| {
         eprintln!("Error: {}", e);
@@ -837,6 +1432,91 @@ pub fn reload_db_chats(conn: &Connection, db_chats: &mut Vec<DbChat>) {
     *db_chats = titles;
 }
 
+pub fn reload_trash_chats(conn: &Connection, trash_chats: &mut Vec<DbChat>) {
+    let titles = crate::db::fetch_deleted_chat_titles(conn).unwrap_or_else(|e| {
+        eprintln!("Error: {}", e);
+        vec![]
+    });
+    *trash_chats = titles;
+}
+
+// asks where to save an exported chat, the file is written once it is picked
+pub fn start_markdown_export(state: &mut State, title: &str, markdown: String) {
+    state.pending_file_dialog_op = Some(FileOp::ExportChat);
+    state.pending_export_content = Some(markdown);
+
+    // Create a safe default filename based on the chat's title
+    let safe_title = title.replace(|c: char| !c.is_alphanumeric() && c != ' ' && c != '-', "_");
+    let default_name = format!("{}.md", safe_title);
+
+    state.file_dialog = egui_file_dialog::FileDialog::new()
+        .default_file_name(&default_name)
+        .add_file_filter("Markdown", std::sync::Arc::new(|p: &std::path::Path| p.extension().is_some_and(|ext| ext == "md")));
+    state.file_dialog.save_file();
+}
+
+// (openrouter, ollama) model names for the autocomplete, the installed
+// ollama models are merged in
+fn read_cached_model_names(conn: &mut Connection, ollama_installed: &[String])
+        -> (Vec<String>, Vec<String>) {
+    let openr_names = get_openr_model_names(conn).unwrap_or_default();
+    let mut ollama_names = get_ollama_model_names(conn).unwrap_or_default();
+    ollama_names.extend(ollama_installed.iter().cloned());
+    ollama_names.sort();
+    ollama_names.dedup();
+    (openr_names, ollama_names)
+}
+
+/// Takes in the installed Ollama models fetched in the background and keeps
+/// them in the cache, so the editor stops offering models that are gone
+pub fn apply_ollama_models(state: &mut State,
+        fetched: Result<Vec<common::DbOllamaModel>, String>) {
+    match fetched {
+        Ok(models) => {
+            if let Some(conn) = state.cache_conn.as_mut() {
+                let _ = populate_ollama_installed(conn, &models);
+            }
+            state.ollama_model_names_installed = models.into_iter()
+                .map(|m| m.name).collect();
+            state.preset_editor_state.router_changed = true;
+        }
+        Err(e) => state.error_msg = Some(e),
+    }
+}
+
+/// Tries the cache db file again after it failed to open at startup. An
+/// in-memory fallback counts as a failure and the current connection is kept.
+pub fn retry_cache_conn(state: &mut State) {
+    match get_cache_db_conn() {
+        Ok((_, true)) => {
+            state.error_msg = Some(t!("cache_db_retry_failed").to_string());
+            state.is_modal_open = true;
+        }
+        Ok((mut conn, false)) => {
+            (state.openr_model_names, state.ollama_model_names) =
+                read_cached_model_names(&mut conn,
+                    &state.ollama_model_names_installed);
+            state.models_last_updated = get_models_last_updated(&conn);
+            state.cache_conn = Some(conn);
+            state.cache_in_memory = false;
+        }
+        Err(e) => {
+            state.error_msg = Some(format!("{}: {}", t!("cache_db_retry_failed"), e));
+            state.is_modal_open = true;
+        }
+    }
+}
+
+// cost of an openrouter reply, computed from the cached model prices
+fn openr_cost(cache_conn: &Option<Connection>, preset: Option<&common::Preset>,
+        usage: &common::MsgUsage) -> Option<f64> {
+    let preset = preset
+        .filter(|p| p.chat_router == common::ChatRouter::Openrouter)?;
+    let info = get_openr_model_info(cache_conn.as_ref()?, &preset.model)
+        .ok()??;
+    info.cost_of(usage.prompt_tokens, usage.completion_tokens)
+}
+
 #[macro_export]
 macro_rules! mybtn {
     // Matches: mybtn!(ui, "label_key", "tooltip_key")
@@ -878,3 +1558,173 @@ pub fn save_layout_to_ron(state: &State) {
         }
     }
 }
+
+// text files dropped onto the window go into the prompt box
+fn load_dropped_files(ctx: &egui::Context, state: &mut State) {
+    let dropped = ctx.input(|i| i.raw.dropped_files.clone());
+    for file in dropped {
+        let name = file.path.as_ref()
+            .and_then(|p| p.file_name())
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or(file.name.clone());
+        let bytes = match (&file.bytes, &file.path) {
+            (Some(bytes), _) => Ok(bytes.to_vec()),
+            (None, Some(path)) => std::fs::read(path),
+            (None, None) => continue,
+        };
+        let text = match bytes {
+            Ok(bytes) => match String::from_utf8(bytes) {
+                Ok(text) if !text.contains('\0') => text,
+                _ => {
+                    state.error_msg = Some(t!("error_dropped_binary",
+                        name = name).to_string());
+                    continue;
+                }
+            },
+            Err(e) => {
+                state.error_msg = Some(format!("Could not read file: {}", e));
+                continue;
+            }
+        };
+
+        let char_count = text.chars().count();
+        let text = if char_count > MAX_DROPPED_CHARS {
+            state.error_msg = Some(t!("warning_dropped_truncated", name = name,
+                kept = MAX_DROPPED_CHARS, total = char_count).to_string());
+            text.chars().take(MAX_DROPPED_CHARS).collect()
+        } else {
+            text
+        };
+
+        let prompt = &mut state.bottom_panel_state.prompt_edited;
+        if !prompt.is_empty() && !prompt.ends_with('\n') {
+            prompt.push('\n');
+        }
+        prompt.push_str(&text);
+    }
+}
+
+// a stream is done: its reply is saved with its elapsed time and usage
+// the agent's reply is done: a failed regeneration brings the stored reply
+// back, otherwise the new one is saved
+fn finish_agent_reply(state: &mut State, chat_id: i64, ind: usize,
+        interrupted: bool) {
+    let replaced = state.chat_streaming_state.replaced_msgs
+            .get_mut(ind).and_then(Option::take);
+    let failed = state.chat_streaming_state.failed
+            & (1 << ind as u128) != 0;
+    match replaced {
+        Some(original) if failed => restore_replaced_reply(
+            state, chat_id, ind, original),
+        replaced => {
+            // the regenerated reply takes over the stored row
+            let msg_id = state.chat_streaming_state.msg_ids[ind];
+            if let Some(msg) = replaced.and_then(|_| state.open_chats
+                    .get(&chat_id)
+                    .and_then(|chat| chat.msg_pool.get(&msg_id))) {
+                let _ = crate::db::mod_msg(&state.db_conn, msg);
+            }
+            save_finished_reply(state, chat_id, ind, interrupted);
+        }
+    }
+}
+
+fn save_finished_reply(state: &mut State, chat_id: i64, ind: usize,
+        interrupted: bool) {
+    // persist the result to db
+    let content = state.chat_streaming_state
+            .content_buffers[ind].clone();
+    let reasoning = state.chat_streaming_state
+            .reasoning_buffers[ind].clone();
+
+    // save the message content and reasoning to the database
+    let msg_id = state.chat_streaming_state.msg_ids[ind];
+    let _ = mod_msg_content_reasoning(
+            &state.db_conn, msg_id, &content, &reasoning);
+
+    // a stopped reply keeps its partial content, marked so
+    if interrupted {
+        if let Some(msg) = state.open_chats.get_mut(&chat_id)
+                .and_then(|chat| chat.msg_pool.get_mut(&msg_id)) {
+            msg.interrupted = true;
+        }
+        let _ = mod_msg_interrupted(&state.db_conn, msg_id, true);
+    }
+
+    // freeze the elapsed time
+    let elapsed_ms = update_elapsed(state, ind);
+    if let Some(elapsed_ms) = elapsed_ms {
+        let _ = mod_msg_elapsed(&state.db_conn, msg_id,
+            elapsed_ms);
+    }
+    if let Some(start) = state.chat_streaming_state
+            .start_times.get_mut(ind) {
+        *start = None;
+    }
+
+    // save the token usage, if the router reported it
+    let usage = state.chat_streaming_state.usage_buffers
+            .get_mut(ind).and_then(|u| u.take());
+    if let Some(mut usage) = usage {
+        if let Some(msg) = state.open_chats.get_mut(&chat_id)
+                .and_then(|chat| chat.msg_pool.get_mut(&msg_id)) {
+            // prefer our own pricing info for openrouter
+            usage.cost = openr_cost(&state.cache_conn,
+                msg.preset.as_ref(), &usage).or(usage.cost);
+            // a continued reply adds to what it already used,
+            // a new or regenerated one starts from None
+            usage.prompt_tokens += msg.prompt_tokens.unwrap_or(0);
+            usage.completion_tokens +=
+                msg.completion_tokens.unwrap_or(0);
+            usage.cost = match (usage.cost, msg.cost) {
+                (Some(cost), Some(before)) => Some(cost + before),
+                (cost, before) => cost.or(before),
+            };
+            // the server's own figure is more accurate, else
+            // average over the whole stream
+            usage.tokens_per_sec = usage.tokens_per_sec.or_else(
+                || tokens_per_sec(usage.completion_tokens,
+                elapsed_ms?));
+            msg.prompt_tokens = Some(usage.prompt_tokens);
+            msg.completion_tokens = Some(usage.completion_tokens);
+            msg.cost = usage.cost;
+            msg.tokens_per_sec = usage.tokens_per_sec;
+        }
+        let _ = mod_msg_usage(&state.db_conn, msg_id,
+            usage.prompt_tokens, usage.completion_tokens,
+            usage.cost, usage.tokens_per_sec);
+    }
+}
+
+// a regeneration failed before it replaced the stored reply, which is put
+// back on screen as it was, with the error shown on it
+fn restore_replaced_reply(state: &mut State, chat_id: i64, ind: usize,
+        original: common::ChatMsg) {
+    let msg_id = state.chat_streaming_state.msg_ids[ind];
+    if let Some(start) = state.chat_streaming_state.start_times.get_mut(ind) {
+        *start = None;
+    }
+    if let Some(msg) = state.open_chats.get_mut(&chat_id)
+            .and_then(|chat| chat.msg_pool.get_mut(&msg_id)) {
+        let error = msg.error.take();
+        *msg = original;
+        msg.error = error;
+    }
+}
+
+
+// sets the streamed message's elapsed time from the agent's start time
+fn update_elapsed(state: &mut State, ind: usize) -> Option<i64> {
+    let start = state.chat_streaming_state.start_times.get(ind).copied()??;
+    let elapsed_ms = start.elapsed().as_millis() as i64;
+    let msg_id = *state.chat_streaming_state.msg_ids.get(ind)?;
+    let chat = state.open_chats.get_mut(&state.chat_streaming_state.chat_id)?;
+    chat.msg_pool.get_mut(&msg_id)?.elapsed_ms = Some(elapsed_ms);
+    Some(elapsed_ms)
+}
+
+// completion tokens per second, None for an empty or instant reply
+fn tokens_per_sec(completion_tokens: i64, elapsed_ms: i64) -> Option<f64> {
+    (completion_tokens > 0 && elapsed_ms > 0)
+        .then(|| completion_tokens as f64 * 1000.0 / elapsed_ms as f64)
+}
@@ -1,6 +1,7 @@
 use egui_tiles::{Behavior, TileId, UiResponse};
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
+use rust_i18n::t;
 
 use crate::gui::SplitButton;
 
@@ -44,10 +45,10 @@ impl<'a> Behavior<Pane> for PaneBehavior<'a> {
         if let Some(active_id) = tabs.active {
             // Grab the active pane, clone it, and attach the split direction.
             if let Some(egui_tiles::Tile::Pane(active_pane)) = tiles.get(active_id) {
-                if ui.button("◫").on_hover_text("Split Right").clicked() {
+                if ui.button("◫").on_hover_text(t!("split_right_tooltip")).clicked() {
                     self.split_requests.push((active_pane.clone(), SplitAction::Right));
                 }
-                if ui.button("⊟").on_hover_text("Split Down").clicked() {
+                if ui.button("⊟").on_hover_text(t!("split_down_tooltip")).clicked() {
                     self.split_requests.push((active_pane.clone(), SplitAction::Down));
                 }
             }
@@ -171,6 +172,9 @@ impl<'a> Behavior<Pane> for PaneBehavior<'a> {
             ui.push_id(tile_id, |ui| {
                 match pane {
                     Pane::Chat { chat_id } => {
+                        if is_active && self.state.find_bar.open {
+                            crate::gui::chat::render_find_bar(ui, self.state, *chat_id);
+                        }
                         egui::ScrollArea::vertical()
                             .id_salt(format!("pane_scroll_{:?}", tile_id))
                             .stick_to_bottom(true)
@@ -242,8 +246,8 @@ impl<'a> Behavior<Pane> for PaneBehavior<'a> {
                         } else {
                             ui.vertical_centered(|ui| {
                                 ui.add_space(20.0);
-                                ui.colored_label(ui.visuals().warn_fg_color, "Merge session data lost.");
-                                ui.label("Please close this tab and reopen it from the chat.");
+                                ui.colored_label(ui.visuals().warn_fg_color, t!("merge_session_lost"));
+                                ui.label(t!("merge_session_lost_hint"));
                             });
                         }
                     }
@@ -253,7 +257,7 @@ impl<'a> Behavior<Pane> for PaneBehavior<'a> {
                         ui.separator();
 
                         if results.is_empty() {
-                            ui.label(egui::RichText::new("No results found.").weak().italics());
+                            ui.label(egui::RichText::new(t!("no_search_results")).weak().italics());
                         } else {
                             egui::ScrollArea::vertical()
                                 .id_salt(format!("search_scroll_{:?}", tile_id))
@@ -266,7 +270,7 @@ impl<'a> Behavior<Pane> for PaneBehavior<'a> {
                                             // It returns true/false for which part was clicked.
                                             let (open_current, open_right) = crate::gui::SplitButton::new(&res.chat_title)
                                                 .id_salt(res.chat_id)
-                                                .arrow_tooltip("Open in right pane")
+                                                .arrow_tooltip(t!("right_button_tooltip"))
                                                 .show(ui);
 
                                             if open_current {
@@ -276,6 +280,11 @@ impl<'a> Behavior<Pane> for PaneBehavior<'a> {
                                             if open_right {
                                                 self.open_chat_requests.push((res.chat_id, true));
                                             }
+
+                                            if open_current || open_right {
+                                                self.state.chat_msg_ui.entry(res.msg_id)
+                                                    .or_default().scroll_to = true;
+                                            }
                                             // -------------------------------
 
                                             // grep-style snippet
@@ -2,7 +2,6 @@ use std::sync::{Arc, Mutex};
 
 use egui::{Align, Layout, RichText, Ui, Vec2b};
 use crate::gui::autocomplete::AutoCompleteTextEdit;
-use ollama_rs::Ollama;
 use rand::RngExt;
 use rusqlite::Connection;
 use rust_i18n::t;
@@ -10,11 +9,11 @@ use tokio_stream::StreamExt;
 
 use crate::{
     common::{
-        ChatRouter, DbOllamaModel, DbOpenrModel, ModelOptions, OllamaDownloading, Preset, PresetSelection, Presets, cloud_color, format_bytes, load_presets, local_color, router_color, err_color, strong_color
+        ChatRouter, ChatStreamEvent, DbOllamaModel, DEFAULT_PRESET_ID, DbOpenrModel, FileOp, ModelOptions, OllamaDownloading, Preset, PresetSelection, Presets, ReasoningEffort, ResponseFormat, cloud_color, format_bytes, load_presets, local_color, router_color, err_color, strong_color
     },
     db::{
         cache::{
-            get_ollama_model_info, get_openr_model_info,
+            get_ollama_installed_size, get_ollama_model_info, get_openr_model_info,
         },
         delete_preset, save_preset,
     },
@@ -30,13 +29,67 @@ pub struct PresetEditorState {
     pub openr_model_info: Option<DbOpenrModel>,
     pub seed_entered: String,
     pub temperature_entered: String,
+    pub max_tokens_entered: String,
+    pub top_p_entered: String,
+    pub top_k_entered: String,
+    pub frequency_penalty_entered: String,
+    pub presence_penalty_entered: String,
+    pub keep_alive_entered: String,
+    pub history_limit_entered: String,
+    pub timeout_secs_entered: String,
     pub router_changed: bool,
     pub is_model_valid: bool,
+    // closest available names while the model is not found
+    pub model_suggestions: Vec<String>,
     pub is_seed_valid: bool,
     pub is_temperature_valid: bool,
+    pub is_max_tokens_valid: bool,
+    pub is_top_p_valid: bool,
+    pub is_top_k_valid: bool,
+    pub is_frequency_penalty_valid: bool,
+    pub is_presence_penalty_valid: bool,
+    pub is_keep_alive_valid: bool,
+    pub is_history_limit_valid: bool,
+    pub is_timeout_secs_valid: bool,
     pub ollama_only_installed: bool,
     pub ollama_model_info: Option<DbOllamaModel>,
     pub ollama_downloading: Arc<Mutex<OllamaDownloading>>,
+    pub preset_test: Arc<Mutex<PresetTest>>,
+}
+
+impl PresetEditorState {
+    /// Starts editing `preset`, its options fill the text fields
+    pub fn load_preset(&mut self, preset: Preset) {
+        let options = &preset.options;
+        self.seed_entered = entered_text(options.seed);
+        self.temperature_entered = entered_text(options.temperature);
+        self.max_tokens_entered = entered_text(options.max_tokens);
+        self.top_p_entered = entered_text(options.top_p);
+        self.top_k_entered = entered_text(options.top_k);
+        self.frequency_penalty_entered = entered_text(options.frequency_penalty);
+        self.presence_penalty_entered = entered_text(options.presence_penalty);
+        self.keep_alive_entered = entered_text(options.keep_alive.as_ref());
+        self.history_limit_entered = entered_text(options.history_limit);
+        self.timeout_secs_entered = entered_text(options.timeout_secs);
+        self.edited_preset = preset;
+        self.router_changed = true;
+    }
+}
+
+// text of an option's field, empty while the option is unset
+fn entered_text<T: ToString>(value: Option<T>) -> String {
+    value.map(|v| v.to_string()).unwrap_or_default()
+}
+
+/// The outcome of the last "Test" of the edited preset, filled in by a
+/// background task
+#[derive(Default, Clone)]
+pub struct PresetTest {
+    pub running: bool,
+    pub model: String,
+    pub content: String,
+    pub reasoning: String,
+    pub error: Option<String>,
 }
 
 // --- Macros ---
@@ -139,12 +192,28 @@ pub fn ui_preset_editor(ctx: &egui::Context, state: &mut State) {
 }
 
 // --- Logic Helpers ---
+
+/// Tells the user when the preset can't be saved as edited, i.e. its json
+/// schema doesn't parse. Returns true if it can.
+pub fn check_edited_options(options: &ModelOptions,
+        error_msg: &mut Option<String>) -> bool {
+    if !options.is_response_format_valid() {
+        *error_msg = Some(t!("error_invalid_json_schema").to_string());
+        return false;
+    }
+    true
+}
+
+// false if nothing was saved, the editor then stays open
 fn save_active_preset(
     conn: &Connection,
     edited_preset: &mut Preset,
     presets: &mut Presets,
     error_msg: &mut Option<String>,
-) {
+) -> bool {
+    if !check_edited_options(&edited_preset.options, error_msg) {
+        return false;
+    }
     // We now use the passed variables directly
     match save_preset(conn, edited_preset) {
         Ok(val) => {
@@ -156,10 +225,12 @@ fn save_active_preset(
                 // Reload the presets list
                 load_presets(conn, presets);
             }
+            true
         }
         Err(error) => {
             *error_msg = Some(format!("Error updating Preset: {}", error));
             eprintln!("Error updating Preset: {}", error);
+            false
         }
     }
 }
@@ -171,6 +242,8 @@ fn handle_global_shortcuts(_ui: &mut egui::Ui, _state: &mut State) {
 
 fn render_view_mode(ui: &mut egui::Ui, state: &mut State) {
     let substate = &mut state.preset_editor_state;
+    // presets can still be looked at and exported in read-only mode
+    let readonly = *state.perma.readonly.lock().unwrap();
 
     ui.colored_label(
         ui.visuals().hyperlink_color, t!("preset_editor_invitation"),
@@ -178,10 +251,19 @@ fn render_view_mode(ui: &mut egui::Ui, state: &mut State) {
 
     ui.horizontal(|ui| {
         // New Preset Button
-        if ui.button(t!("preset_new_btn")).clicked() {
-            substate.edited_preset = Preset::default();
+        if ui.add_enabled(!readonly, egui::Button::new(t!("preset_new_btn")))
+                .clicked() {
+            substate.load_preset(Preset::default());
             substate.editing = true;
-            substate.router_changed = true;
+        }
+
+        // Import Preset Button
+        if ui.add_enabled(!readonly, egui::Button::new(t!("preset_import_btn")))
+                .on_hover_text(t!("preset_import_btn_tooltip")).clicked() {
+            state.pending_file_dialog_op = Some(FileOp::ImportPreset);
+            state.file_dialog = egui_file_dialog::FileDialog::new()
+                .add_file_filter("Inforno Preset", std::sync::Arc::new(|p: &std::path::Path| p.extension().is_some_and(|ext| ext == "json")));
+            state.file_dialog.pick_file();
         }
 
         let num_presets = state.presets.cache.len();
@@ -192,39 +274,39 @@ fn render_view_mode(ui: &mut egui::Ui, state: &mut State) {
                 &mut substate.selected_preset,
                 &state.presets);
 
-            let mut start_editing = |preset: Preset| {
-                substate.edited_preset = preset;
-                substate.seed_entered = substate
-                    .edited_preset
-                    .options
-                    .seed
-                    .map(|n| n.to_string())
-                    .unwrap_or_default();
-                substate.temperature_entered = substate
-                    .edited_preset
-                    .options
-                    .temperature
-                    .map(|n| n.to_string())
-                    .unwrap_or_default();
-                substate.editing = true;
-                substate.router_changed = true;
-            };
-
             if ui.button(t!("preset_edit_btn")).clicked() {
                 if let Some(preset) = state.presets.get(
                             substate.selected_preset.id).cloned() {
-                    start_editing(preset.clone());
+                    substate.load_preset(preset);
+                    substate.editing = true;
                 }
             }
 
-            if ui.button(t!("preset_duplicate_btn")).clicked() {
+            if ui.add_enabled(!readonly,
+                    egui::Button::new(t!("preset_duplicate_btn"))).clicked() {
                 if let Some(mut preset) = state.presets.get(
                             substate.selected_preset.id).cloned() {
                     preset.id = 0;
                     preset.title.push_str(" Copy");
-                    start_editing(preset);
+                    substate.load_preset(preset);
+                    substate.editing = true;
                 }
             }
+
+            let export_clicked = ui.button(t!("preset_export_btn"))
+                    .on_hover_text(t!("preset_export_btn_tooltip")).clicked();
+            let exported = state.presets.get(substate.selected_preset.id)
+                .filter(|_| export_clicked)
+                .and_then(|p| Some((p.title.clone(), p.to_json()?)));
+            if let Some((title, json)) = exported {
+                let safe_title = title.replace(|c: char| !c.is_alphanumeric() && c != ' ' && c != '-', "_");
+                state.pending_file_dialog_op = Some(FileOp::ExportPreset);
+                state.pending_export_content = Some(json);
+                state.file_dialog = egui_file_dialog::FileDialog::new()
+                    .default_file_name(&format!("{}.json", safe_title))
+                    .add_file_filter("Inforno Preset", std::sync::Arc::new(|p: &std::path::Path| p.extension().is_some_and(|ext| ext == "json")));
+                state.file_dialog.save_file();
+            }
         }
     });
 
@@ -254,19 +336,62 @@ fn render_view_mode(ui: &mut egui::Ui, state: &mut State) {
 
             // --- Rows ---
             row(&t!("router_label"), preset.chat_router.to_string());
+
+            if preset.chat_router == ChatRouter::OpenAICompatible {
+                row(&t!("base_url_label"), preset.base_url.clone());
+            }
             row(&t!("model_label"), preset.model.clone());
+            row(&t!("category_label"), preset.category.clone().unwrap_or_else(
+                    || t!("preset_category_general").to_string()));
             row(&t!("tooltip_label"), preset.tooltip.clone());
 
-            row(&t!("reasoning_label"), preset.options.include_reasoning
-                .map_or(t!("unset").to_string(), |s| {
-                if s { t!("yes").to_string() } else { t!("no").to_string() }
-            }));
+            row(&t!("reasoning_label"), reasoning_text(&preset.options));
+
+            row(&t!("stream_reply_label"), if preset.options.streams() {
+                t!("yes").to_string() } else { t!("no").to_string() });
 
             row(&t!("seed_label"), preset.options.seed.map_or(
                     t!("unset").to_string(), |s| s.to_string()));
 
             row(&t!("temperature_label"), preset.options.temperature
                     .map_or(t!("unset").to_string(), |s| s.to_string()));
+
+            row(&t!("max_tokens_label"), preset.options.max_tokens
+                    .map_or(t!("unset").to_string(), |s| s.to_string()));
+
+            row(&t!("top_p_label"), preset.options.top_p
+                    .map_or(t!("unset").to_string(), |s| s.to_string()));
+
+            row(&t!("top_k_label"), preset.options.top_k
+                    .map_or(t!("unset").to_string(), |s| s.to_string()));
+
+            row(&t!("frequency_penalty_label"), preset.options.frequency_penalty
+                    .map_or(t!("unset").to_string(), |s| s.to_string()));
+
+            row(&t!("presence_penalty_label"), preset.options.presence_penalty
+                    .map_or(t!("unset").to_string(), |s| s.to_string()));
+
+            if preset.chat_router == ChatRouter::Ollama {
+                row(&t!("keep_alive_label"), preset.options.keep_alive.clone()
+                    .unwrap_or_else(|| t!("unset").to_string()));
+            }
+
+            row(&t!("history_limit_label"), preset.options.history_limit
+                    .map_or(t!("unlimited").to_string(), |s| s.to_string()));
+
+            row(&t!("timeout_secs_label"), preset.options.timeout_secs
+                    .map_or(t!("unset").to_string(), |s| format!("{}s", s)));
+
+            row(&t!("response_format_label"), response_format_text(
+                    preset.options.response_format.as_ref()));
+
+            let stop = preset.options.stop_sequences();
+            row(&t!("stop_sequences_label"), if stop.is_empty() {
+                t!("unset").to_string()
+            } else {
+                stop.iter().map(|s| format!("{:?}", s))
+                    .collect::<Vec<_>>().join(", ")
+            });
         });
     }
 }
@@ -276,6 +401,7 @@ fn render_view_mode(ui: &mut egui::Ui, state: &mut State) {
 fn render_edit_mode(ui: &mut egui::Ui, ctx: &egui::Context, state: &mut State) {
     render_edit_action_buttons(ui, state);
 
+    let categories = state.presets.categories();
     let substate = &mut state.preset_editor_state;
 
     // determine Dynamic Title Color based on current selection
@@ -283,14 +409,22 @@ fn render_edit_mode(ui: &mut egui::Ui, ctx: &egui::Context, state: &mut State) {
 
     // Title Edit
     ui.horizontal(|ui| {
-        ui.label("Title of Preset:");
+        ui.label(t!("preset_title_label"));
         ui.add(egui::TextEdit::singleline(&mut substate.edited_preset.title)
                 .text_color(current_title_color));
+        let favorite = &mut substate.edited_preset.favorite;
+        let star = if *favorite { "★" } else { "☆" };
+        if ui.selectable_label(*favorite, star)
+                .on_hover_text(t!("preset_favorite_tooltip")).clicked() {
+            *favorite = !*favorite;
+        }
     });
 
+    render_category_edit(ui, &mut substate.edited_preset.category, &categories);
+
     // Router Selection
     ui.horizontal(|ui| {
-        ui.label("Select a Router:");
+        ui.label(t!("select_router_label"));
         if ui
             .radio_value(
                 &mut substate.edited_preset.chat_router,
@@ -311,29 +445,102 @@ fn render_edit_mode(ui: &mut egui::Ui, ctx: &egui::Context, state: &mut State) {
         {
             substate.router_changed = true;
         }
+        if ui
+            .radio_value(
+                &mut substate.edited_preset.chat_router,
+                ChatRouter::OpenAICompatible,
+                egui::RichText::new(t!("openai_compatible_router"))
+                    .color(local_color()),
+            )
+            .on_hover_text(t!("openai_compatible_router_tooltip"))
+            .changed()
+        {
+            substate.router_changed = true;
+        }
     });
 
     ui.separator();
 
+    if substate.edited_preset.chat_router != ChatRouter::OpenAICompatible {
+        render_models_age(ui, state.models_last_updated,
+            &mut state.perma.models_stale_days.lock().unwrap());
+    }
+    let substate = &mut state.preset_editor_state;
+
     match substate.edited_preset.chat_router {
         ChatRouter::Ollama => render_ollama_editor(ui, ctx, state),
         ChatRouter::Openrouter => render_openrouter_editor(ui, state),
+        ChatRouter::OpenAICompatible => render_openai_editor(ui, state),
     }
 }
 
+// "Models cached 12 days ago", in the error color once older than
+// stale_days. Right click to change the threshold.
+fn render_models_age(ui: &mut egui::Ui,
+        last_updated: Option<chrono::DateTime<chrono::Utc>>,
+        stale_days: &mut i64) {
+    let Some(last_updated) = last_updated else {
+        return;
+    };
+    let days = (chrono::Utc::now() - last_updated).num_days();
+    let text = if days == 0 {
+        t!("models_cached_today").to_string()
+    } else {
+        t!("models_cached_days_ago", days = days).to_string()
+    };
+    let color = if days > *stale_days {
+        err_color()
+    } else {
+        ui.visuals().weak_text_color()
+    };
+    ui.label(RichText::new(text).small().color(color))
+        .on_hover_text(t!("models_cached_tooltip"))
+        .context_menu(|ui| {
+            ui.horizontal(|ui| {
+                ui.label(t!("models_stale_days_label"));
+                ui.add(egui::DragValue::new(stale_days).range(1..=365));
+            });
+        });
+}
+
+// Category text field, suggesting the categories already in use
+fn render_category_edit(ui: &mut egui::Ui, category: &mut Option<String>,
+        categories: &[String]) {
+    let mut text = category.clone().unwrap_or_default();
+    ui.horizontal(|ui| {
+        ui.label(t!("category_label"));
+        ui.add(
+            AutoCompleteTextEdit::new(&mut text, categories)
+                .popup_on_focus(true)
+                .highlight_matches(true)
+                .width(200.0)
+                .set_text_edit_properties(|t| t
+                    .hint_text(t!("preset_category_general"))),
+        ).on_hover_text(t!("category_tooltip"));
+    });
+    *category = if text.is_empty() { None } else { Some(text) };
+}
+
 fn render_edit_action_buttons(ui: &mut egui::Ui, state: &mut State) {
+    let test_key = state.openrouter_key(
+        state.preset_editor_state.edited_preset.key_name.as_deref());
     let substate = &mut state.preset_editor_state;
+    let readonly = *state.perma.readonly.lock().unwrap();
     ui.horizontal(|ui| {
         // Save and Exit Button
-        if ui.button(t!("preset_save_and_exit_btn")).clicked() {
-            save_active_preset(&state.db_conn, &mut substate.edited_preset,
-                    &mut state.presets, &mut state.error_msg);
+        if ui.add_enabled(!readonly,
+                egui::Button::new(t!("preset_save_and_exit_btn"))).clicked()
+                && save_active_preset(&state.db_conn,
+                    &mut substate.edited_preset, &mut state.presets,
+                    &mut state.error_msg) {
             state.show_preset_editor = false;
         }
         // Save and Go Back Button
-        if ui.button(t!("preset_save_and_go_back_btn")).clicked() {
-            save_active_preset(&state.db_conn, &mut substate.edited_preset,
-                    &mut state.presets, &mut state.error_msg);
+        if ui.add_enabled(!readonly,
+                egui::Button::new(t!("preset_save_and_go_back_btn"))).clicked()
+                && save_active_preset(&state.db_conn,
+                    &mut substate.edited_preset, &mut state.presets,
+                    &mut state.error_msg) {
             substate.editing = false;
         }
         // Back Without Saving Button
@@ -341,22 +548,33 @@ fn render_edit_action_buttons(ui: &mut egui::Ui, state: &mut State) {
             substate.editing = false;
         }
         // Save Button
-        if ui.button(t!("preset_save_btn")).clicked() {
+        if ui.add_enabled(!readonly, egui::Button::new(t!("preset_save_btn")))
+                .clicked() {
             save_active_preset(&state.db_conn, &mut substate.edited_preset,
                     &mut state.presets, &mut state.error_msg);
         }
         // Save a Copy Button
-        if ui.button(t!("preset_save_a_copy_btn")).clicked() {
+        if ui.add_enabled(!readonly,
+                egui::Button::new(t!("preset_save_a_copy_btn"))).clicked() {
             substate.edited_preset.id = 0;
             save_active_preset(&state.db_conn, &mut substate.edited_preset,
                     &mut state.presets, &mut state.error_msg);
         }
 
+        // Test Button: a canned prompt to see if the preset works at all
+        let running = substate.preset_test.lock().unwrap().running;
+        if ui.add_enabled(!running && !readonly,
+                egui::Button::new(t!("preset_test_btn")))
+                .on_hover_text(t!("preset_test_btn_tooltip")).clicked() {
+            test_edited_preset(ui.ctx(), substate, &test_key, &state.perma.rt);
+        }
+
         // Delete... menu
         // Prepare disjoint borrows for the closure to avoid conflicts
         let conn = &state.db_conn;
         let presets = &mut state.presets;
 
+        ui.add_enabled_ui(!readonly, |ui| {
         ui.menu_button(egui::RichText::new(t!("preset_delete_menu")), |ui| {
 
             ui.set_min_width(80.0);
@@ -375,6 +593,11 @@ fn render_edit_action_buttons(ui: &mut egui::Ui, state: &mut State) {
                         println!("preset {} deleted successfully",
                             substate.edited_preset.id);
                         substate.selected_preset = PresetSelection::default();
+                        // new agents must not start with a preset that's gone
+                        let _ = DEFAULT_PRESET_ID.compare_exchange(
+                            substate.edited_preset.id, 0,
+                            std::sync::atomic::Ordering::Relaxed,
+                            std::sync::atomic::Ordering::Relaxed);
                     },
                     Err(error) => {
                         println!("error deleting preset {}: {}",
@@ -389,10 +612,98 @@ fn render_edit_action_buttons(ui: &mut egui::Ui, state: &mut State) {
         })
         .response
         .on_hover_text(t!("preset_delete_btn_tooltip"));
+        });
     });
+    render_preset_test(ui, &state.preset_editor_state.preset_test.lock().unwrap());
     ui.separator();
 }
 
+const PRESET_TEST_PROMPT: &str = "Say hello in one sentence.";
+
+// sends PRESET_TEST_PROMPT with the edited preset, as it is on screen
+fn test_edited_preset(ctx: &egui::Context, substate: &PresetEditorState,
+        openrouter_api_key: &crate::common::ApiKey,
+        rt: &tokio::runtime::Handle) {
+    let mut preset = substate.edited_preset.clone();
+    if preset.chat_router == ChatRouter::Openrouter {
+        preset.api_key = openrouter_api_key.clone();
+    }
+    *substate.preset_test.lock().unwrap() = PresetTest {
+        running: true,
+        model: preset.model.clone(),
+        ..Default::default()
+    };
+
+    let mut test_chat = crate::common::Chat::default();
+    test_chat.msg_pool.insert(1, crate::common::ChatMsg {
+        id: 1,
+        msg_role: crate::common::MsgRole::User,
+        content: PRESET_TEST_PROMPT.to_string(),
+        ..Default::default()
+    });
+    test_chat.agents[1].msg_ids.push(1);
+    let que = crate::common::ChatQue {
+        agent_ind: 1,
+        preset,
+        chat: Arc::new(test_chat),
+    };
+
+    let test = substate.preset_test.clone();
+    let ctx = ctx.clone();
+    rt.spawn(async move {
+        let (tx, rx) = std::sync::mpsc::channel();
+        let abort = Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let result = crate::common::run_chat_stream_router(que, tx, &ctx,
+            abort).await;
+
+        let mut test = test.lock().unwrap();
+        for event in rx.try_iter() {
+            match event {
+                ChatStreamEvent::Content(_, text) => test.content.push_str(&text),
+                ChatStreamEvent::Reasoning(_, text) =>
+                    test.reasoning.push_str(&text),
+                ChatStreamEvent::Error(_, err) => test.error = Some(err),
+                _ => {}
+            }
+        }
+        if let Err(e) = result {
+            test.error = Some(e.to_string());
+        }
+        test.running = false;
+        ctx.request_repaint();
+    });
+}
+
+// the raw reply to the test prompt, or why there was none
+fn render_preset_test(ui: &mut egui::Ui, test: &PresetTest) {
+    if test.running {
+        ui.horizontal(|ui| {
+            ui.spinner();
+            ui.label(t!("preset_test_running", model = test.model));
+        });
+        return;
+    }
+    if test.model.is_empty() {
+        return; // not tested yet
+    }
+    ui.label(RichText::new(t!("preset_test_result", model = test.model))
+        .strong());
+    if !test.reasoning.is_empty() {
+        ui.label(RichText::new(format!("{}: {}", t!("thought_process"),
+            test.reasoning.trim())).small().weak());
+    }
+    if !test.content.is_empty() {
+        ui.label(test.content.trim());
+    }
+    match &test.error {
+        Some(err) => crate::gui::chat::render_stream_error(ui, err),
+        None if test.content.trim().is_empty() => {
+            ui.label(RichText::new(t!("preset_test_empty")).color(err_color()));
+        }
+        None => {}
+    }
+}
+
 // --- Ollama Editor Logic ---
 
 pub fn render_ollama_editor(
@@ -401,7 +712,7 @@ pub fn render_ollama_editor(
     state: &mut State,
 ) {
     ui.horizontal(|ui| {
-        ui.label("Select a Model:");
+        ui.label(t!("select_model_label"));
         let substate = &mut state.preset_editor_state;
         let model_color = if substate.is_model_valid {
             strong_color()
@@ -448,6 +759,11 @@ pub fn render_ollama_editor(
                     .contains(&substate.edited_preset.model)
             };
             substate.is_model_valid = is_valid;
+            substate.model_suggestions = if is_valid {
+                Vec::new()
+            } else {
+                closest_model_names(&substate.edited_preset.model, source_list)
+            };
 
             substate.edited_preset.tooltip = "".to_string();
             if is_valid {
@@ -488,15 +804,33 @@ pub fn render_ollama_editor(
         }
 
         if ui
-            .checkbox(&mut substate.ollama_only_installed, "Installed Only")
+            .checkbox(&mut substate.ollama_only_installed, t!("installed_only_checkbox"))
             .changed()
         {
             substate.router_changed = true;
         }
 
         render_ollama_download_button(ui, ctx, substate);
+
+        // ollama lists a model pulled without a tag as "name:latest"
+        let model = &substate.edited_preset.model;
+        let installed_name = [model.clone(), format!("{}:latest", model)].into_iter()
+            .find(|name| state.ollama_model_names_installed.contains(name));
+        let is_dl = substate.ollama_downloading.lock().unwrap().is_downloading;
+        let delete_clicked = ui.add_enabled(installed_name.is_some() && !is_dl,
+                egui::Button::new(t!("ollama_delete_model_btn")))
+                .on_hover_text(t!("ollama_delete_model_tooltip"))
+                .clicked();
+        if let Some(name) = installed_name.filter(|_| delete_clicked) {
+            let size = state.cache_conn.as_ref()
+                .and_then(|conn| get_ollama_installed_size(conn, &name).ok().flatten())
+                .unwrap_or(0);
+            state.confirm_delete_model = Some((name, size));
+            state.is_modal_open = true;
+        }
     });
 
+    render_model_suggestions(ui, &mut state.preset_editor_state);
     render_ollama_download_progress(ui, state);
 
     if let Some(original_preset) = state.presets.get(
@@ -513,11 +847,12 @@ pub fn render_ollama_editor(
     ui.separator();
 
     if let Some(info) = &state.preset_editor_state.ollama_model_info {
-        ui.heading("Model Description:");
+        ui.heading(t!("model_description_heading"));
         egui::ScrollArea::vertical()
             .min_scrolled_height(100.0)
             .show(ui, |ui| {
-                ui.label(info.summary.clone().unwrap_or_default());
+                render_model_docs(ui, &mut state.common_mark_cache,
+                    info.summary.as_deref().unwrap_or_default());
             });
     }
 }
@@ -530,22 +865,23 @@ fn render_ollama_download_button(
     let is_dl = substate.ollama_downloading.lock().unwrap().is_downloading;
 
     // Change button text based on the current state
-    let btn_text = if is_dl { "Stop Download" } else { "Download Model" };
+    let btn_text = if is_dl { t!("stop_download_btn") } else { t!("download_model_btn") };
 
     if ui.button(btn_text).clicked() {
         if is_dl {
             // --- CANCEL LOGIC ---
             let mut oll_dl = substate.ollama_downloading.lock().unwrap();
             oll_dl.abort_flag.store(true, std::sync::atomic::Ordering::Relaxed);
-            oll_dl.status_text = "Canceling...".to_string();
+            oll_dl.status_text = t!("download_canceling").to_string();
         } else {
             // --- START DOWNLOAD LOGIC ---
             {
                 let mut oll_dl = substate.ollama_downloading.lock().unwrap();
                 oll_dl.is_downloading = true;
+                oll_dl.model = substate.edited_preset.model.clone();
                 oll_dl.progress = 0.0;
                 oll_dl.error_msg = None;
-                oll_dl.status_text = "Starting...".to_string();
+                oll_dl.status_text = t!("download_starting").to_string();
                 // Reset the abort flag in case it was previously cancelled
                 oll_dl.abort_flag.store(false, std::sync::atomic::Ordering::Relaxed);
             }
@@ -555,7 +891,16 @@ fn render_ollama_download_button(
             let model_name = substate.edited_preset.model.clone();
 
 tokio::spawn(async move {
-                let ollama = Ollama::default();
+                let ollama = match crate::ollama::ollama_client() {
+                    Ok(ollama) => ollama,
+                    Err(e) => {
+                        let mut oll_dl = state_clone.lock().unwrap();
+                        oll_dl.error_msg = Some(e.to_string());
+                        oll_dl.is_downloading = false;
+                        ctx_clone.request_repaint();
+                        return;
+                    }
+                };
                 let mut was_aborted = false;
 
                 match ollama.pull_model_stream(model_name.clone(), false).await {
@@ -587,7 +932,7 @@ tokio::spawn(async move {
                                     ctx_clone.request_repaint();
                                 }
                                 Err(e) => {
-                                    oll_dl.error_msg = Some(e.to_string());
+                                    oll_dl.error_msg = Some(crate::ollama::describe_error(&e));
                                     oll_dl.is_downloading = false;
                                     break;
                                 }
@@ -596,7 +941,7 @@ tokio::spawn(async move {
                     }
                     Err(e) => {
                         let mut oll_dl = state_clone.lock().unwrap();
-                        oll_dl.error_msg = Some(format!("Failed start: {}", e));
+                        oll_dl.error_msg = Some(crate::ollama::describe_error(&e));
                         oll_dl.is_downloading = false;
                         ctx_clone.request_repaint();
                         return; // Exit early on failure
@@ -611,15 +956,15 @@ tokio::spawn(async move {
 
                     let mut oll_dl = state_clone.lock().unwrap();
                     oll_dl.is_downloading = false;
-                    oll_dl.error_msg = Some("Download cancelled and files removed.".to_string());
-                    oll_dl.status_text = "Aborted".to_string();
+                    oll_dl.error_msg = Some(t!("download_cancelled").to_string());
+                    oll_dl.status_text = t!("download_aborted").to_string();
                     ctx_clone.request_repaint();
                 } else {
                     // Set "Done" ONLY if the task finished naturally
                     let mut oll_dl = state_clone.lock().unwrap();
                     if oll_dl.is_downloading {
                         oll_dl.is_downloading = false;
-                        oll_dl.status_text = "Done!".to_string();
+                        oll_dl.status_text = t!("download_done").to_string();
                         oll_dl.progress = 1.0;
                     }
                     ctx_clone.request_repaint();
@@ -629,13 +974,36 @@ tokio::spawn(async move {
     }
 }
 
+// removes an installed model from the Ollama server in the background, then
+// sends the refreshed installed list so the editor and the cache stop
+// offering it
+pub fn delete_ollama_model(ctx: &egui::Context, state: &State, name: &str) {
+    let name = name.to_string();
+    let tx = state.ollama_models_tx.clone();
+    let ctx = ctx.clone();
+    state.perma.rt.spawn(async move {
+        let deleted = async {
+            let ollama = crate::ollama::ollama_client()
+                .map_err(|e| e.to_string())?;
+            ollama.delete_model(name).await
+                .map_err(|e| crate::ollama::describe_error(&e))?;
+            crate::ollama::ollama_fetch_models().await
+                .map_err(|e| e.to_string())
+        }.await;
+        let _ = tx.send(deleted.map_err(|e| format!("{}: {}",
+            t!("ollama_delete_model_failed"), e)));
+        ctx.request_repaint();
+    });
+}
+
 fn render_ollama_download_progress(ui: &mut egui::Ui, state: &State) {
     let dl_state =
         state.preset_editor_state.ollama_downloading.lock().unwrap();
     if dl_state.is_downloading || dl_state.progress > 0.0 {
         ui.add_space(10.0);
         ui.horizontal(|ui| {
-            ui.label(format!("Status: {}", dl_state.status_text));
+            ui.label(t!("ollama_download_status", model = dl_state.model,
+                status = dl_state.status_text));
             ui.with_layout(
                 egui::Layout::right_to_left(egui::Align::Center),
                 |ui| {
@@ -695,6 +1063,12 @@ pub fn render_openrouter_editor(ui: &mut egui::Ui, state: &mut State) {
                 .openr_model_names
                 .contains(&substate.edited_preset.model);
             substate.is_model_valid = is_valid;
+            substate.model_suggestions = if is_valid {
+                Vec::new()
+            } else {
+                closest_model_names(&substate.edited_preset.model,
+                    &state.openr_model_names)
+            };
 
             substate.edited_preset.tooltip = "".to_string();
             if is_valid {
@@ -716,6 +1090,9 @@ pub fn render_openrouter_editor(ui: &mut egui::Ui, state: &mut State) {
             }
         }
     });
+    render_model_suggestions(ui, &mut state.preset_editor_state);
+    render_key_name_edit(ui, &mut state.preset_editor_state.edited_preset.key_name,
+        &state.named_keys);
 
     if let Some(original_preset) = state.presets.get(
                 state.preset_editor_state.selected_preset.id) {
@@ -731,31 +1108,238 @@ pub fn render_openrouter_editor(ui: &mut egui::Ui, state: &mut State) {
     ui.separator();
 
     if let Some(info) = &state.preset_editor_state.openr_model_info {
-        ui.label(format!("Model Name: {}", info.name));
+        ui.label(t!("openr_model_name", name = info.name));
         ui.horizontal(|ui| {
-            ui.label(format!("Context Length: {}", info.context_length));
-            ui.label(format!(
-                "Date: {}",
-                info.ts_model.clone().unwrap_or_default()
-            ));
+            ui.label(t!("openr_context_length", length = info.context_length));
+            ui.label(t!("openr_model_date",
+                date = info.ts_model.clone().unwrap_or_default()));
         });
-        ui.label(format!(
-            "Prompt: ${:.2}/M, Completion: ${:.2}/M",
-            info.price_prompt.unwrap_or(0.0) * 1e6,
-            info.price_completion.unwrap_or(0.0) * 1e6
-        ));
+        ui.label(t!("openr_model_prices",
+            prompt = format!("{:.2}", info.price_prompt.unwrap_or(0.0) * 1e6),
+            completion = format!("{:.2}", info.price_completion.unwrap_or(0.0) * 1e6)));
 
-        ui.heading("Description:");
+        ui.heading(t!("description_heading"));
         egui::ScrollArea::vertical()
             .min_scrolled_height(100.0)
             .show(ui, |ui| {
-                ui.label(&info.description);
+                render_model_docs(ui, &mut state.common_mark_cache,
+                    &info.description);
+            });
+    }
+}
+
+// which OpenRouter account the preset bills to, see the key manager
+fn render_key_name_edit(ui: &mut egui::Ui, key_name: &mut Option<String>,
+        named_keys: &std::collections::BTreeMap<String, crate::common::ApiKey>) {
+    ui.horizontal(|ui| {
+        ui.label(t!("preset_key_name_label"));
+        let selected = key_name.clone().unwrap_or_else(|| t!("default_key").to_string());
+        egui::ComboBox::from_id_salt("preset_key_name")
+            .selected_text(selected)
+            .show_ui(ui, |ui| {
+                ui.selectable_value(key_name, None, t!("default_key"));
+                for name in named_keys.keys() {
+                    ui.selectable_value(key_name, Some(name.clone()), name);
+                }
             });
+        // the key may have been removed from the key manager since
+        if key_name.as_ref().is_some_and(|name| !named_keys.contains_key(name)) {
+            ui.colored_label(err_color(), t!("named_key_missing"));
+        }
+    }).response.on_hover_text(t!("preset_key_name_tooltip"));
+}
+
+// providers rename and retire models, offer the nearest ones still listed
+fn render_model_suggestions(ui: &mut egui::Ui, substate: &mut PresetEditorState) {
+    if substate.is_model_valid || substate.model_suggestions.is_empty() {
+        return;
+    }
+    let mut picked = None;
+    ui.horizontal_wrapped(|ui| {
+        ui.colored_label(err_color(), t!("model_not_found_label"));
+        for name in &substate.model_suggestions {
+            if ui.small_button(name)
+                    .on_hover_text(t!("model_suggestion_tooltip"))
+                    .clicked() {
+                picked = Some(name.clone());
+            }
+        }
+    });
+    if let Some(name) = picked {
+        substate.edited_preset.model = name;
+        substate.model_suggestions.clear();
+        substate.router_changed = true; // validate it and load its info
+    }
+}
+
+/// The available model names closest to `model`: the ones sharing a part
+/// of the name first, then by edit distance, at most 5
+pub fn closest_model_names(model: &str, names: &[String]) -> Vec<String> {
+    let model = model.trim().to_lowercase();
+    if model.is_empty() {
+        return Vec::new();
+    }
+    // "vendor/name:tag" is compared by its name
+    let base = |name: &str| -> String {
+        let name = name.rsplit('/').next().unwrap_or(name);
+        name.split(':').next().unwrap_or(name).to_string()
+    };
+    let model_base = base(&model);
+    let mut ranked: Vec<(bool, usize, &String)> = names.iter()
+        .map(|name| {
+            let lower = name.to_lowercase();
+            let name_base = base(&lower);
+            let related = !model_base.is_empty() && !name_base.is_empty()
+                && (name_base.contains(&model_base) || model_base.contains(&name_base));
+            (!related, levenshtein(&model, &lower), name)
+        })
+        .collect();
+    ranked.sort();
+    ranked.into_iter().take(5).map(|(_, _, name)| name.clone()).collect()
+}
+
+// number of single character edits turning one string into the other
+fn levenshtein(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut prev = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let cost = if ca == *cb { prev } else { prev + 1 };
+            prev = row[j + 1];
+            row[j + 1] = cost.min(row[j] + 1).min(prev + 1);
+        }
+    }
+    row[b.len()]
+}
+
+// model summaries are markdown, often with bare urls that are made clickable
+fn render_model_docs(ui: &mut egui::Ui,
+        cache: &mut egui_commonmark::CommonMarkCache, text: &str) {
+    egui_commonmark::CommonMarkViewer::new()
+        .show(ui, cache, &linkify_urls(text));
+}
+
+// wraps bare urls in <> so that markdown turns them into links. Urls of
+// markdown links, or already in <>, are left alone, and so is the
+// punctuation that ends a sentence.
+fn linkify_urls(text: &str) -> String {
+    static RE_URL: std::sync::OnceLock<regex::Regex> = std::sync::OnceLock::new();
+    let re = RE_URL.get_or_init(|| regex::Regex::new(
+        r"(^|[^(<\[])(https?://[^\s<>()\[\]]*[^\s<>()\[\].,;:!?'\x22])").unwrap());
+    re.replace_all(text, "$1<$2>").into_owned()
+}
+
+/// Editor for servers with an OpenAI compatible api. There is no model list
+/// to pick from, so both the server url and the model name are free text.
+pub fn render_openai_editor(ui: &mut egui::Ui, state: &mut State) {
+    let substate = &mut state.preset_editor_state;
+    ui.horizontal(|ui| {
+        ui.label(t!("base_url_label"));
+        let url = substate.edited_preset.base_url.trim();
+        let url_color = if url.starts_with("http://")
+                || url.starts_with("https://") {
+            strong_color()
+        } else {
+            err_color()
+        };
+        ui.add(egui::TextEdit::singleline(&mut substate.edited_preset.base_url)
+            .hint_text("http://localhost:8000/v1")
+            .text_color(url_color)
+            .desired_width(f32::INFINITY))
+            .on_hover_text(t!("base_url_tooltip"));
+    });
+
+    ui.horizontal(|ui| {
+        ui.label(t!("select_a_model"));
+        ui.add(egui::TextEdit::singleline(&mut substate.edited_preset.model)
+            .text_color(strong_color())
+            .desired_width(f32::INFINITY));
+    });
+    substate.router_changed = false;
+
+    if let Some(original_preset) = state.presets.get(
+                state.preset_editor_state.selected_preset.id) {
+        render_common_options(ui, &mut state.preset_editor_state,
+                &original_preset.options);
+    } else {
+        // if preset does not exist yet, create a temporary ModelOptions
+        let default_model_options = ModelOptions::default();
+        render_common_options(ui, &mut state.preset_editor_state,
+                &default_model_options);
     }
 }
 
 // --- Deduplicated Options ---
 
+fn effort_text(effort: ReasoningEffort) -> String {
+    match effort {
+        ReasoningEffort::Low => t!("effort_low"),
+        ReasoningEffort::Medium => t!("effort_medium"),
+        ReasoningEffort::High => t!("effort_high"),
+    }.to_string()
+}
+
+/// "Yes (Medium)", "No" or "Unset"
+pub fn reasoning_text(options: &ModelOptions) -> String {
+    match options.include_reasoning {
+        Some(true) => format!("{} ({})", t!("yes"), effort_text(options.effort())),
+        Some(false) => t!("no").to_string(),
+        None => t!("unset").to_string(),
+    }
+}
+
+// An optional number, left empty it stays unset. Values outside `range` are
+// clamped, but still highlighted so the user knows what will be sent.
+#[allow(clippy::too_many_arguments)]
+fn number_edit<T>(ui: &mut Ui, name: &str, width: f32, entered: &mut String,
+        is_valid: &mut bool, value: &mut Option<T>, original: Option<T>,
+        range: std::ops::RangeInclusive<T>)
+        where T: std::str::FromStr + PartialOrd + Copy + ToString {
+    let label = format!("{} ({}: {}):", name, t!("currently"),
+        original.map_or(t!("unset").to_string(), |v| v.to_string()));
+    validated_edit!(
+        ui,
+        &label,
+        width,
+        entered,
+        *is_valid,
+        // Validation Logic
+        {
+            if entered.is_empty() {
+                *value = None;
+                *is_valid = true;
+            } else {
+                match entered.parse::<T>().ok()
+                        .and_then(|v| Some((v, clamped(v, &range)?))) {
+                    Some((v, clamped)) => {
+                        *value = Some(clamped);
+                        *is_valid = range.contains(&v);
+                    }
+                    None => *is_valid = false,
+                }
+            }
+        },
+        // Revert Logic
+        {
+            *entered = entered_text(original);
+        }
+    );
+}
+
+// `v` pulled into `range`, None when it can't be compared, e.g. NaN
+fn clamped<T: PartialOrd + Copy>(v: T, range: &std::ops::RangeInclusive<T>)
+        -> Option<T> {
+    Some(if v.partial_cmp(range.start())?.is_lt() {
+        *range.start()
+    } else if v.partial_cmp(range.end())?.is_gt() {
+        *range.end()
+    } else {
+        v
+    })
+}
+
 fn show_original_value(ui: &mut Ui, text: String) {
     ui.label(RichText::new(format!("({}: {})", t!("currently"), text)));
 }
@@ -801,6 +1385,46 @@ pub fn render_common_options(
         );
     });
 
+    // --- Reasoning Effort (OpenRouter) ---
+    if substate.edited_preset.options.include_reasoning == Some(true) {
+        ui.horizontal(|ui| {
+            ui.label(t!("reasoning_effort"));
+            show_original_value(ui, effort_text(original_options.effort()));
+            if ui.button("⟲").on_hover_text(t!("revert_to_initial_tooltip"))
+                    .clicked() {
+                substate.edited_preset.options.reasoning_effort =
+                        original_options.reasoning_effort;
+            }
+
+            let mut effort = substate.edited_preset.options.effort();
+            for level in [ReasoningEffort::Low, ReasoningEffort::Medium,
+                    ReasoningEffort::High] {
+                ui.radio_value(&mut effort, level, effort_text(level));
+            }
+            if effort != substate.edited_preset.options.effort() {
+                substate.edited_preset.options.reasoning_effort = Some(effort);
+            }
+        }).response.on_hover_text(t!("reasoning_effort_tooltip"));
+    }
+
+    // --- Streaming: off waits for the whole reply ---
+    ui.horizontal(|ui| {
+        ui.label(t!("stream_reply"));
+        show_original_value(ui, if original_options.streams() {
+            t!("yes").to_string() } else { t!("no").to_string() });
+        if ui.button("⟲").on_hover_text(t!("revert_to_initial_tooltip"))
+                .clicked() {
+            substate.edited_preset.options.stream = original_options.stream;
+        }
+
+        let mut streams = substate.edited_preset.options.streams();
+        ui.radio_value(&mut streams, true, t!("yes"));
+        ui.radio_value(&mut streams, false, t!("no"));
+        if streams != substate.edited_preset.options.streams() {
+            substate.edited_preset.options.stream = Some(streams);
+        }
+    }).response.on_hover_text(t!("stream_reply_tooltip"));
+
     let seed_label = if let Some(s) = original_options.seed {
         format!("{} ({}: {}):", t!("seed"), t!("currently"), s)
     } else {
@@ -879,4 +1503,212 @@ pub fn render_common_options(
                     .map(|t| t.to_string()).unwrap_or_default();
         }
     );
+
+    number_edit(ui, &t!("max_tokens"), 80.0, &mut substate.max_tokens_entered,
+        &mut substate.is_max_tokens_valid,
+        &mut substate.edited_preset.options.max_tokens,
+        original_options.max_tokens, 1..=u32::MAX);
+    number_edit(ui, &t!("top_p_range"), 40.0, &mut substate.top_p_entered,
+        &mut substate.is_top_p_valid,
+        &mut substate.edited_preset.options.top_p,
+        original_options.top_p, 0.0..=1.0);
+    number_edit(ui, &t!("top_k"), 60.0, &mut substate.top_k_entered,
+        &mut substate.is_top_k_valid,
+        &mut substate.edited_preset.options.top_k,
+        original_options.top_k, 0..=i32::MAX);
+    number_edit(ui, &t!("frequency_penalty_range"), 40.0,
+        &mut substate.frequency_penalty_entered,
+        &mut substate.is_frequency_penalty_valid,
+        &mut substate.edited_preset.options.frequency_penalty,
+        original_options.frequency_penalty, -2.0..=2.0);
+    number_edit(ui, &t!("presence_penalty_range"), 40.0,
+        &mut substate.presence_penalty_entered,
+        &mut substate.is_presence_penalty_valid,
+        &mut substate.edited_preset.options.presence_penalty,
+        original_options.presence_penalty, -2.0..=2.0);
+
+    // --- Keep Alive: only ollama keeps models loaded between requests ---
+    if substate.edited_preset.chat_router == ChatRouter::Ollama {
+        let keep_alive_label = format!("{} ({}: {}):", t!("keep_alive"),
+            t!("currently"), original_options.keep_alive.clone()
+            .unwrap_or_else(|| t!("unset").to_string()));
+
+        validated_edit!(
+            ui,
+            &keep_alive_label,
+            40.0,
+            &mut substate.keep_alive_entered,
+            substate.is_keep_alive_valid,
+            // Validation Logic
+            {
+                let text = substate.keep_alive_entered.trim();
+                if text.is_empty() {
+                    substate.edited_preset.options.keep_alive = None;
+                    substate.is_keep_alive_valid = true;
+                } else if crate::ollama::parse_keep_alive(text).is_some() {
+                    substate.edited_preset.options.keep_alive =
+                        Some(text.to_string());
+                    substate.is_keep_alive_valid = true;
+                } else {
+                    substate.is_keep_alive_valid = false;
+                }
+            },
+            // Revert Logic
+            {
+                substate.keep_alive_entered = original_options.keep_alive
+                    .clone().unwrap_or_default();
+            }
+        );
+        ui.label(t!("keep_alive_hint")).on_hover_text(t!("keep_alive_tooltip"));
+    }
+
+    let history_limit_label = format!("{} ({}: {}):", t!("history_limit"),
+        t!("currently"), original_options.history_limit
+        .map_or(t!("unlimited").to_string(), |n| n.to_string()));
+
+    // --- History Limit: trims what is sent, the chat keeps everything ---
+    validated_edit!(
+        ui,
+        &history_limit_label,
+        80.0,
+        &mut substate.history_limit_entered,
+        substate.is_history_limit_valid,
+        // Validation Logic
+        {
+            if substate.history_limit_entered.is_empty() {
+                substate.edited_preset.options.history_limit = None;
+                substate.is_history_limit_valid = true;
+            } else {
+                match substate.history_limit_entered.parse::<u32>() {
+                    Ok(val) if val > 0 => {
+                        substate.edited_preset.options.history_limit = Some(val);
+                        substate.is_history_limit_valid = true;
+                    }
+                    _ => substate.is_history_limit_valid = false,
+                }
+            }
+        },
+        // Revert Logic
+        {
+            substate.history_limit_entered = original_options.history_limit
+                    .map(|n| n.to_string()).unwrap_or_default();
+        }
+    );
+    ui.label(t!("history_limit_hint")).on_hover_text(t!("history_limit_tooltip"));
+
+    let timeout_secs_label = format!("{} ({}: {}):", t!("timeout_secs"),
+        t!("currently"), original_options.timeout_secs
+        .map_or(t!("unset").to_string(), |n| format!("{}s", n)));
+
+    // --- Timeout: gives up on a stream that stalls ---
+    validated_edit!(
+        ui,
+        &timeout_secs_label,
+        60.0,
+        &mut substate.timeout_secs_entered,
+        substate.is_timeout_secs_valid,
+        // Validation Logic
+        {
+            if substate.timeout_secs_entered.is_empty() {
+                substate.edited_preset.options.timeout_secs = None;
+                substate.is_timeout_secs_valid = true;
+            } else {
+                match substate.timeout_secs_entered.parse::<u64>() {
+                    Ok(val) if val > 0 => {
+                        substate.edited_preset.options.timeout_secs = Some(val);
+                        substate.is_timeout_secs_valid = true;
+                    }
+                    _ => substate.is_timeout_secs_valid = false,
+                }
+            }
+        },
+        // Revert Logic
+        {
+            substate.timeout_secs_entered = original_options.timeout_secs
+                    .map(|n| n.to_string()).unwrap_or_default();
+        }
+    );
+    ui.label(t!("timeout_secs_hint")).on_hover_text(t!("timeout_secs_tooltip"));
+
+    render_response_format_edit(ui, substate, original_options);
+
+    // --- Stop Sequences: one line per entry ---
+    ui.horizontal(|ui| {
+        ui.label(format!("{} ({}: {}):", t!("stop_sequences"), t!("currently"),
+            original_options.stop_sequences().len()));
+        if ui.button("⟲").on_hover_text(t!("revert_to_initial_tooltip"))
+                .clicked() {
+            substate.edited_preset.options.stop = original_options.stop.clone();
+        }
+        if ui.button("+").on_hover_text(t!("add_stop_sequence_tooltip"))
+                .clicked() {
+            substate.edited_preset.options.stop.push(String::new());
+        }
+    });
+    let mut to_remove = None;
+    for (i, stop) in substate.edited_preset.options.stop.iter_mut()
+            .enumerate() {
+        ui.horizontal(|ui| {
+            ui.add(egui::TextEdit::singleline(stop).desired_width(150.0));
+            if ui.button("✖").on_hover_text(t!("remove_stop_sequence_tooltip"))
+                    .clicked() {
+                to_remove = Some(i);
+            }
+        });
+    }
+    if let Some(i) = to_remove {
+        substate.edited_preset.options.stop.remove(i);
+    }
+}
+
+/// "Text", "JSON" or "JSON schema"
+pub fn response_format_text(format: Option<&ResponseFormat>) -> String {
+    match format {
+        None => t!("response_format_text"),
+        Some(ResponseFormat::JsonObject) => t!("response_format_json"),
+        Some(ResponseFormat::JsonSchema(_)) => t!("response_format_schema"),
+    }.to_string()
+}
+
+// --- Response Format: free text, any json, or json following a schema ---
+fn render_response_format_edit(ui: &mut egui::Ui,
+        substate: &mut PresetEditorState, original_options: &ModelOptions) {
+    ui.horizontal(|ui| {
+        ui.label(t!("response_format"));
+        show_original_value(ui, response_format_text(
+            original_options.response_format.as_ref()));
+        if ui.button("⟲").on_hover_text(t!("revert_to_initial_tooltip"))
+                .clicked() {
+            substate.edited_preset.options.response_format =
+                original_options.response_format.clone();
+        }
+
+        let format = &mut substate.edited_preset.options.response_format;
+        if ui.radio(format.is_none(), response_format_text(None)).clicked() {
+            *format = None;
+        }
+        let json = Some(ResponseFormat::JsonObject);
+        if ui.radio(*format == json, response_format_text(json.as_ref()))
+                .clicked() {
+            *format = json;
+        }
+        let is_schema = matches!(format, Some(ResponseFormat::JsonSchema(_)));
+        let schema = ResponseFormat::JsonSchema(String::new());
+        if ui.radio(is_schema, response_format_text(Some(&schema))).clicked()
+                && !is_schema {
+            *format = Some(schema);
+        }
+    }).response.on_hover_text(t!("response_format_tooltip"));
+
+    let is_valid = substate.edited_preset.options.is_response_format_valid();
+    if let Some(ResponseFormat::JsonSchema(text)) =
+            &mut substate.edited_preset.options.response_format {
+        let color = if is_valid { strong_color() } else { err_color() };
+        ui.add(egui::TextEdit::multiline(text)
+            .code_editor()
+            .text_color(color)
+            .desired_rows(6)
+            .desired_width(f32::INFINITY)
+            .hint_text(t!("json_schema_hint")));
+    }
 }
@@ -0,0 +1,194 @@
+use eframe::egui::{self, RichText, TextEdit};
+use rust_i18n::t;
+
+use std::sync::atomic::Ordering;
+
+use crate::common::{DEFAULT_PRESET_ID, OLLAMA_HOST, OllamaHost, PresetSelection, Presets};
+use crate::db::set_default_preset;
+use crate::gui::bottom_panel::preset_combo_box;
+use crate::ollama::ollama_fetch_models;
+use super::State;
+
+pub fn ui_settings(ctx: &egui::Context, state: &mut State) {
+    let mut apply_host = false;
+    egui::Window::new(t!("settings"))
+        .collapsible(false)
+        .open(&mut state.show_settings)
+        .default_width(400.)
+        .show(ctx, |ui| {
+        if state.is_modal_open {
+            ui.disable();
+        }
+
+        // --- Theme ---
+        ui.label(RichText::new(t!("settings_theme")).strong());
+        let mut theme = ctx.options(|o| o.theme_preference);
+        ui.horizontal(|ui| {
+            ui.radio_value(&mut theme, egui::ThemePreference::System,
+                t!("settings_theme_system"));
+            ui.radio_value(&mut theme, egui::ThemePreference::Dark,
+                t!("settings_theme_dark"));
+            ui.radio_value(&mut theme, egui::ThemePreference::Light,
+                t!("settings_theme_light"));
+        });
+        if theme != ctx.options(|o| o.theme_preference) {
+            ctx.set_theme(theme);
+        }
+
+        ui.add_space(10.0);
+        ui.separator();
+
+        // --- Language ---
+        ui.label(RichText::new(t!("settings_language")).strong());
+        let mut language = state.perma.app_language.lock().unwrap().clone();
+        ui.horizontal_wrapped(|ui| {
+            for (locale, name) in crate::gui::app_languages() {
+                ui.radio_value(&mut language, locale, name);
+            }
+        });
+        if *state.perma.app_language.lock().unwrap() != language {
+            crate::gui::set_app_language(&state.perma, &language);
+        }
+
+        ui.add_space(10.0);
+        ui.separator();
+
+        // --- Ollama Server ---
+        ui.label(RichText::new(t!("ollama_server")).strong());
+        ui.horizontal(|ui| {
+            ui.label(t!("ollama_host_label"));
+            ui.add(TextEdit::singleline(&mut state.ollama_host_entered)
+                .hint_text("http://127.0.0.1")
+                .desired_width(200.0));
+            ui.label(t!("ollama_port_label"));
+            ui.add(TextEdit::singleline(&mut state.ollama_port_entered)
+                .hint_text("11434")
+                .desired_width(60.0));
+            if ui.button(t!("apply_ollama_host_btn"))
+                    .on_hover_text(t!("apply_ollama_host_tooltip")).clicked() {
+                apply_host = true;
+            }
+        });
+
+        ui.add_space(10.0);
+        ui.separator();
+
+        // --- Chats ---
+        ui.checkbox(&mut state.perma.auto_title.lock().unwrap(),
+            t!("settings_auto_title"))
+            .on_hover_text(t!("settings_auto_title_tooltip"));
+        ui.checkbox(&mut state.perma.expand_reasoning.lock().unwrap(),
+            t!("settings_expand_reasoning"))
+            .on_hover_text(t!("settings_expand_reasoning_tooltip"));
+        // kept in the Sandbox, so left alone in read-only mode
+        ui.add_enabled_ui(!*state.perma.readonly.lock().unwrap(), |ui| {
+            render_default_preset(ui, &state.db_conn, &state.presets,
+                &mut state.error_msg);
+        });
+        ui.checkbox(&mut state.perma.abort_all_on_error.lock().unwrap(),
+            t!("settings_abort_all_on_error"))
+            .on_hover_text(t!("settings_abort_all_on_error_tooltip"));
+        ui.checkbox(&mut state.perma.compact_chat.lock().unwrap(),
+            t!("settings_compact_chat"))
+            .on_hover_text(t!("settings_compact_chat_tooltip"));
+        ui.checkbox(&mut state.perma.stack_agents.lock().unwrap(),
+            t!("settings_stack_agents"))
+            .on_hover_text(t!("settings_stack_agents_tooltip"));
+        render_column_widths(ui,
+            &mut state.perma.column_widths.lock().unwrap());
+        ui.horizontal(|ui| {
+            ui.label(t!("settings_prompt_char_limit"));
+            ui.add(egui::DragValue::new(
+                &mut *state.perma.prompt_char_limit.lock().unwrap())
+                .range(0..=10_000_000)
+                .speed(100.0));
+        }).response.on_hover_text(t!("settings_prompt_char_limit_tooltip"));
+
+        // --- Model Lists ---
+        ui.horizontal(|ui| {
+            ui.label(t!("models_stale_days_label"));
+            ui.add(egui::DragValue::new(
+                &mut *state.perma.models_stale_days.lock().unwrap())
+                .range(1..=365));
+        }).response.on_hover_text(t!("models_cached_tooltip"));
+    });
+
+    if apply_host {
+        apply_ollama_host(ctx, state);
+    }
+}
+
+// the preset new agents start with, kept in the sandbox like the presets
+fn render_default_preset(ui: &mut egui::Ui, conn: &rusqlite::Connection,
+        presets: &Presets, error_msg: &mut Option<String>) {
+    let id = DEFAULT_PRESET_ID.load(Ordering::Relaxed);
+    let mut selection = PresetSelection::from_id(id, presets);
+    let mut changed = false;
+    ui.horizontal(|ui| {
+        ui.label(t!("settings_default_preset"));
+        changed = preset_combo_box(ui, "default_preset_select",
+            &mut selection, presets);
+        if id != 0 && ui.button("✖")
+                .on_hover_text(t!("settings_default_preset_clear")).clicked() {
+            selection.id = 0;
+            changed = true;
+        }
+    }).response.on_hover_text(t!("settings_default_preset_tooltip"));
+
+    if changed {
+        match set_default_preset(conn, selection.id) {
+            Ok(()) => DEFAULT_PRESET_ID.store(selection.id, Ordering::Relaxed),
+            Err(e) => *error_msg = Some(e.to_string()),
+        }
+    }
+}
+
+// how narrow and how wide the side by side replies may get: narrow columns
+// fit more agents on a wide screen
+fn render_column_widths(ui: &mut egui::Ui, (min, max): &mut (f32, f32)) {
+    let range = crate::gui::COLUMN_WIDTH_RANGE;
+    ui.horizontal(|ui| {
+        ui.label(t!("settings_column_widths"));
+        ui.add(egui::DragValue::new(min).range(range.clone()).speed(5.0)
+            .suffix(" px"));
+        ui.label("–");
+        ui.add(egui::DragValue::new(max).range(*min..=*range.end()).speed(5.0)
+            .suffix(" px"));
+        if ui.button("↺").on_hover_text(t!("settings_column_widths_reset"))
+                .clicked() {
+            (*min, *max) = crate::gui::DEFAULT_COLUMN_WIDTHS;
+        }
+    }).response.on_hover_text(t!("settings_column_widths_tooltip"));
+}
+
+// validates the entered host, then refetches the installed models from it in
+// the background so an unreachable server is reported without freezing the UI
+fn apply_ollama_host(ctx: &egui::Context, state: &mut State) {
+    let Ok(port) = state.ollama_port_entered.trim().parse::<u16>() else {
+        state.error_msg = Some(format!("{}: '{}'", t!("invalid_ollama_port"),
+            state.ollama_port_entered));
+        return;
+    };
+    let host = OllamaHost {
+        host: state.ollama_host_entered.trim().to_string(),
+        port,
+    };
+    if let Err(e) = host.url() {
+        state.error_msg = Some(e.to_string());
+        return;
+    }
+    *OLLAMA_HOST.write().unwrap() = host.clone();
+
+    let tx = state.ollama_models_tx.clone();
+    let ctx = ctx.clone();
+    state.perma.rt.spawn(async move {
+        let fetched = tokio::time::timeout(std::time::Duration::from_secs(5),
+            ollama_fetch_models()).await
+            .map_err(|e| e.to_string())
+            .and_then(|res| res.map_err(|e| e.to_string()))
+            .map_err(|e| format!("{} {}:{}: {}",
+                t!("ollama_unreachable"), host.host, host.port, e));
+        let _ = tx.send(fetched);
+        ctx.request_repaint();
+    });
+}
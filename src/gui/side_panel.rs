@@ -1,4 +1,4 @@
-use crate::{common::Chat, db::{delete_chat, export_chat_to_markdown, fetch_chat}, gui::{State, split_button}};
+use crate::{common::{Chat, DbChat}, db::{chat_stats, delete_chat, duplicate_chat, export_chat_to_markdown, fetch_chat, mod_chat_pinned, purge_chat, restore_chat}, gui::{State, split_button}};
 use rust_i18n::t;
 use split_button::SplitButton;
 
@@ -20,7 +20,7 @@ pub fn ui_side_panel(ctx: &egui::Context, state: &mut State) {
             ui.horizontal(|ui| {
                 let response = ui.add(
                     egui::TextEdit::singleline(&mut state.search_query)
-                        .hint_text("Search chats...")
+                        .hint_text(t!("search_chats_hint"))
                         .desired_width(ui.available_width() - 80.0)
                 );
 
@@ -66,17 +66,14 @@ pub fn ui_side_panel(ctx: &egui::Context, state: &mut State) {
                     .show(ui);
 
                 if main_clicked {
-                    let temp_id = get_temp_id(state);
-                    let mut new_chat = Chat::default();
-                    new_chat.id = temp_id;
-                    state.open_chats.insert(temp_id, new_chat);
-                    crate::gui::panes::open_chat_in_tab(state, temp_id);
+                    start_new_chat(state);
                 }
 
                 if arrow_clicked {
                     let temp_id = get_temp_id(state);
                     let mut new_chat = Chat::default();
                     new_chat.id = temp_id;
+                    load_system_prompt(&new_chat, &mut state.bottom_panel_state);
                     state.open_chats.insert(temp_id, new_chat);
                     crate::gui::panes::open_chat_in_right_pane(state, temp_id);
                 }
@@ -92,6 +89,7 @@ pub fn ui_side_panel(ctx: &egui::Context, state: &mut State) {
                         agent.id = 0;
                         agent.msg_ids.clear();
                     }
+                    load_system_prompt(&template, &mut state.bottom_panel_state);
                     state.open_chats.insert(temp_id, template);
                     crate::gui::panes::open_chat_in_tab(state, temp_id);
                 }
@@ -128,12 +126,27 @@ pub fn ui_side_panel(ctx: &egui::Context, state: &mut State) {
             let mut to_delete_chat_id = 0;
             let mut clicked_chat_id = None; // 1. Create a temporary holder
             let mut right_clicked_chat_id: Option<i64> = None; // track right arrow clicks
-
-            // Iterate through chats
-            for db_chat in &mut state.db_chats {
+            let mut export_chat_id: Option<i64> = None;
+            let mut duplicate_chat_id: Option<i64> = None;
+            let mut pin_chat_id: Option<(i64, bool)> = None;
+            let mut details_chat_id: Option<i64> = None;
+            // read-only keeps only what leaves the Sandbox as it is
+            let readonly = *state.perma.readonly.lock().unwrap();
+
+            // Iterate through chats, branches nested under their parent
+            let mut last_group = None;
+            for (ind, depth) in chat_tree_order(&state.db_chats) {
+                let db_chat = &state.db_chats[ind];
+                // branches stay with their parent, whatever their own date
+                let group = date_group(db_chat);
+                if depth == 0 && last_group != Some(group) {
+                    last_group = Some(group);
+                    ui.label(egui::RichText::new(group.label()).small().weak());
+                }
                 ui.horizontal_top(|ui| {
                     ui.set_max_height(20.0);
                     ui.spacing_mut().item_spacing.x = 2.0;
+                    ui.add_space(depth as f32 * 12.0);
 
                     let is_selected = state.active_chat_id == Some(db_chat.id);
 
@@ -144,35 +157,38 @@ pub fn ui_side_panel(ctx: &egui::Context, state: &mut State) {
                         ui.menu_button("🔧", |ui| {
                             ui.set_min_width(80.0);
 
-                            if ui.button(egui::RichText::new(t!("rename_chat_btn"))).on_hover_text(egui::RichText::new(t!("rename_chat_tooltip")).heading()).clicked() {
+                            if ui.add_enabled(!readonly, egui::Button::new(t!("rename_chat_btn"))).on_hover_text(egui::RichText::new(t!("rename_chat_tooltip")).heading()).clicked() {
                                 state.chat_to_rename = Some(db_chat.id);
                                 state.chat_rename_buffer = db_chat.title.split('\n').next().unwrap_or(&db_chat.title).trim().to_string();
                                 ui.close();
                             }
 
+                            if ui.add_enabled(!readonly, egui::Button::new(t!("duplicate_chat_btn"))).on_hover_text(egui::RichText::new(t!("duplicate_chat_tooltip")).heading()).clicked() {
+                                duplicate_chat_id = Some(db_chat.id);
+                                ui.close();
+                            }
+
+                            let pin_text = if db_chat.pinned { t!("unpin_chat_btn") } else { t!("pin_chat_btn") };
+                            if ui.add_enabled(!readonly, egui::Button::new(pin_text)).on_hover_text(egui::RichText::new(t!("pin_chat_tooltip")).heading()).clicked() {
+                                pin_chat_id = Some((db_chat.id, !db_chat.pinned));
+                                ui.close();
+                            }
+
+                            if ui.button(t!("chat_details_btn")).on_hover_text(egui::RichText::new(t!("chat_details_tooltip")).heading()).clicked() {
+                                details_chat_id = Some(db_chat.id);
+                                ui.close();
+                            }
+
                             ui.separator();
 
                             if ui.button(egui::RichText::new(t!("export_chat_btn"))).on_hover_text(egui::RichText::new(t!("export_chat_tooltip")).heading()).clicked() {
-                                if let Ok(markdown) = export_chat_to_markdown(&state.db_conn, db_chat.id, &state.presets) {
-                                    // Trigger the native egui dialog for saving
-                                    state.pending_file_dialog_op = Some(crate::common::FileOp::ExportChat);
-                                    state.pending_export_content = Some(markdown);
-
-                                    // Create a safe default filename based on the chat's title
-                                    let safe_title = db_chat.title.replace(|c: char| !c.is_alphanumeric() && c != ' ' && c != '-', "_");
-                                    let default_name = format!("{}.md", safe_title); // <--- Create the String
-
-                                    state.file_dialog = egui_file_dialog::FileDialog::new()
-                                        .default_file_name(&default_name) // <--- Pass it as a reference (&str)
-                                        .add_file_filter("Markdown", std::sync::Arc::new(|p: &std::path::Path| p.extension().is_some_and(|ext| ext == "md")));
-                                    state.file_dialog.save_file();
-                                }
+                                export_chat_id = Some(db_chat.id);
                                 ui.close();
                             }
 
                             ui.separator();
 
-                            if ui.button(egui::RichText::new(t!("delete_chat_btn")).color(ui.visuals().error_fg_color)).on_hover_text(egui::RichText::new(t!("delete_chat_tooltip")).heading().color(ui.visuals().error_fg_color)).clicked() {
+                            if ui.add_enabled(!readonly, egui::Button::new(egui::RichText::new(t!("delete_chat_btn")).color(ui.visuals().error_fg_color))).on_hover_text(egui::RichText::new(t!("delete_chat_tooltip")).heading().color(ui.visuals().error_fg_color)).clicked() {
                                 if let Ok(_) = delete_chat(&state.db_conn, db_chat.id) {
                                     if state.active_chat_id == Some(db_chat.id) {
                                         state.open_chats.remove(&db_chat.id);
@@ -196,7 +212,19 @@ pub fn ui_side_panel(ctx: &egui::Context, state: &mut State) {
                             }
                         }
 
-                        // 3. The Unified Split Button
+                        if db_chat.pinned {
+                            ui.label("📌");
+                        }
+
+                        // 3. Running cost of the active chat
+                        if let Some(chat) = state.open_chats.get(&db_chat.id)
+                                .filter(|_| is_selected) {
+                            let cost = chat.total_cost();
+                            ui.label(egui::RichText::new(format!("${:.2}", cost)).weak())
+                                .on_hover_text(format!("{}: ${:.6}", t!("chat_total_cost_tooltip"), cost));
+                        }
+
+                        // 4. The Unified Split Button
                         // We pass the full available width to our custom component, which handles the hover split automatically.
                         let available_width = ui.available_width();
                         let display_title = db_chat.title.split('\n').next().unwrap_or(&db_chat.title).trim();
@@ -220,26 +248,59 @@ pub fn ui_side_panel(ctx: &egui::Context, state: &mut State) {
                 });
             }
 
+            if let Some((chat_id, pinned)) = pin_chat_id {
+                match mod_chat_pinned(&state.db_conn, chat_id, pinned) {
+                    Ok(()) => crate::gui::reload_db_chats(&state.db_conn, &mut state.db_chats),
+                    Err(e) => eprintln!("Error pinning chat {}: {}", chat_id, e),
+                }
+            }
+
+            if let Some(chat_id) = details_chat_id {
+                let title = state.db_chats.iter().find(|c| c.id == chat_id)
+                    .map(|c| c.title.clone()).unwrap_or_default();
+                match chat_stats(&state.db_conn, chat_id) {
+                    Ok(stats) => state.chat_details = Some((title, stats)),
+                    Err(e) => eprintln!("Error reading chat {} stats: {}", chat_id, e),
+                }
+            }
+
+            if let Some(chat_id) = duplicate_chat_id {
+                match duplicate_chat(&state.db_conn, chat_id,
+                        &t!("chat_copy_suffix")) {
+                    Ok(new_chat_id) => {
+                        crate::gui::reload_db_chats(&state.db_conn, &mut state.db_chats);
+                        open_saved_chat(state, new_chat_id);
+                    }
+                    Err(e) => eprintln!("Error duplicating chat {}: {}", chat_id, e),
+                }
+            }
+
+            if let Some(chat_id) = export_chat_id {
+                let title = state.db_chats.iter().find(|c| c.id == chat_id)
+                    .map(|c| c.title.clone()).unwrap_or_default();
+                if let Ok(markdown) = export_chat_to_markdown(&state.db_conn, chat_id, &state.presets) {
+                    // Trigger the native egui dialog for saving
+                    crate::gui::start_markdown_export(state, &title, markdown);
+                }
+            }
+
             // Cleanup deleted chats after the loop
             if to_delete_chat_id != 0 {
                 state.db_chats.retain(|c| c.id != to_delete_chat_id);
+                crate::gui::reload_trash_chats(&state.db_conn,
+                    &mut state.trash_chats);
             }
 
+            render_trash(ui, state);
+
             // 3. Handle the click outside the loop safely!
             if let Some(chat_id) = clicked_chat_id {
-                if !state.open_chats.contains_key(&chat_id) {
-                    let loaded_chat = fetch_chat(&state.db_conn, chat_id, &state.presets).unwrap_or_default();
-                    state.open_chats.insert(chat_id, loaded_chat);
-                }
-                crate::gui::panes::open_chat_in_tab(state, chat_id);
+                open_saved_chat(state, chat_id);
             }
 
             // Handle opening in the right pane!
             if let Some(chat_id) = right_clicked_chat_id {
-                if !state.open_chats.contains_key(&chat_id) {
-                    let loaded_chat = fetch_chat(&state.db_conn, chat_id, &state.presets).unwrap_or_default();
-                    state.open_chats.insert(chat_id, loaded_chat);
-                }
+                load_saved_chat(state, chat_id);
                 crate::gui::panes::open_chat_in_right_pane(state, chat_id);
             }
         });
@@ -248,6 +309,111 @@ pub fn ui_side_panel(ctx: &egui::Context, state: &mut State) {
     // --- RENAME POPUP WINDOW ---
     // This draws a small window on top of everything if a chat is being renamed
     render_rename_window(ctx, state);
+    render_details_window(ctx, state);
+}
+
+// deleted chats at the bottom of the list, to restore or delete for good
+fn render_trash(ui: &mut egui::Ui, state: &mut State) {
+    if state.trash_chats.is_empty() {
+        return;
+    }
+    let mut restore_chat_id = None;
+    let mut purge_chat_id = None;
+    let readonly = *state.perma.readonly.lock().unwrap();
+
+    ui.add_space(10.0);
+    egui::CollapsingHeader::new(format!("{} ({})", t!("trash_label"),
+            state.trash_chats.len()))
+        .id_salt("chat_trash")
+        .show(ui, |ui| {
+        for db_chat in &state.trash_chats {
+            ui.horizontal(|ui| {
+                ui.spacing_mut().item_spacing.x = 2.0;
+                if ui.add_enabled(!readonly, egui::Button::new("♻"))
+                        .on_hover_text(t!("restore_chat_tooltip")).clicked() {
+                    restore_chat_id = Some(db_chat.id);
+                }
+                // the menu doubles as a confirmation, there is no undo
+                ui.menu_button("🗑", |ui| {
+                    if readonly {
+                        ui.disable();
+                    }
+                    if ui.button(egui::RichText::new(t!("purge_chat_btn"))
+                            .color(ui.visuals().error_fg_color))
+                            .on_hover_text(t!("purge_chat_tooltip")).clicked() {
+                        purge_chat_id = Some(db_chat.id);
+                        ui.close();
+                    }
+                });
+                let title = db_chat.title.split('\n').next()
+                    .unwrap_or(&db_chat.title).trim();
+                ui.label(egui::RichText::new(title).weak())
+                    .on_hover_text(&db_chat.title);
+            });
+        }
+    });
+
+    if let Some(chat_id) = restore_chat_id {
+        match restore_chat(&state.db_conn, chat_id) {
+            Ok(()) => crate::gui::reload_db_chats(&state.db_conn,
+                &mut state.db_chats),
+            Err(e) => eprintln!("Error restoring chat {}: {}", chat_id, e),
+        }
+    }
+    if let Some(chat_id) = purge_chat_id {
+        match purge_chat(&state.db_conn, chat_id) {
+            // its branches now hang off another chat
+            Ok(()) => crate::gui::reload_db_chats(&state.db_conn,
+                &mut state.db_chats),
+            Err(e) => eprintln!("Error deleting chat {}: {}", chat_id, e),
+        }
+    }
+    if restore_chat_id.is_some() || purge_chat_id.is_some() {
+        crate::gui::reload_trash_chats(&state.db_conn, &mut state.trash_chats);
+    }
+}
+
+// Opens an empty chat with a temporary id in a new tab
+pub fn start_new_chat(state: &mut State) {
+    let mut temp_id = 0;
+    while state.open_chats.contains_key(&temp_id) { temp_id -= 1; }
+    let new_chat = Chat {
+        id: temp_id,
+        ..Default::default()
+    };
+    load_system_prompt(&new_chat, &mut state.bottom_panel_state);
+    state.open_chats.insert(temp_id, new_chat);
+    crate::gui::panes::open_chat_in_tab(state, temp_id);
+}
+
+// Loads a saved chat from the database unless it is already open and shows
+// its system prompt
+fn load_saved_chat(state: &mut State, chat_id: i64) {
+    if !state.open_chats.contains_key(&chat_id) {
+        let loaded_chat = fetch_chat(&state.db_conn, chat_id, &state.presets).unwrap_or_default();
+        state.open_chats.insert(chat_id, loaded_chat);
+    }
+    if let Some(chat) = state.open_chats.get(&chat_id) {
+        load_system_prompt(chat, &mut state.bottom_panel_state);
+    }
+}
+
+pub fn open_saved_chat(state: &mut State, chat_id: i64) {
+    load_saved_chat(state, chat_id);
+    crate::gui::panes::open_chat_in_tab(state, chat_id);
+}
+
+// Shows the chat's system prompt in the bottom panel, or hides the system
+// prompt column if the chat has none
+fn load_system_prompt(chat: &Chat,
+        bottom_state: &mut crate::gui::bottom_panel::BottomPanelState) {
+    if let Some(sys_msg) = chat.system_msg() {
+        bottom_state.system_prompt_edited = sys_msg.content.clone();
+        bottom_state.show_system_prompt = true;
+    } else {
+        bottom_state.system_prompt_edited.clear();
+        bottom_state.show_system_prompt = false;
+    }
 }
 
 // Helper function to extract prompts and re-attach files
@@ -328,14 +494,14 @@ fn render_rename_window(ctx: &egui::Context, state: &mut State) {
         let mut open = true;
 
         // Center the window and fix the size
-        egui::Window::new("Rename Chat")
+        egui::Window::new(t!("rename_chat_title"))
             .open(&mut open)
             .collapsible(false)
             .resizable(false)
             .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
             .show(ctx, |ui| {
 
-                ui.label("Enter new name:");
+                ui.label(t!("rename_chat_label"));
 
                 // Text input
                 let response = ui.text_edit_singleline(
@@ -351,10 +517,10 @@ fn render_rename_window(ctx: &egui::Context, state: &mut State) {
                 ui.add_space(10.0);
 
                 ui.horizontal(|ui| {
-                    if ui.button("Cancel").clicked() {
+                    if ui.button(t!("cancel_btn")).clicked() {
                         state.chat_to_rename = None;
                     }
-                    if ui.button("Save").clicked() {
+                    if ui.button(t!("preset_save_btn")).clicked() {
                         save_rename(state, chat_id);
                     }
                 });
@@ -367,6 +533,59 @@ fn render_rename_window(ctx: &egui::Context, state: &mut State) {
     }
 }
 
+// a read-only summary of a chat, opened from its wrench menu
+fn render_details_window(ctx: &egui::Context, state: &mut State) {
+    let Some((title, stats)) = &state.chat_details else {
+        return;
+    };
+    let mut open = true;
+    egui::Window::new(t!("chat_details_title"))
+        .open(&mut open)
+        .collapsible(false)
+        .resizable(false)
+        .show(ctx, |ui| {
+            ui.strong(title.split('\n').next().unwrap_or(title).trim());
+            ui.add_space(6.0);
+            egui::Grid::new("chat_details_grid")
+                .num_columns(2)
+                .striped(true)
+                .show(ui, |ui| {
+                    ui.label(t!("chat_details_msgs"));
+                    ui.label(stats.msgs.to_string());
+                    ui.end_row();
+
+                    ui.label(t!("chat_details_agents"));
+                    ui.label(stats.agents.to_string());
+                    ui.end_row();
+
+                    ui.label(t!("chat_details_models"));
+                    ui.label(if stats.models.is_empty() {
+                        "-".to_string()
+                    } else {
+                        stats.models.join("\n")
+                    });
+                    ui.end_row();
+
+                    ui.label(t!("chat_details_created"));
+                    let created = stats.ts_created.as_deref()
+                        .and_then(|ts| chrono::NaiveDateTime::parse_from_str(
+                            ts, "%Y-%m-%d %H:%M:%S").ok())
+                        .map(|ts| ts.and_utc().with_timezone(&chrono::Local)
+                            .format("%Y-%m-%d %H:%M").to_string())
+                        .unwrap_or_else(|| "-".to_string());
+                    ui.label(created);
+                    ui.end_row();
+
+                    ui.label(t!("chat_details_cost"));
+                    ui.label(format!("${:.6}", stats.cost));
+                    ui.end_row();
+                });
+        });
+    if !open {
+        state.chat_details = None;
+    }
+}
+
 // Helper to save changes to DB and State
 fn save_rename(state: &mut State, chat_id: i64) {
     if let Some(target_db_chat) =
@@ -393,3 +612,69 @@ fn save_rename(state: &mut State, chat_id: i64) {
         state.chat_to_rename = None;
     }
 }
+
+// side panel sections, the chat list is already sorted pinned first, then
+// newest first
+#[derive(Clone, Copy, PartialEq)]
+enum DateGroup {
+    Pinned,
+    Today,
+    Yesterday,
+    LastWeek,
+    Older,
+}
+
+impl DateGroup {
+    fn label(self) -> String {
+        match self {
+            DateGroup::Pinned => t!("chats_pinned"),
+            DateGroup::Today => t!("chats_today"),
+            DateGroup::Yesterday => t!("chats_yesterday"),
+            DateGroup::LastWeek => t!("chats_last_week"),
+            DateGroup::Older => t!("chats_older"),
+        }.to_string()
+    }
+}
+
+// which section a chat goes in, by the local date it was created on
+fn date_group(chat: &DbChat) -> DateGroup {
+    if chat.pinned {
+        return DateGroup::Pinned;
+    }
+    let Some(created) = chat.ts_created.as_deref().and_then(|ts|
+            chrono::NaiveDateTime::parse_from_str(ts, "%Y-%m-%d %H:%M:%S").ok()) else {
+        return DateGroup::Older;
+    };
+    let created = created.and_utc().with_timezone(&chrono::Local).date_naive();
+    match (chrono::Local::now().date_naive() - created).num_days() {
+        ..=0 => DateGroup::Today,
+        1 => DateGroup::Yesterday,
+        2..=7 => DateGroup::LastWeek,
+        _ => DateGroup::Older,
+    }
+}
+
+// (index into chats, nesting depth) in display order: every chat is followed
+// by its branches. Branches whose parent is gone are shown at the top level.
+pub fn chat_tree_order(chats: &[DbChat]) -> Vec<(usize, usize)> {
+    fn push_branches(chats: &[DbChat], parent: i64, depth: usize,
+            order: &mut Vec<(usize, usize)>) {
+        for (ind, chat) in chats.iter().enumerate() {
+            if chat.parent == Some(parent) {
+                order.push((ind, depth));
+                push_branches(chats, chat.id, depth + 1, order);
+            }
+        }
+    }
+
+    let mut order = Vec::with_capacity(chats.len());
+    for (ind, chat) in chats.iter().enumerate() {
+        let is_root = chat.parent
+            .is_none_or(|p| !chats.iter().any(|c| c.id == p));
+        if is_root {
+            order.push((ind, 0));
+            push_branches(chats, chat.id, 1, &mut order);
+        }
+    }
+    order
+}
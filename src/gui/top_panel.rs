@@ -1,7 +1,7 @@
 use egui::{Color32, RichText};
 use rust_i18n::t;
 
-use crate::{common::{FileOp, FileOpMsg, err_color}, db::reset_sandbox_db, gui::State, mybtn};
+use crate::{common::{FileOp, err_color}, db::{backup_sandbox, chat_to_markdown, count_sandbox_contents, export_sandbox_json}, gui::State, mybtn};
 
 pub fn ui_top_panel(ctx: &egui::Context, state: &mut State) {
     egui::TopBottomPanel::top("top_panel").show(ctx, |ui| {
@@ -10,27 +10,30 @@ pub fn ui_top_panel(ctx: &egui::Context, state: &mut State) {
         }
         egui::MenuBar::new().ui(ui, |ui| {
             let app_language = state.perma.app_language.lock().unwrap().clone();
-            let (lang_label, target_lang) = if app_language == "ru" {
-                ("ru", "en")
-            } else {
-                ("en", "ru")
-            };
-            if ui.button(lang_label)
-                .on_hover_text(egui::RichText::new(
+            ui.menu_button(&app_language, |ui| {
+                for (locale, name) in crate::gui::app_languages() {
+                    if ui.selectable_label(locale == app_language, name).clicked() {
+                        crate::gui::set_app_language(&state.perma, &locale);
+                        ui.close();
+                    }
+                }
+            }).response.on_hover_text(egui::RichText::new(
                     "Switch Language / Переключить язык")
                     .strong()
                     .heading()
+                );
+            ui.separator(); // Visual spacer
+
+            // Settings Button
+            if ui.add(egui::Button::new("⚙").selected(state.show_settings))
+                .on_hover_text(
+                    egui::RichText::new(t!("menu_settings_btn_tooltip"))
+                    .strong()
+                    .heading()
                 )
-                .clicked()
-            {
-                // 1. Update the state variable (for saving to disk later)
-                *state.perma.app_language.lock().unwrap() =
-                        target_lang.to_string();
-
-                // 2. Update the live locale immediately
-                rust_i18n::set_locale(target_lang);
+                .clicked() {
+                state.show_settings = !state.show_settings;
             }
-            ui.separator(); // Visual spacer
 
             // API Keys Button
             let api_btn = egui::Button::new(t!("menu_api_keys_btn"))
@@ -79,6 +82,7 @@ pub fn ui_top_panel(ctx: &egui::Context, state: &mut State) {
             ui.colored_label(ui.visuals().code_bg_color,"|");
 
             // Sandbox Menu
+            let readonly = *state.perma.readonly.lock().unwrap();
             ui.menu_button(t!("menu_sandbox"), |ui| {
 
                 // Save As Button
@@ -101,25 +105,83 @@ pub fn ui_top_panel(ctx: &egui::Context, state: &mut State) {
                     state.file_dialog.save_file();
                 }
 
+                // Backup Button
+                if mybtn!(ui, "menu_sandbox_backup_btn") {
+                    match backup_sandbox(&state.db_conn, &state.sandbox) {
+                        Ok(path) => state.last_backup = Some(path),
+                        Err(e) => {
+                            state.error_msg = Some(format!(
+                                "Could not back up the Sandbox: {}", e));
+                            ui.close();
+                        }
+                    }
+                }
+                if let Some(name) = state.last_backup.as_ref()
+                        .and_then(|path| path.file_name()) {
+                    ui.label(egui::RichText::new(format!("{} {}",
+                        t!("last_backup"), name.to_string_lossy())).small().weak());
+                }
+
+                ui.separator();
+
+                // Export Button: the whole sandbox as one JSON file
+                if mybtn!(ui, "menu_sandbox_export_btn") {
+                    ui.close();
+                    match export_sandbox_json(&state.db_conn) {
+                        Ok(json) => {
+                            let stem = state.sandbox.file_stem()
+                                .map(|s| s.to_string_lossy().to_string())
+                                .unwrap_or_else(|| "sandbox".to_string());
+                            state.pending_file_dialog_op = Some(FileOp::ExportSandbox);
+                            state.pending_export_content = Some(json);
+                            state.file_dialog = egui_file_dialog::FileDialog::new()
+                                .default_file_name(&format!("{}.json", stem))
+                                .add_file_filter("Inforno Sandbox Archive", std::sync::Arc::new(|p: &std::path::Path| p.extension().is_some_and(|ext| ext == "json")));
+                            state.file_dialog.save_file();
+                        }
+                        Err(e) => {
+                            state.error_msg = Some(format!(
+                                "Could not export the Sandbox: {}", e));
+                            state.is_modal_open = true;
+                        }
+                    }
+                }
+
+                // Import Button: adds the chats and presets of an archive
+                if ui.add_enabled_ui(!readonly,
+                        |ui| mybtn!(ui, "menu_sandbox_import_btn")).inner {
+                    ui.close();
+                    state.pending_file_dialog_op = Some(FileOp::ImportSandbox);
+                    state.file_dialog = egui_file_dialog::FileDialog::new()
+                        .add_file_filter("Inforno Sandbox Archive", std::sync::Arc::new(|p: &std::path::Path| p.extension().is_some_and(|ext| ext == "json")));
+                    state.file_dialog.pick_file();
+                }
+
+                // Export All Chats Button: one Markdown file per chat
+                if mybtn!(ui, "menu_sandbox_export_all_chats_btn") {
+                    ui.close();
+                    state.pending_file_dialog_op = Some(FileOp::ExportAllChats);
+                    state.file_dialog = egui_file_dialog::FileDialog::new();
+                    state.file_dialog.pick_directory();
+                }
+
                 ui.add_space(10.0);
                 ui.separator();
                 ui.add_space(10.0);
 
                 // Clear Button
-                if ui.button(
+                if ui.add_enabled(!readonly, egui::Button::new(
                     egui::RichText::new(t!("menu_sandbox_clear"))
                     .color(ui.visuals().error_fg_color)
-                ).clicked() {
-                    let _ = reset_sandbox_db(&state.db_conn);
-                    let tx_clone = state.op_tx.clone();
-                    let _ = tx_clone.send(FileOpMsg {
-                        op: FileOp::Clear,
-                        cancelled: false,
-                        path: None,
-                        attachments: None,
-                        left_content: None,
-                        right_content: None,
-                    });
+                )).clicked() {
+                    ui.close();
+                    // the actual reset happens after confirmation in MyApp
+                    match count_sandbox_contents(&state.db_conn) {
+                        Ok(counts) => state.confirm_clear = Some(counts),
+                        Err(e) => state.error_msg = Some(format!(
+                            "Could not read the sandbox: {}", e)),
+                    }
+                    state.is_modal_open = true;
                 }
             }).response.on_hover_text(
                 egui::RichText::new(t!("menu_sandbox_tooltip"))
@@ -173,7 +235,64 @@ pub fn ui_top_panel(ctx: &egui::Context, state: &mut State) {
                 }
             });
 
+            ui.menu_button(t!("menu_export_chat"), |ui| {
+                // exports the chat as it is shown, even if it is not saved yet
+                let active_chat = state.active_chat_id
+                    .and_then(|id| state.open_chats.get(&id))
+                    .filter(|chat| !chat.msg_pool.is_empty());
+                if ui.add_enabled(active_chat.is_some(),
+                        egui::Button::new(t!("menu_export_chat_markdown_btn")))
+                        .on_hover_text(t!("export_chat_tooltip"))
+                        .clicked() {
+                    ui.close();
+                    if let Some(chat) = active_chat {
+                        let (title, markdown) = (chat.title.clone(),
+                            chat_to_markdown(chat));
+                        crate::gui::start_markdown_export(state, &title, markdown);
+                    }
+                }
+            });
+
+            // thought process of every reply in the active chat at once
+            ui.menu_button(t!("menu_reasoning"), |ui| {
+                let active_chat_id = state.active_chat_id
+                    .filter(|id| state.open_chats.contains_key(id));
+                ui.add_enabled_ui(active_chat_id.is_some(), |ui| {
+                    let mut open = None;
+                    if ui.button(t!("collapse_all_reasoning_btn")).clicked() {
+                        open = Some(false);
+                    }
+                    if ui.button(t!("expand_all_reasoning_btn")).clicked() {
+                        open = Some(true);
+                    }
+                    if let (Some(open), Some(chat_id)) = (open, active_chat_id) {
+                        crate::gui::chat::set_all_reasoning_open(state, chat_id, open);
+                        ui.close();
+                    }
+                });
+            });
+
             ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                // Read-only toggle, also set by --readonly
+                let mut readonly = *state.perma.readonly.lock().unwrap();
+                let text = if readonly {
+                    egui::RichText::new(t!("readonly_badge"))
+                        .color(ui.visuals().warn_fg_color).strong()
+                } else {
+                    egui::RichText::new("🔓").weak()
+                };
+                if ui.toggle_value(&mut readonly, text)
+                        .on_hover_text(t!("readonly_tooltip")).changed() {
+                    match crate::db::set_readonly(&state.db_conn, readonly) {
+                        Ok(()) => *state.perma.readonly.lock().unwrap() = readonly,
+                        Err(e) => {
+                            state.error_msg = Some(t!("error_switching_readonly",
+                                error = e).to_string());
+                            state.is_modal_open = true;
+                        }
+                    }
+                }
+
                 if let Some(root) = &state.project_root {
                     // 1. Get the absolute path (fallback to the original root if it fails)
                     let abs_path = std::path::absolute(root).unwrap_or_else(|_| root.clone());
@@ -185,13 +304,29 @@ pub fn ui_top_panel(ctx: &egui::Context, state: &mut State) {
                         .to_string_lossy();    // Converts OsStr to a usable string type
 
                     ui.label(egui::RichText::new(format!("📁 {}", folder_name)).strong())
-                        .on_hover_text(format!("Active Project Root:\n{}", abs_path.display()));
+                        .on_hover_text(t!("project_root_tooltip", path = abs_path.display()));
                 } else {
-                    ui.label(egui::RichText::new("🏠 No Project").weak())
-                        .on_hover_text("No active project. Using default global sandbox.");
+                    ui.label(egui::RichText::new(t!("no_project")).weak())
+                        .on_hover_text(t!("no_project_tooltip"));
                 }
             });
 
         });
+
+        // without the cache db the model autocomplete lists stay empty, and
+        // with the in-memory fallback nothing fetched outlives the session
+        if state.cache_conn.is_none() || state.cache_in_memory {
+            let text = if state.cache_in_memory {
+                t!("cache_db_in_memory")
+            } else {
+                t!("cache_db_unavailable")
+            };
+            ui.horizontal(|ui| {
+                ui.colored_label(ui.visuals().warn_fg_color, text);
+                if ui.button(t!("cache_db_retry_btn")).clicked() {
+                    crate::gui::retry_cache_conn(state);
+                }
+            });
+        }
     });
 }
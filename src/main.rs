@@ -14,6 +14,7 @@ rust_i18n::i18n!("locales");
 
 mod common;
 mod openr;
+mod openai;
 mod ollama;
 mod gui;
 mod db;
@@ -29,6 +30,12 @@ struct Args {
     sandbox: Option<String>,
     #[arg(long)]
     la: Option<String>,
+    #[arg(long)]
+    // Open the Sandbox for viewing only: nothing is sent, edited or saved
+    readonly: bool,
+    #[arg(long)]
+    // Append every request and streamed chunk to this JSONL file
+    debug_log: Option<PathBuf>,
     // Optional project directory to load a local Sandbox from
     #[arg(required = false)]
     project_dir: Option<String>,
@@ -42,6 +49,17 @@ fn main() -> eframe::Result {
 
     let args = Args::parse();
 
+    if let Some(path) = &args.debug_log {
+        match std::fs::OpenOptions::new().create(true).append(true).open(path) {
+            Ok(file) => {
+                let _ = common::DEBUG_LOG.set(std::sync::Mutex::new(file));
+                println!("Logging requests and replies to {}", path.display());
+            }
+            Err(e) => eprintln!("Could not open the debug log {}: {}",
+                path.display(), e),
+        }
+    }
+
     // create the tokio runtime
     let rt = Runtime::new().expect("Unable to create Runtime");
 
@@ -69,7 +87,7 @@ fn main() -> eframe::Result {
 
     let rt_handle = rt.handle().clone();
 
-    eframe::run_native(
+    let result = eframe::run_native(
         "inforno",
         native_options,
         Box::new(move |cc| {
@@ -81,27 +99,86 @@ fn main() -> eframe::Result {
                     app_language = saved_lang;
                 }
             }
+            let available = rust_i18n::available_locales!();
             if let Some(la) = args.la {
-                match la.as_str() {
-                    "ru" | "en" => app_language = la,
-                    _ => {
-                        eprintln!("Warning: Unsupported language '{}'.
-                            Supported: 'en', 'ru'.", la);
-                    }
+                if available.contains(&la.as_str()) {
+                    app_language = la;
+                } else {
+                    eprintln!("Warning: Unsupported language '{}'. Supported: {}.",
+                        la, available.join(", "));
                 }
             }
+            // a saved language whose locale file is gone
+            if !available.contains(&app_language.as_str()) {
+                app_language = "en".to_string();
+            }
             rust_i18n::set_locale(&app_language);
 
-            // theme persistence
-            if let Some(theme) = args.theme {
-                println!("{}", theme);
-                if theme == "light" {
-                    cc.egui_ctx.set_theme(egui::Theme::Light);
-                } else if theme == "dark" {
-                    cc.egui_ctx.set_theme(egui::Theme::Dark);
-                }
+            let models_stale_days = cc.storage.and_then(|storage|
+                    eframe::get_value::<i64>(storage, "models_stale_days"))
+                .unwrap_or(gui::DEFAULT_MODELS_STALE_DAYS);
+
+            let auto_title = cc.storage.and_then(|storage|
+                    eframe::get_value::<bool>(storage, "auto_title"))
+                .unwrap_or(false);
+
+            let expand_reasoning = cc.storage.and_then(|storage|
+                    eframe::get_value::<bool>(storage, "expand_reasoning"))
+                .unwrap_or(true);
+
+            let column_widths = cc.storage.and_then(|storage|
+                    eframe::get_value::<(f32, f32)>(storage, "column_widths"))
+                .map(|(min, max)| {
+                    let range = gui::COLUMN_WIDTH_RANGE;
+                    let min = min.clamp(*range.start(), *range.end());
+                    (min, max.clamp(min, *range.end()))
+                })
+                .unwrap_or(gui::DEFAULT_COLUMN_WIDTHS);
+
+            let compact_chat = cc.storage.and_then(|storage|
+                    eframe::get_value::<bool>(storage, "compact_chat"))
+                .unwrap_or(false);
+
+            let stack_agents = cc.storage.and_then(|storage|
+                    eframe::get_value::<bool>(storage, "stack_agents"))
+                .unwrap_or(false);
+
+            let prompt_char_limit = cc.storage.and_then(|storage|
+                    eframe::get_value::<usize>(storage, "prompt_char_limit"))
+                .unwrap_or(0);
+
+            let api_key_names = cc.storage.and_then(|storage|
+                    eframe::get_value::<Vec<String>>(storage, "api_key_names"))
+                .unwrap_or_default();
+
+            let abort_all_on_error = cc.storage.and_then(|storage|
+                    eframe::get_value::<bool>(storage, "abort_all_on_error"))
+                .unwrap_or(false);
+
+            let bottom_panel_layout = cc.storage.and_then(|storage|
+                    eframe::get_value::<gui::BottomPanelLayout>(
+                        storage, "bottom_panel_layout"))
+                .unwrap_or_default();
+
+            // Ollama server address persistence
+            if let Some(saved_host) = cc.storage.and_then(|storage|
+                    eframe::get_value::<common::OllamaHost>(storage, "ollama_host")) {
+                *common::OLLAMA_HOST.write().unwrap() = saved_host;
             }
 
+            // theme persistence: the saved choice, then --theme, then the
+            // system one
+            let cli_theme = match args.theme.as_deref() {
+                Some("light") => egui::ThemePreference::Light,
+                Some("dark") => egui::ThemePreference::Dark,
+                _ => egui::ThemePreference::System,
+            };
+            let theme_preference = cc.storage.and_then(|storage|
+                    eframe::get_value::<egui::ThemePreference>(storage,
+                        "theme_preference"))
+                .unwrap_or(cli_theme);
+            cc.egui_ctx.set_theme(theme_preference);
+
             cc.egui_ctx.style_mut(|style| {
                 // Show the url of a hyperlink on hover
                 style.url_in_tooltip = true;
@@ -132,9 +209,28 @@ fn main() -> eframe::Result {
                 sandbox,
                 pending_project_init: std::sync::Mutex::new(pending_project_init),
                 app_language: std::sync::Mutex::new(app_language),
+                models_stale_days: std::sync::Mutex::new(models_stale_days),
+                theme_preference: std::sync::Mutex::new(theme_preference),
+                auto_title: std::sync::Mutex::new(auto_title),
+                expand_reasoning: std::sync::Mutex::new(expand_reasoning),
+                column_widths: std::sync::Mutex::new(column_widths),
+                compact_chat: std::sync::Mutex::new(compact_chat),
+                stack_agents: std::sync::Mutex::new(stack_agents),
+                prompt_char_limit: std::sync::Mutex::new(prompt_char_limit),
+                api_key_names: std::sync::Mutex::new(api_key_names),
+                abort_all_on_error: std::sync::Mutex::new(abort_all_on_error),
+                bottom_panel_layout: std::sync::Mutex::new(bottom_panel_layout),
+                readonly: std::sync::Mutex::new(args.readonly),
             })))
         }),
-    )
+    );
+
+    // streams were told to abort in on_exit, give them a moment to close
+    // their connections before the runtime drops them
+    drop(_enter);
+    rt.shutdown_timeout(std::time::Duration::from_millis(500));
+
+    result
 }
 
 fn configure_fonts(ctx: &egui::Context) {
@@ -1,49 +1,88 @@
-use ollama_rs::{Ollama, error::OllamaError, generation::chat::{ChatMessageResponse, request::ChatMessageRequest}, models::ModelOptions};
+use ollama_rs::{Ollama, error::OllamaError, generation::{chat::{ChatMessageResponse, request::ChatMessageRequest}, parameters::{FormatType, KeepAlive}}, models::ModelOptions};
 
-use crate::common::{ChatQue, ChatStreamEvent, DbOllamaModel};
+use crate::common::{ChatQue, ChatStreamEvent, DbOllamaModel, MsgUsage, MyError, OLLAMA_HOST, ResponseFormat, Utf8Buffer, debug_log, next_or_timeout, or_timeout, timeout_error};
 use std::sync::{Arc, atomic::{AtomicBool, Ordering}, mpsc::Sender};
-use tokio_stream::StreamExt;
 
-pub async fn do_ollama_chat_que(query: ChatQue) ->
-        Result<ChatMessageResponse, OllamaError> {
-    let ollama = Ollama::default();
+/// Client for the configured Ollama server, fails on an invalid host setting
+pub fn ollama_client() -> Result<Ollama, MyError> {
+    let host = OLLAMA_HOST.read().unwrap().clone();
+    let url = host.url()?;
+    Ok(Ollama::new(url.as_str(), host.port))
+}
 
-    let mut options = ModelOptions::default();
-        //.temperature(query.preset.options.temperature)
-        //.repeat_penalty(1.5)
-        //.top_k(0)
-        //.top_p(1.0);
-    if let Some(t) = query.preset.options.temperature {
-        if t >= 0.0 && t <= 2.0 {
-            options = options.temperature(t as f32);
-        }
-    }
-    if let Some(seed) = query.preset.options.seed {
-        options = options.seed(seed as i32);
+/// Explains a failed request in terms the user can act on: a refused
+/// connection means the Ollama server is not running at the configured host
+pub fn describe_error(e: &OllamaError) -> String {
+    match e {
+        OllamaError::ReqwestError(err) if err.is_connect() => not_running_error(),
+        OllamaError::ReqwestError(err) => format!("Request to Ollama failed: {}", err),
+        _ => e.to_string(),
     }
+}
 
-    // create the Request
-    let request = ChatMessageRequest::new(
-        query.preset.model,
-        query.chat.to_ollama_messages(0),
-    ).options(options);
+fn not_running_error() -> String {
+    let host = OLLAMA_HOST.read().unwrap().clone();
+    format!("Ollama is not running at {}:{}. Start it with `ollama serve` \
+        or check the server address in Settings", host.host, host.port)
+}
 
-    // send
-    ollama.send_chat_messages(request).await
+/// Parses a keep_alive value the way ollama accepts it: "-1" keeps the model
+/// loaded forever, "0" unloads it right after the reply, and a whole number
+/// followed by s, m or h ("30s", "5m", "2h") keeps it for that long
+pub fn parse_keep_alive(text: &str) -> Option<KeepAlive> {
+    serde_json::from_value(serde_json::Value::String(text.trim().to_string()))
+        .ok()
 }
 
-#[tracing::instrument(skip_all)]
-pub async fn do_ollama_chat_stream(
-    query: ChatQue,
-    tx: Sender<ChatStreamEvent>,
-    ctx: &egui::Context,
-    abort_flag: Arc<AtomicBool>,
-) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    let ollama = Ollama::default();
+/// Attaches the preset's keep_alive to the request, if it has a valid one
+fn apply_keep_alive(request: ChatMessageRequest, query: &ChatQue) ->
+        Result<ChatMessageRequest, String> {
+    let Some(text) = &query.preset.options.keep_alive else {
+        return Ok(request);
+    };
+    match parse_keep_alive(text) {
+        Some(keep_alive) => Ok(request.keep_alive(keep_alive)),
+        None => Err(format!("Preset '{}' has an invalid keep_alive: {:?}",
+            query.preset.title, text)),
+    }
+}
+
+// simple request without streaming, the whole reply arrives at once
+pub async fn do_ollama_chat_que(query: ChatQue) ->
+        Result<ChatMessageResponse, OllamaError> {
+    let ollama = ollama_client()
+        .map_err(|e| OllamaError::Other(e.to_string()))?;
+    let request = build_chat_request(&query).map_err(OllamaError::Other)?;
+    debug_log(query.agent_ind, "request", "", || serde_json::json!({
+        "router": query.preset.chat_router.to_string(),
+        "host": OLLAMA_HOST.read().map(|h| format!("{}:{}", h.host, h.port))
+            .unwrap_or_default(),
+        "request": request,
+    }));
+    let response = ollama.send_chat_messages(request).await;
+    // the response type can't be serialized as a whole
+    debug_log(query.agent_ind, "response", "", || match &response {
+        Ok(response) => serde_json::json!({
+            "model": response.model,
+            "message": response.message,
+            "done": response.done,
+            "prompt_eval_count": response.final_data.as_ref()
+                .map(|data| data.prompt_eval_count),
+            "eval_count": response.final_data.as_ref()
+                .map(|data| data.eval_count),
+        }),
+        Err(e) => serde_json::json!({ "error": e.to_string() }),
+    });
+    response
+}
+
+/// Builds the request from the preset, shared by streaming and non-streaming
+fn build_chat_request(query: &ChatQue) -> Result<ChatMessageRequest, String> {
     let model_name = query.preset.model.clone();
-    let messages = query.chat.to_ollama_messages(query.agent_ind);
+    let messages = query.chat.to_ollama_messages(query.agent_ind,
+        query.preset.options.history_limit);
 
-    // 1. Prepare the ModelOptions (Seed & Temperature)
+    // 1. Prepare the ModelOptions (Sampling & Max Tokens)
     let mut options = ModelOptions::default();
 
     if let Some(seed) = query.preset.options.seed {
@@ -56,11 +95,39 @@ pub async fn do_ollama_chat_stream(
         options = options.temperature(temp as f32);
     }
 
-    options = options.top_k(0).top_p(1.0);
+    if let Some(max_tokens) = query.preset.options.max_tokens {
+        // ollama calls it num_predict and uses i32
+        options = options.num_predict(max_tokens.min(i32::MAX as u32) as i32);
+    }
+
+    if let Some(top_p) = query.preset.options.top_p {
+        options = options.top_p(top_p as f32);
+    }
+
+    if let Some(top_k) = query.preset.options.top_k {
+        options = options.top_k(top_k.max(0) as u32);
+    }
+
+    // ollama has no frequency/presence penalty, only the multiplicative
+    // repeat_penalty (1.0 = off). As a best-effort approximation we fold both
+    // additive penalties (-2.0..=2.0 each) into it: their sum over 4 added to
+    // 1.0 maps the combined range onto 0.0..=2.0.
+    let freq = query.preset.options.frequency_penalty;
+    let pres = query.preset.options.presence_penalty;
+    if freq.is_some() || pres.is_some() {
+        let sum = freq.unwrap_or(0.0) + pres.unwrap_or(0.0);
+        options = options.repeat_penalty((1.0 + sum / 4.0) as f32);
+    }
+
+    let stop = query.preset.options.stop_sequences();
+    if !stop.is_empty() {
+        options = options.stop(stop);
+    }
 
     // 2. Create the Request and attach Options
-    let mut request = ChatMessageRequest::new(model_name, messages)
-        .options(options);
+    let mut request = apply_keep_alive(
+        ChatMessageRequest::new(model_name, messages).options(options),
+        query)?;
 
     // 3. Conditional: Apply "Thinking"
     // Assuming your version of ollama_rs has the .think() method as shown in your snippet
@@ -78,50 +145,195 @@ pub async fn do_ollama_chat_stream(
         }
     }
 
-    let mut stream = ollama.send_chat_messages_stream(request).await?;
+    // 4. Conditional: Apply the Response Format, ollama takes the schema
+    // itself where openrouter wraps it
+    match &query.preset.options.response_format {
+        Some(ResponseFormat::JsonObject) => {
+            request = request.format(FormatType::Json);
+        }
+        Some(format @ ResponseFormat::JsonSchema(_)) => {
+            let format = format.schema()
+                .and_then(|schema| serde_json::from_value(schema).ok());
+            if let Some(format) = format {
+                request = request.format(format);
+            }
+        }
+        None => {}
+    }
+    Ok(request)
+}
+
+/// Sends what a response (a whole reply or one streamed chunk) carries.
+/// Ollama doesn't say why it stopped, so a reply as long as `max_tokens`
+/// counts as cut off.
+pub fn send_response_events(
+    response: ChatMessageResponse,
+    agent_ind: usize,
+    max_tokens: Option<u32>,
+    tx: &Sender<ChatStreamEvent>,
+    ctx: &egui::Context,
+) {
+    // the last response carries the token counts
+    if let Some(final_data) = &response.final_data {
+        let _ = tx.send(ChatStreamEvent::Usage(agent_ind,
+            MsgUsage {
+                prompt_tokens: final_data.prompt_eval_count as i64,
+                completion_tokens: final_data.eval_count as i64,
+                cost: None, // local models are free
+                // eval_duration is in nanoseconds and leaves out the
+                // prompt processing, so this is the pure generation speed
+                tokens_per_sec: (final_data.eval_duration > 0).then(||
+                    final_data.eval_count as f64
+                    / (final_data.eval_duration as f64 / 1e9)),
+            }));
+        if max_tokens.is_some_and(|max| final_data.eval_count >= max as u64) {
+            let _ = tx.send(ChatStreamEvent::Truncated(agent_ind));
+        }
+    }
+    let msg = response.message;
+    if !msg.content.is_empty() {
+        let _ = tx.send(ChatStreamEvent::Content(
+            agent_ind,
+            msg.content
+        ));
+        ctx.request_repaint();
+    }
+    if let Some(thinking) = &msg.thinking {
+        if !thinking.is_empty() {
+            let _ = tx.send(ChatStreamEvent::Reasoning(
+                agent_ind,
+                thinking.to_string(),
+            ));
+            ctx.request_repaint();
+        }
+    }
+}
+
+#[tracing::instrument(skip_all)]
+pub async fn do_ollama_chat_stream(
+    query: ChatQue,
+    tx: Sender<ChatStreamEvent>,
+    ctx: &egui::Context,
+    abort_flag: Arc<AtomicBool>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let request = build_chat_request(&query)?;
+    debug_log(query.agent_ind, "request", "", || serde_json::json!({
+        "router": query.preset.chat_router.to_string(),
+        "host": OLLAMA_HOST.read().map(|h| format!("{}:{}", h.host, h.port))
+            .unwrap_or_default(),
+        "request": request,
+    }));
 
-    while let Some(res) = stream.next().await {
+    let timeout = query.preset.options.stream_timeout();
+    let response = match or_timeout(send_chat_stream_request(&request),
+            timeout).await {
+        Ok(response) => response?,
+        Err(_) => {
+            let _ = tx.send(ChatStreamEvent::Error(query.agent_ind,
+                timeout_error(timeout)));
+            ctx.request_repaint();
+            return Ok(());
+        }
+    };
+    let chunks = futures_util::stream::unfold(response, |mut response| async move {
+        response.chunk().await.transpose().map(|chunk| (chunk, response))
+    });
+    let mut chunks = std::pin::pin!(chunks);
+
+    let mut utf8 = Utf8Buffer::default();
+    let mut lines = String::new();
+    let mut broken = false;
+    while let Some(res) = match next_or_timeout(&mut chunks, timeout).await {
+        Ok(res) => res,
+        Err(_) => {
+            let _ = tx.send(ChatStreamEvent::Error(query.agent_ind,
+                timeout_error(timeout)));
+            None // ends the loop
+        }
+    } {
         if abort_flag.load(Ordering::Relaxed) {
             println!("Agent {} stream aborted by user.", query.agent_ind);
             break;
         }
         match res {
-            Ok(response) => {
-                let msg = response.message;
-                if !msg.content.is_empty() {
-                    let _ = tx.send(ChatStreamEvent::Content(
-                        query.agent_ind,
-                        msg.content
-                    ));
-                    ctx.request_repaint();
-                }
-                if let Some(thinking) = &msg.thinking {
-                    if !thinking.is_empty() {
-                        let _ = tx.send(ChatStreamEvent::Reasoning(
-                            query.agent_ind,
-                            thinking.to_string(),
-                        ));
-                        ctx.request_repaint();
-                    }
-                }
-            }
+            Ok(chunk) => lines.push_str(&utf8.push(&chunk)),
+            // the connection is gone, what came so far is all there is
             Err(e) => {
-                let _ = tx.send(ChatStreamEvent::Error(
-                    query.agent_ind,
-                    format!("Ollama stream error: {:?}", e)
-                ));
+                let _ = tx.send(ChatStreamEvent::Error(query.agent_ind,
+                    format!("Error: Ollama stream error: {}", e)));
                 ctx.request_repaint();
+                broken = true;
+                break;
             }
         }
+        // every response is one line of json
+        while let Some(end) = lines.find('\n') {
+            let line: String = lines.drain(..=end).collect();
+            send_stream_line(line.trim(), &query, &tx, ctx);
+        }
+    }
+    // a line cut off by a broken connection is not worth a warning
+    if !broken && !abort_flag.load(Ordering::Relaxed) {
+        lines.push_str(&utf8.finish());
+        send_stream_line(lines.trim(), &query, &tx, ctx);
     }
     println!("Finished stream from Ollama");
     ctx.request_repaint();
     Ok(())
 }
 
+// ollama-rs drops any http chunk that is not valid utf-8 by itself, and
+// with it a character split between two chunks, so the chat stream is
+// read here instead
+async fn send_chat_stream_request(request: &ChatMessageRequest)
+        -> Result<reqwest::Response, Box<dyn std::error::Error + Send + Sync>> {
+    let url = ollama_client()?.url().join("api/chat")?;
+    let mut body = serde_json::to_value(request)?;
+    body["stream"] = serde_json::Value::Bool(true);
+    let response = reqwest::Client::new().post(url).json(&body).send().await
+        .map_err(|e| -> Box<dyn std::error::Error + Send + Sync> {
+            if e.is_connect() {
+                not_running_error().into()
+            } else {
+                e.into()
+            }
+        })?;
+    if !response.status().is_success() {
+        let status = response.status();
+        let text = response.text().await.unwrap_or_else(|e| e.to_string());
+        // ollama explains a refused request as {"error": "..."}
+        let message = serde_json::from_str::<serde_json::Value>(&text).ok()
+            .and_then(|body| body.get("error")?.as_str().map(str::to_string))
+            .unwrap_or(text);
+        return Err(format!("{}: {}", status, message).into());
+    }
+    Ok(response)
+}
+
+// a broken line is reported and skipped, the stream goes on
+fn send_stream_line(line: &str, query: &ChatQue, tx: &Sender<ChatStreamEvent>,
+        ctx: &egui::Context) {
+    if line.is_empty() {
+        return;
+    }
+    debug_log(query.agent_ind, "chunk", "", || serde_json::from_str(line)
+        .unwrap_or_else(|_| serde_json::json!(line)));
+    match serde_json::from_str::<ChatMessageResponse>(line) {
+        Ok(response) => send_response_events(response, query.agent_ind,
+            query.preset.options.max_tokens, tx, ctx),
+        Err(e) => {
+            let _ = tx.send(ChatStreamEvent::Warning(
+                query.agent_ind,
+                format!("Ollama stream error: {}: {}", e, line)
+            ));
+            ctx.request_repaint();
+        }
+    }
+}
+
 pub async fn ollama_fetch_models() -> Result<Vec<DbOllamaModel>,
         Box<dyn std::error::Error>> {
-    let ollama = Ollama::default();
+    let ollama = ollama_client()?;
     let models = ollama.list_local_models().await?;
 
     let db_models = models
@@ -130,7 +342,7 @@ pub async fn ollama_fetch_models() -> Result<Vec<DbOllamaModel>,
             DbOllamaModel {
                 id: 0,
                 name: item.name,
-                // size: item.size.min(i64::MAX as u64) as i64,
+                size: item.size.min(i64::MAX as u64) as i64,
                 ts_model: Some(item.modified_at),
                 ..Default::default()
             }
@@ -0,0 +1,52 @@
+/*
+streaming from any server with an OpenAI compatible chat completions api,
+e.g. vLLM, llama.cpp server, LM Studio. The preset's base_url points at it,
+something like http://192.168.1.10:8000/v1
+*/
+
+use std::sync::{Arc, atomic::AtomicBool, mpsc::Sender};
+
+use openrouter_rs::{OpenRouterClient, types::CompletionsResponse};
+use secrecy::ExposeSecret;
+
+use crate::common::{ChatQue, ChatStreamEvent, Preset};
+
+// local servers usually run without a key, but the client insists on one
+const NO_API_KEY: &str = "EMPTY";
+
+/// Client for the preset's server, fails when it has no base url
+fn openai_client(preset: &Preset) ->
+        Result<OpenRouterClient, Box<dyn std::error::Error + Send + Sync>> {
+    let base_url = preset.base_url.trim().trim_end_matches('/');
+    if base_url.is_empty() {
+        return Err(format!("Preset '{}' has no server base url",
+            preset.title).into());
+    }
+
+    let api_key = preset.api_key.key.expose_secret();
+    Ok(OpenRouterClient::builder()
+        .base_url(base_url)
+        .api_key(if api_key.is_empty() { NO_API_KEY } else { api_key })
+        .build()?)
+}
+
+// simple request without streaming, the whole reply arrives at once
+pub async fn do_openai_chat_que(query: ChatQue) ->
+        Result<CompletionsResponse, Box<dyn std::error::Error + Send + Sync>> {
+    let client = openai_client(&query.preset)?;
+    Ok(crate::openr::complete_chat(&client, &query).await?)
+}
+
+#[tracing::instrument(skip_all)]
+pub async fn do_openai_chat_stream(
+    query: ChatQue,
+    tx: Sender<ChatStreamEvent>,
+    ctx: &egui::Context,
+    abort_flag: Arc<AtomicBool>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let client = openai_client(&query.preset)?;
+    crate::openr::stream_chat_completion(&client, query, tx, ctx, abort_flag)
+        .await?;
+    println!("Finished stream from OpenAI compatible server");
+    Ok(())
+}
@@ -6,17 +6,20 @@ openrouter_api = { version = "0.1.6", features = ["tracing"] }
 tokio = "1.47.1"
 */
 
-use futures_util::StreamExt;
 use std::sync::{Arc, atomic::{AtomicBool, Ordering}, mpsc::Sender};
 
-use openrouter_rs::{OpenRouterClient, api::chat::*, types::{CompletionsResponse, Effort}};
+use openrouter_rs::{OpenRouterClient, api::chat::*, error::OpenRouterError, types::{CompletionsResponse, Effort, FinishReason}};
 use secrecy::ExposeSecret;
 
-use crate::common::{ApiKey, ChatQue, ChatStreamEvent, DbOpenrModel, mask_key_secure};
+use crate::common::{ApiKey, ChatQue, ChatStreamEvent, DbOpenrModel, debug_log, MsgUsage, ReasoningEffort, ResponseFormat, mask_key_secure, next_or_timeout, or_timeout, timeout_error};
 
-// simple request without streaming or history
+// how many times a stream is started before giving up on 429/5xx/network errors
+const MAX_STREAM_ATTEMPTS: u32 = 3;
+const RETRY_BASE_DELAY: std::time::Duration = std::time::Duration::from_secs(1);
+
+// simple request without streaming, the whole reply arrives at once
 pub async fn do_openr_chat_que(query: ChatQue) ->
-        Result<CompletionsResponse, Box<dyn std::error::Error>> {
+        Result<CompletionsResponse, OpenRouterError> {
 
     // print the frist two and last two characters of the key in case we are not
     // sure whether the right key is used
@@ -28,15 +31,27 @@ pub async fn do_openr_chat_que(query: ChatQue) ->
         .api_key(query.preset.api_key.key.expose_secret())
         .build()?;
 
-    // Send chat completion
-    let request = ChatCompletionRequest::builder()
-    .model(query.preset.model)
-    // Pass '0' or a variable like 'current_hist_id' here
-    .messages(query.chat.to_openrouter_messages(0))
-    .build()?;
+    complete_chat(&client, &query).await
+}
 
-    let response = client.send_chat_completion(&request).await?;
-    Ok(response)
+/// Sends the request built from the preset and waits for the whole reply.
+/// Shared with the OpenAI compatible router, like `stream_chat_completion`.
+pub async fn complete_chat(client: &OpenRouterClient, query: &ChatQue) ->
+        Result<CompletionsResponse, OpenRouterError> {
+    let chat_request = build_chat_request(query, false)?;
+    let key = query.preset.api_key.key.expose_secret();
+    debug_log(query.agent_ind, "request", key, || serde_json::json!({
+        "router": query.preset.chat_router.to_string(),
+        "base_url": query.preset.base_url,
+        "key": "[REDACTED]",
+        "request": chat_request,
+    }));
+    let response = client.send_chat_completion(&chat_request).await;
+    debug_log(query.agent_ind, "response", key, || match &response {
+        Ok(response) => serde_json::json!(response),
+        Err(e) => serde_json::json!({ "error": e.to_string() }),
+    });
+    response
 }
 
 #[tracing::instrument(skip_all)]
@@ -53,17 +68,74 @@ pub async fn do_openr_chat_stream(
         .api_key(query.preset.api_key.key.expose_secret())
         .build()?;
 
+    stream_chat_completion(&client, query, tx, ctx, abort_flag).await?;
+    println!("Finished stream from OpenRouter");
+    Ok(())
+}
+
+/// Builds the request from the preset and streams the reply through `tx`.
+/// Shared with the OpenAI compatible router, which speaks the same protocol
+/// and only differs in the client's base url.
+pub async fn stream_chat_completion(
+    client: &OpenRouterClient,
+    query: ChatQue,
+    tx: Sender<ChatStreamEvent>,
+    ctx: &egui::Context,
+    abort_flag: Arc<AtomicBool>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let chat_request = build_chat_request(&query, true)?;
+
+    let agent_ind = query.agent_ind;
+    let key = query.preset.api_key.key.expose_secret();
+    debug_log(agent_ind, "request", key, || serde_json::json!({
+        "router": query.preset.chat_router.to_string(),
+        "base_url": query.preset.base_url,
+        "key": "[REDACTED]",
+        "request": chat_request,
+    }));
+    for attempt in 1..=MAX_STREAM_ATTEMPTS {
+        match stream_attempt(client, &chat_request, agent_ind, &tx, ctx,
+                &abort_flag, query.preset.options.stream_timeout()).await {
+            Ok(()) => break,
+            Err(e) if attempt < MAX_STREAM_ATTEMPTS && is_retryable(&e)
+                    && !abort_flag.load(Ordering::Relaxed) => {
+                println!("Stream attempt {} failed: {}", attempt, e);
+                let _ = tx.send(ChatStreamEvent::Warning(agent_ind,
+                    format!("Retrying ({}/{})...", attempt + 1,
+                        MAX_STREAM_ATTEMPTS)));
+                ctx.request_repaint();
+                // back off a bit longer every time: 1s, 2s, 4s...
+                tokio::time::sleep(RETRY_BASE_DELAY * 2u32.pow(attempt - 1))
+                    .await;
+            }
+            Err(e) => return Err(e.into()),
+        }
+    }
+    ctx.request_repaint();
+    Ok(())
+}
+
+/// Builds the request from the preset, `stream` also asks for the token
+/// usage in the final chunk
+fn build_chat_request(query: &ChatQue, stream: bool) ->
+        Result<ChatCompletionRequest, OpenRouterError> {
     // 1. Start the builder with mandatory fields
     let mut request_builder = ChatCompletionRequest::builder();
     request_builder
-        .model(query.preset.model)
-        .messages(query.chat.to_openrouter_messages(query.agent_ind));
+        .model(query.preset.model.clone())
+        .messages(query.chat.to_openrouter_messages(query.agent_ind,
+            query.preset.options.history_limit));
 
     // 2. Conditional: Apply Reasoning
     match query.preset.options.include_reasoning {
         Some(true) => {
-            // User explicitly wants reasoning -> Force High Effort
-            request_builder.reasoning_effort(Effort::High);
+            // User explicitly wants reasoning -> at the chosen effort
+            request_builder.reasoning_effort(
+                match query.preset.options.effort() {
+                    ReasoningEffort::Low => Effort::Low,
+                    ReasoningEffort::Medium => Effort::Medium,
+                    ReasoningEffort::High => Effort::High,
+                });
         }
         Some(false) => {
             // User explicitly wants NO reasoning -> Disable it
@@ -86,49 +158,208 @@ pub async fn do_openr_chat_stream(
         request_builder.temperature(temp);
     }
 
-    // 5. Finalize build
-    let chat_request = request_builder.build()?;
+    // 5. Conditional: Apply Max Tokens
+    if let Some(max_tokens) = query.preset.options.max_tokens {
+        request_builder.max_tokens(max_tokens);
+    }
+
+    // 6. Conditional: Apply Top P / Top K
+    if let Some(top_p) = query.preset.options.top_p {
+        request_builder.top_p(top_p);
+    }
+    if let Some(top_k) = query.preset.options.top_k {
+        request_builder.top_k(top_k.max(0) as u32);
+    }
+
+    // 7. Conditional: Apply Frequency / Presence Penalty
+    if let Some(penalty) = query.preset.options.frequency_penalty {
+        request_builder.frequency_penalty(penalty);
+    }
+    if let Some(penalty) = query.preset.options.presence_penalty {
+        request_builder.presence_penalty(penalty);
+    }
+
+    // 8. Conditional: Apply Stop Sequences
+    let stop = query.preset.options.stop_sequences();
+    if !stop.is_empty() {
+        request_builder.stop(stop);
+    }
+
+    // 9. Conditional: Apply the Response Format
+    match &query.preset.options.response_format {
+        Some(ResponseFormat::JsonObject) => {
+            request_builder.response_format(
+                openrouter_rs::types::ResponseFormat::json_object());
+        }
+        Some(format @ ResponseFormat::JsonSchema(_)) => {
+            // the editor won't save a schema that doesn't parse
+            if let Some(schema) = format.schema() {
+                request_builder.response_format(
+                    openrouter_rs::types::ResponseFormat::json_schema(
+                        "response", true, schema));
+            }
+        }
+        None => {}
+    }
+
+    // 10. Ask for the token usage in the final chunk
+    if stream {
+        let mut stream_options = StreamOptions::default();
+        stream_options.include_usage = Some(true);
+        request_builder.stream_options(stream_options);
+    }
+
+    // 11. Finalize build
+    request_builder.build()
+}
+
+const OPENROUTER_CHAT_URL: &str = "https://openrouter.ai/api/v1/chat/completions";
+
+/// The request built from the preset as a cURL command, for sharing repros.
+/// It asks for the whole reply at once and reads the key from the
+/// environment, so the command can be pasted without leaking it.
+pub fn curl_command(query: &ChatQue) -> Result<String, OpenRouterError> {
+    let chat_request = build_chat_request(query, false)?;
+    let body = serde_json::to_string_pretty(&chat_request)?;
+    // the body goes in single quotes, so its own are closed, escaped, reopened
+    Ok(format!("curl {} \\\n  -H \"Authorization: Bearer $OPENROUTER_API_KEY\" \\\n  \
+        -H \"Content-Type: application/json\" \\\n  -d '{}'",
+        OPENROUTER_CHAT_URL, body.replace('\'', "'\\''")))
+}
+
+// OpenRouter answered that the key itself is no good
+pub fn is_key_rejected(e: &OpenRouterError) -> bool {
+    matches!(e, OpenRouterError::Api(api_error)
+        if matches!(api_error.status.as_u16(), 401 | 403))
+}
+
+// rate limits, server hiccups and network blips usually go away on their own
+fn is_retryable(e: &OpenRouterError) -> bool {
+    match e {
+        OpenRouterError::Api(api_error) => api_error.is_retryable(),
+        OpenRouterError::HttpRequest(_) => true,
+        _ => false,
+    }
+}
 
-    let mut stream = client.stream_chat_completion(&chat_request).await?;
+/// One try at streaming the reply. Retryable errors are returned (so the
+/// caller can retry) only until the first token arrives. The rest, and any
+/// error after that, are reported through `tx` and the stream goes on, since
+/// a partial reply can't be taken back.
+async fn stream_attempt(
+    client: &OpenRouterClient,
+    chat_request: &ChatCompletionRequest,
+    agent_ind: usize,
+    tx: &Sender<ChatStreamEvent>,
+    ctx: &egui::Context,
+    abort_flag: &AtomicBool,
+    timeout: Option<std::time::Duration>,
+) -> Result<(), OpenRouterError> {
+    let mut stream = match or_timeout(
+            client.stream_chat_completion(chat_request), timeout).await {
+        Ok(stream) => stream?,
+        Err(_) => {
+            let _ = tx.send(ChatStreamEvent::Error(agent_ind,
+                timeout_error(timeout)));
+            ctx.request_repaint();
+            return Ok(());
+        }
+    };
+    let mut got_output = false;
 
-    while let Some(event_result) = stream.next().await {
+    while let Some(event_result) = match next_or_timeout(&mut stream, timeout)
+            .await {
+        Ok(event_result) => event_result,
+        Err(_) => {
+            let _ = tx.send(ChatStreamEvent::Error(agent_ind,
+                timeout_error(timeout)));
+            None // ends the loop
+        }
+    } {
         // 1. CHECK SIGNAL: Stop immediately if flag is true
         if abort_flag.load(Ordering::Relaxed) {
-            println!("OpenRouter stream aborted by user.");
+            println!("Chat completion stream aborted by user.");
             break; // Breaks the loop, dropping 'stream' and closing connection
         }
 
+        debug_log(agent_ind, "chunk", "", || match &event_result {
+            Ok(event) => serde_json::json!(event),
+            Err(e) => serde_json::json!({ "error": e.to_string() }),
+        });
         match event_result {
             Ok(event) => {
-                if let Some(choice) = event.choices.first() {
-                    if let Some(reasoning) = choice.reasoning() {
-                        if !reasoning.is_empty() {
-                            let _ = tx.send(ChatStreamEvent::Reasoning(
-                                    query.agent_ind, reasoning.to_string()));
-                            ctx.request_repaint();
-                        }
-                    }
-                    if let Some(content) = choice.content() {
-                        if !content.is_empty() {
-                            let _ = tx.send(ChatStreamEvent::Content(
-                                    query.agent_ind, content.to_string()));
-                            ctx.request_repaint();
-                        }
-                    }
-                }
+                got_output |= send_response_events(&event, agent_ind, tx, ctx);
             }
+            Err(e) if !got_output && is_retryable(&e) => return Err(e),
             Err(e) => {
-                let _ = tx.send(ChatStreamEvent::Error(
-                            query.agent_ind, e.to_string()));
+                // e.g. a frame that doesn't parse, later chunks may still
+                let _ = tx.send(ChatStreamEvent::Warning(
+                            agent_ind, e.to_string()));
                 ctx.request_repaint();
             }
         }
     }
-    println!("Finished stream from OpenRouter");
-    ctx.request_repaint();
     Ok(())
 }
 
+/// Sends what a response (a whole reply or one streamed chunk) carries,
+/// returns whether it had any text
+pub fn send_response_events(
+    event: &CompletionsResponse,
+    agent_ind: usize,
+    tx: &Sender<ChatStreamEvent>,
+    ctx: &egui::Context,
+) -> bool {
+    let mut got_output = false;
+    if let Some(choice) = event.choices.first() {
+        if let Some(reasoning) = choice.reasoning() {
+            if !reasoning.is_empty() {
+                got_output = true;
+                let _ = tx.send(ChatStreamEvent::Reasoning(
+                        agent_ind, reasoning.to_string()));
+                ctx.request_repaint();
+            }
+        }
+        if let Some(content) = choice.content() {
+            if !content.is_empty() {
+                got_output = true;
+                let _ = tx.send(ChatStreamEvent::Content(
+                        agent_ind, content.to_string()));
+                ctx.request_repaint();
+            }
+        }
+        if matches!(choice.finish_reason(), Some(FinishReason::Length)) {
+            let _ = tx.send(ChatStreamEvent::Truncated(agent_ind));
+        }
+    }
+    // usage only comes with the final chunk
+    if let Some(usage) = &event.usage {
+        let _ = tx.send(ChatStreamEvent::Usage(agent_ind,
+            MsgUsage {
+                prompt_tokens: usage.prompt_tokens as i64,
+                completion_tokens: usage.completion_tokens as i64,
+                cost: usage.cost,
+                tokens_per_sec: None, // worked out from the elapsed time
+            }));
+    }
+    got_output
+}
+
+// checks that the key is accepted and returns the remaining balance: the
+// account credits if the key may read them, otherwise the key's own limit
+pub async fn openr_check_key(api_key: &ApiKey) -> Result<Option<f64>,
+        OpenRouterError> {
+    let client = OpenRouterClient::builder()
+        .api_key(api_key.key.expose_secret())
+        .build()?;
+
+    let key_info = client.get_current_api_key_info().await?;
+    Ok(match client.get_credits().await {
+        Ok(credits) => Some(credits.total_credits - credits.total_usage),
+        Err(_) => key_info.limit_remaining,
+    })
+}
+
 pub async fn openr_fetch_models(api_key: &ApiKey) -> Result<Vec<DbOpenrModel>,
         openrouter_rs::error::OpenRouterError> {
     // Create an OpenRouter client.